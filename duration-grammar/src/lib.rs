@@ -0,0 +1,100 @@
+//! The one duration grammar, read by `retryable::parse_duration` and by the
+//! `"250ms"` options of the `retryable` and `timeit` macros
+
+/// The nanoseconds in a duration like `250ms`, `1m30s`, `1.5h` or `30` (a bare
+/// number is seconds), `None` if it isn't one
+///
+/// Each part is a number, possibly with a fraction, followed by a unit (`ns`,
+/// `us`, `ms`, `s`, `m` or `h`). Whitespace around the duration is ignored.
+pub const fn parse_nanos(s: &str) -> Option<u64> {
+    let s = s.as_bytes();
+    let (mut i, mut end) = (0, s.len());
+    while i < end && s[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    while end > i && s[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+    if i == end {
+        return None;
+    }
+    let (mut total, mut parts): (u128, u32) = (0, 0);
+    while i < end {
+        let mut digits = 0;
+        let mut whole: u128 = 0;
+        while i < end && s[i].is_ascii_digit() {
+            whole = whole * 10 + (s[i] - b'0') as u128;
+            if whole > u64::MAX as u128 {
+                return None;
+            }
+            digits += 1;
+            i += 1;
+        }
+        // Digits past the 18th of a fraction are below a nanosecond anyway
+        let (mut fraction, mut scale): (u128, u128) = (0, 1);
+        if i < end && s[i] == b'.' {
+            i += 1;
+            while i < end && s[i].is_ascii_digit() {
+                if scale < 1_000_000_000_000_000_000 {
+                    fraction = fraction * 10 + (s[i] - b'0') as u128;
+                    scale *= 10;
+                }
+                digits += 1;
+                i += 1;
+            }
+        }
+        if digits == 0 {
+            return None;
+        }
+        let start = i;
+        while i < end && !s[i].is_ascii_digit() {
+            i += 1;
+        }
+        let (_, rest) = s.split_at(start);
+        let (unit, _) = rest.split_at(i - start);
+        let nanos_per_unit: u128 = match unit {
+            b"ns" => 1,
+            b"us" => 1_000,
+            b"ms" => 1_000_000,
+            b"s" => 1_000_000_000,
+            b"m" => 60_000_000_000,
+            b"h" => 3_600_000_000_000,
+            // A bare number, when it's all there is
+            b"" if start == end && parts == 0 => 1_000_000_000,
+            _ => return None,
+        };
+        parts += 1;
+        total += whole * nanos_per_unit + fraction * nanos_per_unit / scale;
+        if total > u64::MAX as u128 {
+            return None;
+        }
+    }
+    Some(total as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nanos() {
+        assert_eq!(parse_nanos("250ms"), Some(250_000_000));
+        assert_eq!(parse_nanos("1m30s"), Some(90_000_000_000));
+        assert_eq!(parse_nanos("1h1ms"), Some(3_600_001_000_000));
+        assert_eq!(parse_nanos(" 10us "), Some(10_000));
+        assert_eq!(parse_nanos("1.5m"), Some(90_000_000_000));
+        assert_eq!(parse_nanos("1m0.25s"), Some(60_250_000_000));
+        assert_eq!(parse_nanos("30"), Some(30_000_000_000));
+        assert_eq!(parse_nanos("0.5"), Some(500_000_000));
+
+        assert_eq!(parse_nanos(""), None);
+        assert_eq!(parse_nanos("ms"), None);
+        assert_eq!(parse_nanos(".s"), None);
+        assert_eq!(parse_nanos("1m30"), None);
+        assert_eq!(parse_nanos("1.2.3s"), None);
+        assert_eq!(parse_nanos("3d"), None);
+        assert_eq!(parse_nanos("30 parsecs"), None);
+        assert_eq!(parse_nanos("99999999999h"), None);
+        assert_eq!(parse_nanos("99999999999999999999ns"), None);
+    }
+}
@@ -0,0 +1,356 @@
+//! A structured stream of everything `timeit` and `retryable` do
+//!
+//! Each timed expression and retry loop produces [`InstrumentationEvent`]s as it
+//! goes: started, each failed attempt, and how it ended. Every installed
+//! [`Exporter`] gets all of them, so metrics, log shipping and the like can each
+//! be an exporter of their own and sit side by side:
+//! ```ignore
+//! observability::add_exporter(observability::Stderr);
+//! observability::add_exporter(|event: &InstrumentationEvent| {
+//!     if let InstrumentationEvent::RetryGaveUp { op, .. } = event {
+//!         metrics::counter!("gave_up", "label" => op.label.clone()).increment(1);
+//!     }
+//! });
+//! ```
+//! ```text
+//! event=started mono_ns=1760606400003216540 kind=retry label="src/sync.rs:40:9" id=3 attempt=1
+//! event=retry mono_ns=1760606400093845212 kind=retry label="src/sync.rs:40:9" id=3 attempt=1 delay_ms=1200
+//! event=succeeded mono_ns=1760606401294018355 kind=retry label="src/sync.rs:40:9" id=3 attempt=2 elapsed_ms=1290
+//! ```
+//! Events carry a [`Timestamp`], so the streams of several processes can be
+//! merged and put in order. [`set_wall_clock`](crate::set_wall_clock) adds the
+//! wall-clock time (UTC), for lining up machines whose clocks are in sync.
+//! A [`Sink`](crate::Sink) sees the same stream, narrowed down to backoffs and
+//! finished operations.
+//!
+//! [`with_exporter`] redirects the events of a single thread for a while, to
+//! check the instrumentation of a code path in a test for instance.
+//!
+//! No exporter is installed by default. The stream comes on top of the output
+//! the crates have of their own, rather than replacing it: `timeit` still
+//! hands its lines to its `Reporter` (stderr unless another one is set), and
+//! `retryable`'s flaky test and telemetry summaries still print to stderr, so
+//! exporting to [`Stderr`] by default would print every operation twice.
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::{golden, sink, Event, Kind, Operation, Timestamp};
+
+/// Rebuilt when one is added, so [`emit`] only holds the lock long enough to
+/// clone the list, and an exporter can add another without deadlocking
+static EXPORTERS: RwLock<Option<Exporters>> = RwLock::new(None);
+
+type Exporters = Arc<[Arc<dyn Exporter>]>;
+
+thread_local! {
+    static SCOPED: RefCell<Vec<Rc<dyn Exporter>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Something that happened to a timed expression or retry loop, and when
+#[derive(Clone, Copy, Debug)]
+pub enum InstrumentationEvent<'a> {
+    /// A `timeit!` measurement started
+    TimingStarted { op: &'a Operation, at: Timestamp },
+    /// A `timeit!` measurement is over
+    TimingFinished {
+        op: &'a Operation,
+        at: Timestamp,
+        elapsed: Duration,
+    },
+    /// A retry loop is making its first attempt
+    RetryStarted { op: &'a Operation, at: Timestamp },
+    /// Attempt `op.attempt` failed, the next one is made after `delay`
+    RetryAttempt {
+        op: &'a Operation,
+        at: Timestamp,
+        delay: Duration,
+    },
+    /// A retry loop is over, and its last attempt (`op.attempt`) succeeded
+    RetrySucceeded {
+        op: &'a Operation,
+        at: Timestamp,
+        elapsed: Duration,
+    },
+    /// A retry loop is over without succeeding, out of retries or time
+    RetryGaveUp {
+        op: &'a Operation,
+        at: Timestamp,
+        elapsed: Duration,
+    },
+}
+
+impl<'a> InstrumentationEvent<'a> {
+    pub fn operation(&self) -> &'a Operation {
+        match *self {
+            InstrumentationEvent::TimingStarted { op, .. }
+            | InstrumentationEvent::TimingFinished { op, .. }
+            | InstrumentationEvent::RetryStarted { op, .. }
+            | InstrumentationEvent::RetryAttempt { op, .. }
+            | InstrumentationEvent::RetrySucceeded { op, .. }
+            | InstrumentationEvent::RetryGaveUp { op, .. } => op,
+        }
+    }
+
+    pub fn timestamp(&self) -> Timestamp {
+        match *self {
+            InstrumentationEvent::TimingStarted { at, .. }
+            | InstrumentationEvent::TimingFinished { at, .. }
+            | InstrumentationEvent::RetryStarted { at, .. }
+            | InstrumentationEvent::RetryAttempt { at, .. }
+            | InstrumentationEvent::RetrySucceeded { at, .. }
+            | InstrumentationEvent::RetryGaveUp { at, .. } => at,
+        }
+    }
+
+    /// The same event for a [`Sink`](crate::Sink), if it's one they get
+    fn to_sink_event(self) -> Option<Event<'a>> {
+        match self {
+            InstrumentationEvent::TimingStarted { .. }
+            | InstrumentationEvent::RetryStarted { .. } => None,
+            InstrumentationEvent::RetryAttempt { op, delay, .. } => {
+                Some(Event::Backoff { op, delay })
+            }
+            InstrumentationEvent::TimingFinished { op, elapsed, .. }
+            | InstrumentationEvent::RetrySucceeded { op, elapsed, .. } => Some(Event::Finished {
+                op,
+                elapsed,
+                failed: false,
+            }),
+            InstrumentationEvent::RetryGaveUp { op, elapsed, .. } => Some(Event::Finished {
+                op,
+                elapsed,
+                failed: true,
+            }),
+        }
+    }
+}
+
+/// `key=value` pairs, like [`Event`], with the [`Timestamp`]:
+/// > event=gave_up mono_ns=1760606400123456789 kind=retry label="src/sync.rs:40:9" id=3 attempt=5 elapsed_ms=9100
+impl fmt::Display for InstrumentationEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = self.operation();
+        let event = match self {
+            InstrumentationEvent::TimingStarted { .. }
+            | InstrumentationEvent::RetryStarted { .. } => "started",
+            InstrumentationEvent::TimingFinished { .. } => "finished",
+            InstrumentationEvent::RetryAttempt { .. } => "retry",
+            InstrumentationEvent::RetrySucceeded { .. } => "succeeded",
+            InstrumentationEvent::RetryGaveUp { .. } => "gave_up",
+        };
+        write!(
+            f,
+            "event={} {} kind={} label={:?} id={}",
+            event,
+            self.timestamp(),
+            op.kind,
+            op.label,
+            op.id
+        )?;
+        if op.kind == Kind::Retry {
+            write!(f, " attempt={}", op.attempt)?;
+        }
+        match self {
+            InstrumentationEvent::TimingStarted { .. }
+            | InstrumentationEvent::RetryStarted { .. } => Ok(()),
+            InstrumentationEvent::RetryAttempt { delay, .. } => {
+                write!(f, " delay_ms={}", delay.as_millis())
+            }
+            InstrumentationEvent::TimingFinished { elapsed, .. }
+            | InstrumentationEvent::RetrySucceeded { elapsed, .. }
+            | InstrumentationEvent::RetryGaveUp { elapsed, .. } => {
+                write!(f, " elapsed_ms={}", elapsed.as_millis())
+            }
+        }
+    }
+}
+
+/// Receives every [`InstrumentationEvent`], from whichever thread it happened on
+pub trait Exporter: Send + Sync {
+    fn export(&self, event: &InstrumentationEvent);
+}
+
+impl<F: Fn(&InstrumentationEvent) + Send + Sync> Exporter for F {
+    fn export(&self, event: &InstrumentationEvent) {
+        self(event)
+    }
+}
+
+/// Prints every event to stderr, one line each
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stderr;
+
+impl Exporter for Stderr {
+    fn export(&self, event: &InstrumentationEvent) {
+        eprintln!("{}", event);
+    }
+}
+
+/// Send all events to `exporter` from now on, along with any added before
+pub fn add_exporter(exporter: impl Exporter + 'static) {
+    let mut exporters = EXPORTERS.write().unwrap_or_else(|e| e.into_inner());
+    let mut added: Vec<Arc<dyn Exporter>> =
+        exporters.iter().flat_map(|e| e.iter().cloned()).collect();
+    added.push(Arc::new(exporter));
+    *exporters = Some(added.into());
+}
+
+/// Run `f` with the events of this thread going to `exporter`, instead of the
+/// exporters added with [`add_exporter`]
+/// ```ignore
+/// let events = Arc::new(Mutex::new(Vec::new()));
+/// let collect = Arc::clone(&events);
+/// observability::with_exporter(
+///     move |event: &InstrumentationEvent| collect.lock().unwrap().push(event.to_string()),
+///     || sync_users(),
+/// );
+/// ```
+/// Scopes nest, the innermost one wins. Events of other threads (including
+/// those spawned by `f`) still go to the added exporters, and sinks get every
+/// event as usual.
+pub fn with_exporter<R>(exporter: impl Exporter + 'static, f: impl FnOnce() -> R) -> R {
+    SCOPED.with(|scoped| scoped.borrow_mut().push(Rc::new(exporter)));
+    let _scope = Scope;
+    f()
+}
+
+/// Pops the scoped exporter, even if `f` panics
+struct Scope;
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        SCOPED.with(|scoped| scoped.borrow_mut().pop());
+    }
+}
+
+pub(crate) fn emit(event: InstrumentationEvent) {
+    if let Some(event) = event.to_sink_event() {
+        golden::record(&event);
+        sink::emit(&event);
+    }
+    // Neither the scopes nor the lock are held while exporting, as an exporter
+    // may time or retry something itself, or add another exporter
+    if let Some(exporter) = SCOPED.with(|scoped| scoped.borrow().last().cloned()) {
+        exporter.export(&event);
+        return;
+    }
+    let exporters = EXPORTERS.read().unwrap_or_else(|e| e.into_inner()).clone();
+    for exporter in exporters.iter().flat_map(|e| e.iter()) {
+        exporter.export(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Mutex};
+
+    #[test]
+    fn test_exporters() {
+        let (tx, rx) = mpsc::channel();
+        for name in ["first", "second"] {
+            let tx = Mutex::new(tx.clone());
+            add_exporter(move |event: &InstrumentationEvent| {
+                if event.operation().label == "tests::export" {
+                    let line = format!("{} {}", name, event);
+                    tx.lock().unwrap().send(line).unwrap();
+                }
+            });
+        }
+
+        let op = crate::begin(Kind::Retry, "tests::export");
+        op.backoff(Duration::from_millis(1500));
+        op.next_attempt();
+        drop(op);
+        let op = crate::begin(Kind::Retry, "tests::export");
+        op.failed();
+        drop(crate::begin(Kind::Timing, "tests::export"));
+
+        let all: Vec<_> = rx.try_iter().collect();
+        let lines: Vec<_> = all.iter().filter(|l| l.starts_with("first")).collect();
+        assert_eq!(all.len(), lines.len() * 2);
+        let events: Vec<_> = lines
+            .iter()
+            .map(|l| l.split_whitespace().nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "event=started",
+                "event=retry",
+                "event=succeeded",
+                "event=started",
+                "event=gave_up",
+                "event=started",
+                "event=finished"
+            ]
+        );
+        assert!(lines[1].ends_with("attempt=1 delay_ms=1500"));
+        assert!(lines[2].contains("kind=retry") && lines[2].contains("attempt=2 elapsed_ms="));
+        assert!(!lines[5].contains("attempt="));
+        // Stamped in the order they happened
+        let stamps: Vec<u128> = lines
+            .iter()
+            .map(|l| l.split_whitespace().nth(2).unwrap())
+            .map(|field| field.strip_prefix("mono_ns=").unwrap().parse().unwrap())
+            .collect();
+        assert!(stamps.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_exporter_adds_exporter() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        add_exporter(move |event: &InstrumentationEvent| {
+            if event.operation().label == "tests::export adds" {
+                add_exporter(|_: &InstrumentationEvent| {});
+                tx.lock().unwrap().send(()).unwrap();
+            }
+        });
+        drop(crate::begin(Kind::Timing, "tests::export adds"));
+        assert_eq!(rx.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn test_with_exporter() {
+        let (tx, rx) = mpsc::channel();
+        let global = Mutex::new(tx.clone());
+        add_exporter(move |event: &InstrumentationEvent| {
+            if event.operation().label.starts_with("tests::scoped") {
+                let line = format!("global {}", event.operation().label);
+                global.lock().unwrap().send(line).unwrap();
+            }
+        });
+        let scoped = |name: &'static str| {
+            let tx = Mutex::new(tx.clone());
+            move |event: &InstrumentationEvent| {
+                let line = format!("{} {}", name, event.operation().label);
+                tx.lock().unwrap().send(line).unwrap();
+            }
+        };
+
+        with_exporter(scoped("outer"), || {
+            drop(crate::begin(Kind::Timing, "tests::scoped outer"));
+            with_exporter(scoped("inner"), || {
+                drop(crate::begin(Kind::Timing, "tests::scoped inner"));
+            });
+        });
+        drop(crate::begin(Kind::Timing, "tests::scoped after"));
+
+        let lines: Vec<_> = rx.try_iter().collect();
+        assert_eq!(
+            lines,
+            [
+                "outer tests::scoped outer",
+                "outer tests::scoped outer",
+                "inner tests::scoped inner",
+                "inner tests::scoped inner",
+                "global tests::scoped after",
+                "global tests::scoped after"
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Golden-output tests of instrumentation
+//!
+//! [`capture()`] puts the current thread in a deterministic mode: operations
+//! read a mock clock that moves [`STEP`] per reading (and skips over backoffs
+//! instead of waiting them out), and their events are collected in order. The
+//! [`Capture::render`]ed events only change when the instrumentation does, so
+//! they can be compared against a checked-in snapshot:
+//! ```ignore
+//! let capture = observability::golden::capture();
+//! retryable::seed_jitter(42);
+//! sync_users();
+//! assert_eq!(capture.render(), include_str!("snapshots/sync_users.txt"));
+//! ```
+//! ```text
+//! event=backoff kind=retry label="src/sync.rs:40:9" id=1 attempt=1 delay_ms=100
+//! event=finished kind=timing label="sync users" id=2 elapsed_ms=1
+//! event=finished kind=retry label="src/sync.rs:40:9" id=1 attempt=2 elapsed_ms=104
+//! ```
+//! Only the clock of this crate is mocked: timings measured by `timeit` itself,
+//! and the sleeps of retry loops, still take real time (see `Retryable::with_sleeper`).
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::{Event, Operation};
+
+/// How far the mock clock moves each time it's read
+pub const STEP: Duration = Duration::from_millis(1);
+
+thread_local! {
+    static CLOCK: Cell<Option<Instant>> = const { Cell::new(None) };
+    static EVENTS: RefCell<Option<Vec<Captured>>> = const { RefCell::new(None) };
+}
+
+enum Captured {
+    Backoff {
+        op: Operation,
+        delay: Duration,
+    },
+    Finished {
+        op: Operation,
+        elapsed: Duration,
+        failed: bool,
+    },
+}
+
+/// Collects this thread's events until dropped, see the [module docs](self)
+#[must_use = "events are only captured until it's dropped"]
+#[derive(Debug)]
+pub struct Capture {
+    // Tied to the thread it's capturing
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+/// Start capturing this thread's events, on a mock clock
+pub fn capture() -> Capture {
+    CLOCK.with(|clock| clock.set(Some(Instant::now())));
+    EVENTS.with(|events| *events.borrow_mut() = Some(Vec::new()));
+    Capture {
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+impl Capture {
+    /// The events so far, one per line
+    ///
+    /// Operation ids count up from 1 in order of appearance, rather than
+    /// depending on what else ran in the process first.
+    pub fn render(&self) -> String {
+        let mut ids = BTreeMap::new();
+        let mut rendered = String::new();
+        EVENTS.with(|events| {
+            for captured in events.borrow().iter().flatten() {
+                let op = match captured {
+                    Captured::Backoff { op, .. } | Captured::Finished { op, .. } => op,
+                };
+                let next = ids.len() as u64 + 1;
+                let op = Operation {
+                    id: *ids.entry(op.id).or_insert(next),
+                    ..op.clone()
+                };
+                let event = match *captured {
+                    Captured::Backoff { delay, .. } => Event::Backoff { op: &op, delay },
+                    Captured::Finished {
+                        elapsed, failed, ..
+                    } => Event::Finished {
+                        op: &op,
+                        elapsed,
+                        failed,
+                    },
+                };
+                let _ = writeln!(rendered, "{}", event);
+            }
+        });
+        rendered
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        CLOCK.with(|clock| clock.set(None));
+        EVENTS.with(|events| *events.borrow_mut() = None);
+    }
+}
+
+/// The time, from the mock clock while capturing
+pub(crate) fn now() -> Instant {
+    CLOCK.with(|clock| match clock.get() {
+        Some(now) => {
+            clock.set(Some(now + STEP));
+            now + STEP
+        }
+        None => Instant::now(),
+    })
+}
+
+/// Move the mock clock past a backoff, as if it had been waited out
+pub(crate) fn skip(delay: Duration) {
+    CLOCK.with(|clock| clock.set(clock.get().map(|now| now + delay)));
+}
+
+pub(crate) fn record(event: &Event) {
+    EVENTS.with(|events| {
+        if let Some(events) = events.borrow_mut().as_mut() {
+            events.push(match *event {
+                Event::Backoff { op, delay } => Captured::Backoff {
+                    op: op.clone(),
+                    delay,
+                },
+                Event::Finished {
+                    op,
+                    elapsed,
+                    failed,
+                } => Captured::Finished {
+                    op: op.clone(),
+                    elapsed,
+                    failed,
+                },
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{begin, Kind};
+
+    #[test]
+    fn test_capture() {
+        // Ids handed out before capturing don't show up in the output
+        drop(begin(Kind::Timing, "tests::before"));
+
+        let capture = capture();
+        let retry = begin(Kind::Retry, "tests::golden");
+        drop(begin(Kind::Timing, "tests::golden"));
+        retry.backoff(Duration::from_millis(100));
+        retry.next_attempt();
+        retry.failed();
+        let expected = "\
+event=finished kind=timing label=\"tests::golden\" id=1 elapsed_ms=1
+event=backoff kind=retry label=\"tests::golden\" id=2 attempt=1 delay_ms=100
+event=failed kind=retry label=\"tests::golden\" id=2 attempt=2 elapsed_ms=104
+";
+        assert_eq!(capture.render(), expected);
+        drop(capture);
+
+        // Back on the real clock, with nothing captured
+        let start = now();
+        assert!(now() - start < STEP);
+    }
+}
@@ -0,0 +1,342 @@
+//! Live, queryable state of the `timeit` and `retryable` crates
+//!
+//! With their `observability` features enabled, both crates register each
+//! timed expression and retry loop here while it runs, along with any backoff
+//! a retry loop is sleeping through. [`snapshot()`] returns the current state,
+//! enough to back a debug endpoint or a status page:
+//! ```ignore
+//! eprintln!("{}", observability::snapshot());
+//! ```
+//! ```text
+//! in flight:
+//!   #3 retry  src/sync.rs:40:9  attempt 4, backing off for 1.2s
+//!   #7 timing 'fetch_user'      for 15ms
+//! latency:
+//!   'fetch_user'      count 120  mean 12.1ms  min 8ms  max 41ms
+//!   src/sync.rs:40:9  count 3    mean 3.4s    min 1ms  max 9.1s
+//! ```
+//!
+//! Operations can also be streamed as they happen, as [`InstrumentationEvent`]s
+//! to any number of [`Exporter`]s (see the [`export`] module), or to an installed
+//! [`Sink`]. With the `syslog` feature, [`syslog::Syslog`] sends them to the system logger.
+//!
+//! [`golden::capture()`] collects them on a mock clock instead, for snapshot
+//! tests of the instrumentation itself.
+//!
+//! With the `tui` feature, [`tui::spawn`] keeps a live view of the in-flight
+//! operations on the terminal.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub mod export;
+pub mod golden;
+mod sink;
+#[cfg(all(feature = "syslog", unix))]
+pub mod syslog;
+mod timestamp;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+pub use export::{add_exporter, with_exporter, Exporter, InstrumentationEvent, Stderr};
+pub use sink::{set_sink, Event, Priority, Sink};
+pub use timestamp::{set_wall_clock, Timestamp};
+
+static STATE: Mutex<State> = Mutex::new(State {
+    next_id: 1,
+    in_flight: BTreeMap::new(),
+    latency: BTreeMap::new(),
+});
+
+struct State {
+    next_id: u64,
+    in_flight: BTreeMap<u64, Operation>,
+    latency: BTreeMap<String, Latency>,
+}
+
+fn state() -> std::sync::MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Which crate an operation comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A `timeit!` measurement
+    Timing,
+    /// A retry loop
+    Retry,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Timing => f.pad("timing"),
+            Kind::Retry => f.pad("retry"),
+        }
+    }
+}
+
+/// An operation that's currently running
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub id: u64,
+    pub kind: Kind,
+    pub label: String,
+    pub started: Instant,
+    /// The current attempt of a retry loop (1 for the first)
+    pub attempt: usize,
+    /// When a retry loop that's backing off will try again
+    pub backoff_until: Option<Instant>,
+}
+
+/// Aggregated durations of finished operations with the same label
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Latency {
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos((self.total.as_nanos() / u128::from(self.count.max(1))) as u64)
+    }
+}
+
+/// Register an operation as in flight until the returned guard is dropped
+pub fn begin(kind: Kind, label: &str) -> InFlight {
+    let op = {
+        let mut state = state();
+        let id = state.next_id;
+        state.next_id += 1;
+        let op = Operation {
+            id,
+            kind,
+            label: label.to_string(),
+            started: golden::now(),
+            attempt: 1,
+            backoff_until: None,
+        };
+        state.in_flight.insert(id, op.clone());
+        op
+    };
+    let at = Timestamp::now();
+    export::emit(match kind {
+        Kind::Timing => InstrumentationEvent::TimingStarted { op: &op, at },
+        Kind::Retry => InstrumentationEvent::RetryStarted { op: &op, at },
+    });
+    InFlight {
+        id: op.id,
+        failed: false,
+    }
+}
+
+/// Add a finished operation's duration to the aggregates of its label
+pub fn record(label: &str, elapsed: Duration) {
+    let mut state = state();
+    match state.latency.get_mut(label) {
+        Some(latency) => {
+            latency.count += 1;
+            latency.total += elapsed;
+            latency.min = latency.min.min(elapsed);
+            latency.max = latency.max.max(elapsed);
+        }
+        None => {
+            let latency = Latency {
+                count: 1,
+                total: elapsed,
+                min: elapsed,
+                max: elapsed,
+            };
+            state.latency.insert(label.to_string(), latency);
+        }
+    }
+}
+
+/// Handle to an in-flight operation, removed from the registry on drop
+#[derive(Debug)]
+pub struct InFlight {
+    id: u64,
+    failed: bool,
+}
+
+impl InFlight {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The operation is sleeping for `delay` before its next attempt
+    pub fn backoff(&self, delay: Duration) {
+        let op = state().in_flight.get_mut(&self.id).map(|op| {
+            op.backoff_until = Some(golden::now() + delay);
+            op.clone()
+        });
+        golden::skip(delay);
+        if let Some(op) = op {
+            let at = Timestamp::now();
+            export::emit(InstrumentationEvent::RetryAttempt { op: &op, at, delay });
+        }
+    }
+
+    /// The backoff is over and the next attempt is starting
+    pub fn next_attempt(&self) {
+        if let Some(op) = state().in_flight.get_mut(&self.id) {
+            op.attempt += 1;
+            op.backoff_until = None;
+        }
+    }
+
+    /// The operation is over and gave up, instead of the success that
+    /// dropping the handle implies
+    pub fn failed(mut self) {
+        self.failed = true;
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let op = state().in_flight.remove(&self.id);
+        if let Some(op) = op {
+            let elapsed = golden::now().saturating_duration_since(op.started);
+            let (op, at) = (&op, Timestamp::now());
+            export::emit(match (op.kind, self.failed) {
+                (Kind::Timing, _) => InstrumentationEvent::TimingFinished { op, at, elapsed },
+                (Kind::Retry, false) => InstrumentationEvent::RetrySucceeded { op, at, elapsed },
+                (Kind::Retry, true) => InstrumentationEvent::RetryGaveUp { op, at, elapsed },
+            });
+        }
+    }
+}
+
+/// Point-in-time copy of the registry
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    /// Running operations, oldest first
+    pub in_flight: Vec<Operation>,
+    /// Latency aggregates, by label
+    pub latency: Vec<(String, Latency)>,
+}
+
+impl Snapshot {
+    /// Retry loops currently sleeping, with their wake-up deadlines
+    pub fn backoffs(&self) -> impl Iterator<Item = (&Operation, Instant)> {
+        self.in_flight
+            .iter()
+            .filter_map(|op| op.backoff_until.map(|until| (op, until)))
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let now = Instant::now();
+        writeln!(f, "in flight:")?;
+        for op in &self.in_flight {
+            write!(f, "  #{} {:<6} {}", op.id, op.kind, op.label)?;
+            match op.backoff_until {
+                Some(until) => writeln!(
+                    f,
+                    "  attempt {}, backing off for {:?}",
+                    op.attempt,
+                    until.saturating_duration_since(now)
+                )?,
+                None if op.kind == Kind::Retry => writeln!(
+                    f,
+                    "  attempt {}, for {:?}",
+                    op.attempt,
+                    now.duration_since(op.started)
+                )?,
+                None => writeln!(f, "  for {:?}", now.duration_since(op.started))?,
+            }
+        }
+        writeln!(f, "latency:")?;
+        for (label, latency) in &self.latency {
+            writeln!(
+                f,
+                "  {}  count {}  mean {:?}  min {:?}  max {:?}",
+                label,
+                latency.count,
+                latency.mean(),
+                latency.min,
+                latency.max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The current state of every registered operation
+pub fn snapshot() -> Snapshot {
+    let state = state();
+    Snapshot {
+        in_flight: state.in_flight.values().cloned().collect(),
+        latency: state
+            .latency
+            .iter()
+            .map(|(label, latency)| (label.clone(), *latency))
+            .collect(),
+    }
+}
+
+/// Forget all latency aggregates (in-flight operations are kept)
+pub fn reset() {
+    state().latency.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_flight() {
+        let op = begin(Kind::Retry, "tests::in_flight");
+        let find = || {
+            snapshot()
+                .in_flight
+                .into_iter()
+                .find(|o| o.id == op.id())
+        };
+        assert_eq!(find().unwrap().attempt, 1);
+
+        op.backoff(Duration::from_secs(60));
+        let snap = snapshot();
+        let (backing_off, until) = snap.backoffs().find(|(o, _)| o.id == op.id()).unwrap();
+        assert_eq!(backing_off.label, "tests::in_flight");
+        assert!(until > Instant::now() + Duration::from_secs(59));
+
+        op.next_attempt();
+        let current = find().unwrap();
+        assert_eq!(current.attempt, 2);
+        assert_eq!(current.backoff_until, None);
+
+        let id = op.id();
+        drop(op);
+        assert!(snapshot().in_flight.iter().all(|o| o.id != id));
+    }
+
+    #[test]
+    fn test_latency() {
+        record("tests::latency", Duration::from_millis(10));
+        record("tests::latency", Duration::from_millis(30));
+        let snap = snapshot();
+        let (_, latency) = snap
+            .latency
+            .iter()
+            .find(|(label, _)| label == "tests::latency")
+            .unwrap();
+        assert_eq!(latency.count, 2);
+        assert_eq!(latency.mean(), Duration::from_millis(20));
+        assert_eq!(latency.min, Duration::from_millis(10));
+        assert_eq!(latency.max, Duration::from_millis(30));
+        assert!(snap.to_string().contains("tests::latency  count 2"));
+
+        // More operations than fit in a `u32`
+        let latency = Latency {
+            count: 5_000_000_000,
+            total: Duration::from_secs(10_000),
+            ..*latency
+        };
+        assert_eq!(latency.mean(), Duration::from_micros(2));
+    }
+}
@@ -0,0 +1,127 @@
+//! Streaming operations to a log pipeline as they happen
+use std::fmt;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::{Kind, Operation};
+
+static SINK: RwLock<Option<Box<dyn Sink>>> = RwLock::new(None);
+
+/// Receives every backoff and finished operation, from whichever thread it happened on
+pub trait Sink: Send + Sync {
+    fn event(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Send + Sync> Sink for F {
+    fn event(&self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Send all events to `sink` from now on, replacing the previous one
+pub fn set_sink(sink: impl Sink + 'static) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(sink));
+}
+
+pub(crate) fn emit(event: &Event) {
+    let sink = SINK.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(sink) = sink.as_ref() {
+        sink.event(event);
+    }
+}
+
+/// Something that happened to an operation
+#[derive(Clone, Copy, Debug)]
+pub enum Event<'a> {
+    /// A retry loop is sleeping for `delay` after a failed attempt
+    Backoff { op: &'a Operation, delay: Duration },
+    /// The operation is over, `failed` when a retry loop gave up
+    Finished {
+        op: &'a Operation,
+        elapsed: Duration,
+        failed: bool,
+    },
+}
+
+/// Syslog severities, most severe first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Emergency,
+    Alert,
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl<'a> Event<'a> {
+    pub fn operation(&self) -> &'a Operation {
+        match self {
+            Event::Backoff { op, .. } | Event::Finished { op, .. } => op,
+        }
+    }
+
+    /// Give-ups are errors and backoffs warnings, everything else is informational
+    pub fn priority(&self) -> Priority {
+        match self {
+            Event::Backoff { .. } => Priority::Warning,
+            Event::Finished { failed: true, .. } => Priority::Error,
+            Event::Finished { .. } => Priority::Info,
+        }
+    }
+}
+
+/// `key=value` pairs, parseable by most log pipelines:
+/// > event=backoff kind=retry label="src/sync.rs:40:9" id=3 attempt=1 delay_ms=1200
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = self.operation();
+        let event = match self {
+            Event::Backoff { .. } => "backoff",
+            Event::Finished { failed: true, .. } => "failed",
+            Event::Finished { .. } => "finished",
+        };
+        write!(f, "event={} kind={} label={:?} id={}", event, op.kind, op.label, op.id)?;
+        if op.kind == Kind::Retry {
+            write!(f, " attempt={}", op.attempt)?;
+        }
+        match self {
+            Event::Backoff { delay, .. } => write!(f, " delay_ms={}", delay.as_millis()),
+            Event::Finished { elapsed, .. } => write!(f, " elapsed_ms={}", elapsed.as_millis()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_sink() {
+        let (tx, rx) = mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        set_sink(move |event: &Event| {
+            if event.operation().label == "tests::sink" {
+                let line = format!("{:?} {}", event.priority(), event);
+                tx.lock().unwrap().send(line).unwrap();
+            }
+        });
+
+        let op = crate::begin(Kind::Retry, "tests::sink");
+        op.backoff(Duration::from_millis(1500));
+        op.next_attempt();
+        op.failed();
+        drop(crate::begin(Kind::Timing, "tests::sink"));
+
+        let lines: Vec<_> = rx.try_iter().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("Warning event=backoff kind=retry label=\"tests::sink\""));
+        assert!(lines[0].ends_with("attempt=1 delay_ms=1500"));
+        assert!(lines[1].starts_with("Error event=failed kind=retry"));
+        assert!(lines[1].contains("attempt=2 elapsed_ms="));
+        assert!(lines[2].starts_with("Info event=finished kind=timing"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Sending operations to the system logger
+//!
+//! For ops teams whose log pipeline starts at syslog (or journald, which
+//! listens on the same socket) rather than at scraped stdout:
+//! ```ignore
+//! observability::set_sink(observability::syslog::Syslog::new("billing")?);
+//! ```
+//! Each event becomes an RFC 3164 message at its [`Priority`](crate::Priority), e.g.
+//! ```text
+//! <12>billing[4242]: event=backoff kind=retry label="src/sync.rs:40:9" id=3 attempt=1 delay_ms=1200
+//! ```
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+
+use crate::{Event, Sink};
+
+/// Where the system logger listens, Linux first then macOS
+const SOCKETS: &[&str] = &["/dev/log", "/var/run/syslog"];
+
+/// Syslog facilities applications are likely to log under
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facility {
+    User = 1,
+    Daemon = 3,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// A [`Sink`] writing to the local syslog socket
+#[derive(Debug)]
+pub struct Syslog {
+    socket: UnixDatagram,
+    ident: String,
+    facility: Facility,
+    pid: u32,
+}
+
+impl Syslog {
+    /// Connect to the system logger, tagging messages with `ident`
+    /// (usually the program name) under the `user` facility
+    pub fn new(ident: &str) -> io::Result<Self> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no syslog socket found");
+        for path in SOCKETS {
+            match Self::with_socket(path, ident) {
+                Ok(syslog) => return Ok(syslog),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Connect to a syslog socket at a non-standard path
+    pub fn with_socket(path: impl AsRef<Path>, ident: &str) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self {
+            socket,
+            ident: ident.to_string(),
+            facility: Facility::User,
+            pid: std::process::id(),
+        })
+    }
+
+    pub fn facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    fn message(&self, event: &Event) -> String {
+        let pri = self.facility as u8 * 8 + event.priority() as u8;
+        format!("<{}>{}[{}]: {}", pri, self.ident, self.pid, event)
+    }
+}
+
+impl Sink for Syslog {
+    fn event(&self, event: &Event) {
+        // Logging is best effort, a full or missing socket shouldn't fail the operation
+        let _ = self.socket.send(self.message(event).as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Kind, Operation};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_syslog() {
+        let path = std::env::temp_dir().join(format!("observability-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path).unwrap();
+        let syslog = Syslog::with_socket(&path, "tests").unwrap().facility(Facility::Local0);
+
+        let op = Operation {
+            id: 9,
+            kind: Kind::Retry,
+            label: "src/sync.rs:40:9".to_string(),
+            started: Instant::now(),
+            attempt: 3,
+            backoff_until: None,
+        };
+        let event = Event::Finished {
+            op: &op,
+            elapsed: Duration::from_millis(20),
+            failed: true,
+        };
+        syslog.event(&event);
+
+        let mut buf = [0; 256];
+        let len = server.recv(&mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected = format!(
+            "<131>tests[{}]: event=failed kind=retry label=\"src/sync.rs:40:9\" id=9 attempt=3 elapsed_ms=20",
+            std::process::id()
+        );
+        assert_eq!(std::str::from_utf8(&buf[..len]).unwrap(), expected);
+    }
+}
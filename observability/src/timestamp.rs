@@ -0,0 +1,136 @@
+//! When events happen, in a form that can be ordered across processes
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static WALL_CLOCK: AtomicBool = AtomicBool::new(false);
+
+/// The monotonic clock, and the wall clock at the same moment, read once per
+/// process
+static ANCHOR: OnceLock<(Instant, Duration)> = OnceLock::new();
+
+/// Also stamp events with the wall clock (UTC) from now on
+///
+/// Unlike the monotonic timestamp it follows clock adjustments (NTP steps,
+/// manual changes), so it can jump or go backwards, but it's directly
+/// comparable between machines with synced clocks.
+pub fn set_wall_clock(enabled: bool) {
+    WALL_CLOCK.store(enabled, Ordering::Relaxed);
+}
+
+/// When an [`InstrumentationEvent`](crate::InstrumentationEvent) happened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Time since the Unix epoch, counted on the monotonic clock from a single
+    /// reading of the wall clock per process: it never goes backwards within a
+    /// process, and processes on the same machine line up to within the
+    /// precision of that reading
+    pub monotonic: Duration,
+    /// The wall clock, if enabled with [`set_wall_clock`]
+    pub wall: Option<SystemTime>,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        let (instant, since_epoch) =
+            *ANCHOR.get_or_init(|| (Instant::now(), since_epoch(SystemTime::now())));
+        Self {
+            monotonic: since_epoch + instant.elapsed(),
+            wall: if WALL_CLOCK.load(Ordering::Relaxed) {
+                Some(SystemTime::now())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// `mono_ns=1760606400123456789 utc=2026-10-16T09:20:00.123456Z`, without `utc`
+/// unless the wall clock is enabled
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mono_ns={}", self.monotonic.as_nanos())?;
+        if let Some(wall) = self.wall {
+            write!(f, " utc={}", Utc(wall))?;
+        }
+        Ok(())
+    }
+}
+
+fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// RFC 3339, to the microsecond
+struct Utc(SystemTime);
+
+impl fmt::Display for Utc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since = since_epoch(self.0);
+        let secs = since.as_secs();
+        let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+        let time = secs % 86_400;
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time % 3600 / 60,
+            time % 60,
+            since.subsec_micros()
+        )
+    }
+}
+
+/// Year, month and day of a count of days since 1970-01-01, in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc() {
+        let utc =
+            |secs, micros: u32| Utc(UNIX_EPOCH + Duration::new(secs, micros * 1000)).to_string();
+        assert_eq!(utc(0, 0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(utc(951_782_400, 0), "2000-02-29T00:00:00.000000Z");
+        assert_eq!(utc(1_700_000_000, 42), "2023-11-14T22:13:20.000042Z");
+        assert_eq!(utc(4_107_542_399, 999_999), "2100-02-28T23:59:59.999999Z");
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let (first, second) = (Timestamp::now(), Timestamp::now());
+        assert!(second.monotonic >= first.monotonic);
+        // Anchored to the wall clock, give or take the time since the anchor was read
+        let wall = since_epoch(SystemTime::now());
+        assert!(first.monotonic.as_secs().abs_diff(wall.as_secs()) < 60);
+
+        let stamp = Timestamp {
+            monotonic: Duration::from_nanos(1_700_000_000_000_000_123),
+            wall: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        };
+        assert_eq!(
+            stamp.to_string(),
+            "mono_ns=1700000000000000123 utc=2023-11-14T22:13:20.000000Z"
+        );
+    }
+}
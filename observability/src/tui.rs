@@ -0,0 +1,172 @@
+//! A live view of in-flight operations, redrawn in place on the terminal
+//!
+//! For keeping an eye on long-running scripts (migrations, backfills) while
+//! they work through their timed scopes and retry loops:
+//! ```ignore
+//! let _view = observability::tui::spawn();
+//! migrate_users();
+//! ```
+//! ```text
+//! in flight (2):
+//!   #3 retry   src/sync.rs:40:9  1.3s  attempt 4, next retry in 1.2s
+//!   #7 timing  migrate users     42.0s
+//! ```
+//! The view is drawn on stderr, below any output already there, and cleared
+//! when the returned [`LiveView`] is dropped. Lines printed by the program
+//! itself while the view is up will be drawn over.
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{snapshot, Kind, Snapshot};
+
+/// How often [`spawn`] redraws the view
+pub const REFRESH: Duration = Duration::from_millis(250);
+
+/// Draws the view from a background thread until dropped
+#[must_use = "the view is cleared as soon as it's dropped"]
+#[derive(Debug)]
+pub struct LiveView {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    renderer: Option<JoinHandle<()>>,
+}
+
+/// Start drawing the view, every [`REFRESH`]
+///
+/// Nothing is drawn when stderr isn't a terminal (redirected to a file, or
+/// captured by a test harness), so the view can be left on in scripts.
+pub fn spawn() -> LiveView {
+    spawn_every(REFRESH)
+}
+
+/// Start drawing the view, every `refresh`
+pub fn spawn_every(refresh: Duration) -> LiveView {
+    let stop = Arc::new((Mutex::new(false), Condvar::new()));
+    let renderer = if io::stderr().is_terminal() {
+        let stop = Arc::clone(&stop);
+        Some(thread::spawn(move || draw_until_stopped(&stop, refresh)))
+    } else {
+        None
+    };
+    LiveView { stop, renderer }
+}
+
+impl Drop for LiveView {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        wake.notify_one();
+        if let Some(renderer) = self.renderer.take() {
+            let _ = renderer.join();
+        }
+    }
+}
+
+fn draw_until_stopped(stop: &(Mutex<bool>, Condvar), refresh: Duration) {
+    let (stopped, wake) = stop;
+    let mut drawn = 0;
+    let mut stopped = stopped.lock().unwrap_or_else(|e| e.into_inner());
+    while !*stopped {
+        drawn = redraw(drawn, Some(&snapshot())).unwrap_or(drawn);
+        stopped = wake
+            .wait_timeout(stopped, refresh)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    let _ = redraw(drawn, None);
+}
+
+/// Replace the `drawn` lines of the previous frame, returning how many were drawn now
+fn redraw(drawn: usize, snap: Option<&Snapshot>) -> io::Result<usize> {
+    let mut frame = Vec::new();
+    let lines = match snap {
+        Some(snap) => render(snap, Instant::now(), &mut frame)?,
+        None => 0,
+    };
+    let mut stderr = io::stderr().lock();
+    if drawn > 0 {
+        // Back to the first line of the previous frame, and clear everything below
+        write!(stderr, "\x1b[{}A\r\x1b[J", drawn)?;
+    }
+    stderr.write_all(&frame)?;
+    stderr.flush()?;
+    Ok(lines)
+}
+
+/// Write one frame of the view, returning how many lines it took
+fn render(snap: &Snapshot, now: Instant, out: &mut impl Write) -> io::Result<usize> {
+    writeln!(out, "in flight ({}):", snap.in_flight.len())?;
+    let width = snap
+        .in_flight
+        .iter()
+        .map(|op| op.label.chars().count())
+        .max()
+        .unwrap_or_default();
+    for op in &snap.in_flight {
+        write!(
+            out,
+            "  #{} {:<6}  {:<width$}  {:.1?}",
+            op.id,
+            op.kind,
+            op.label,
+            now.saturating_duration_since(op.started),
+            width = width
+        )?;
+        match op.backoff_until {
+            Some(until) => writeln!(
+                out,
+                "  attempt {}, next retry in {:.1?}",
+                op.attempt,
+                until.saturating_duration_since(now)
+            )?,
+            None if op.kind == Kind::Retry => writeln!(out, "  attempt {}", op.attempt)?,
+            None => writeln!(out)?,
+        }
+    }
+    Ok(snap.in_flight.len() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Operation;
+
+    #[test]
+    fn test_render() {
+        let now = Instant::now();
+        let op = |id, kind, label: &str, attempt, backoff_until| Operation {
+            id,
+            kind,
+            label: label.to_string(),
+            started: now - Duration::from_millis(1300),
+            attempt,
+            backoff_until,
+        };
+        let retry_at = Some(now + Duration::from_millis(1200));
+        let snap = Snapshot {
+            in_flight: vec![
+                op(3, Kind::Retry, "src/sync.rs:40:9", 4, retry_at),
+                op(5, Kind::Retry, "src/sync.rs:52:9", 2, None),
+                op(7, Kind::Timing, "migrate users", 1, None),
+            ],
+            latency: Vec::new(),
+        };
+        let mut frame = Vec::new();
+        assert_eq!(render(&snap, now, &mut frame).unwrap(), 4);
+        assert_eq!(
+            String::from_utf8(frame).unwrap(),
+            "in flight (3):\n\
+             \x20 #3 retry   src/sync.rs:40:9  1.3s  attempt 4, next retry in 1.2s\n\
+             \x20 #5 retry   src/sync.rs:52:9  1.3s  attempt 2\n\
+             \x20 #7 timing  migrate users     1.3s\n"
+        );
+    }
+
+    #[test]
+    fn test_live_view_stops() {
+        let view = spawn_every(Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(5));
+        drop(view);
+    }
+}
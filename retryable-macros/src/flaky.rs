@@ -0,0 +1,56 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Error, ItemFn, MetaNameValue};
+
+use crate::duration::duration_option;
+use crate::int_option;
+
+pub(crate) fn expand(options: Vec<MetaNameValue>, func: ItemFn) -> syn::Result<TokenStream> {
+    let mut attempts = 3u64;
+    let mut delay = quote!(::std::time::Duration::from_millis(0));
+    for option in options {
+        if option.path.is_ident("attempts") {
+            attempts = int_option(&option)?;
+            if attempts == 0 {
+                return Err(Error::new_spanned(&option.value, "attempts must be at least 1"));
+            }
+        } else if option.path.is_ident("delay") {
+            delay = duration_option(&option.value)?;
+        } else if option.path.is_ident("delay_ms") {
+            let ms = int_option(&option)?;
+            delay = quote!(::std::time::Duration::from_millis(#ms));
+        } else {
+            return Err(Error::new_spanned(
+                &option.path,
+                "unknown option, expected `attempts` or `delay`",
+            ));
+        }
+    }
+    if let Some(asyncness) = &func.sig.asyncness {
+        return Err(Error::new_spanned(asyncness, "async tests aren't supported"));
+    }
+    if !func.sig.inputs.is_empty() {
+        return Err(Error::new(Span::call_site(), "tests can't take arguments"));
+    }
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = func;
+    let name = &sig.ident;
+    let output = &sig.output;
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        #vis #sig {
+            ::retryable::flaky::run(
+                concat!(module_path!(), "::", stringify!(#name)),
+                #attempts as usize,
+                #delay,
+                || #output #block,
+            )
+        }
+    })
+}
@@ -0,0 +1,63 @@
+//! Attribute macros for `retryable`, use them through the re-exports in that crate
+use proc_macro::TokenStream;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Error, Expr, ExprLit, ItemFn, Lit, MetaNameValue, Token};
+
+mod duration;
+mod flaky;
+mod retry;
+
+/// Options given as `key = value` pairs, like `#[flaky_test(attempts = 5)]`
+fn parse_options(args: TokenStream) -> syn::Result<Vec<MetaNameValue>> {
+    let options = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse(args)?;
+    Ok(options.into_iter().collect())
+}
+
+fn int_option(option: &MetaNameValue) -> syn::Result<u64> {
+    match &option.value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(int), ..
+        }) => int.base10_parse(),
+        value => Err(Error::new_spanned(value, "expected an integer")),
+    }
+}
+
+/// Rerun a failing test up to a number of times, passing on the first success
+///
+/// ```ignore
+/// #[retryable::flaky_test(attempts = 5, delay = "100ms")]
+/// fn talks_to_staging() {
+///     assert!(staging::ping().is_ok());
+/// }
+/// ```
+/// Defaults to 3 attempts with no delay. Tests returning a `Result` are rerun on `Err`
+/// as well as panics, and each flake is reported on stderr.
+#[proc_macro_attribute]
+pub fn flaky_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match parse_options(args).and_then(|options| flaky::expand(options, func)) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Retry a function (sync or `async`) returning a `Result` whenever it returns `Err`
+///
+/// ```ignore
+/// #[retryable::retry(retries = 5, delay = "250ms", max_elapsed = "1m30s")]
+/// fn fetch_config(url: &str) -> Result<Config, Error> {
+///     http::get(url)?.parse()
+/// }
+/// ```
+/// Defaults to 3 retries with no delay. Durations are checked at compile time.
+/// The body runs again for each attempt, so arguments it moves out of need to be
+/// `Copy` (or borrowed instead).
+#[proc_macro_attribute]
+pub fn retry(args: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match parse_options(args).and_then(|options| retry::expand(options, func)) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,75 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, ItemFn, MetaNameValue, ReturnType};
+
+use crate::duration::duration_option;
+use crate::int_option;
+
+pub(crate) fn expand(options: Vec<MetaNameValue>, func: ItemFn) -> syn::Result<TokenStream> {
+    let mut retries = 3u64;
+    let mut delay = quote!(::std::time::Duration::from_millis(0));
+    let mut max_elapsed = None;
+    for option in options {
+        if option.path.is_ident("retries") {
+            retries = int_option(&option)?;
+        } else if option.path.is_ident("delay") {
+            delay = duration_option(&option.value)?;
+        } else if option.path.is_ident("max_elapsed") {
+            max_elapsed = Some(duration_option(&option.value)?);
+        } else {
+            return Err(Error::new_spanned(
+                &option.path,
+                "unknown option, expected `retries`, `delay` or `max_elapsed`",
+            ));
+        }
+    }
+    let output = match &func.sig.output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => {
+            return Err(Error::new_spanned(
+                &func.sig,
+                "#[retry] functions must return a `Result`",
+            ))
+        }
+    };
+    let max_elapsed = max_elapsed.map(|max_elapsed| {
+        quote!(_strategy.with_max_elapsed(#max_elapsed);)
+    });
+    // `return` in the body has to end the attempt, not the whole function
+    let block = &func.block;
+    let attempt = match func.sig.asyncness {
+        Some(_) => quote!((async { let _res: #output = #block; _res }).await),
+        None => quote!((|| -> #output { #block })()),
+    };
+    let sleep = match func.sig.asyncness {
+        Some(_) => quote!(::retryable::future::sleep(_delay).await),
+        None => quote!(::std::thread::sleep(_delay)),
+    };
+
+    let ItemFn { attrs, vis, sig, .. } = &func;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #[allow(unused_mut)]
+            let mut _strategy = ::retryable::RetryStrategy::new(
+                #retries as usize,
+                ::retryable::RetryDelay::Fixed(#delay),
+            );
+            #max_elapsed
+            let mut _retry = ::retryable::RetryLoop::new(_strategy);
+            loop {
+                let _res: #output = #attempt;
+                match _retry.after(::retryable::Decision::default_for(&_res)) {
+                    Some(_delay) => {
+                        #sleep;
+                        _retry.resume();
+                    }
+                    None => {
+                        _retry.finish(_res.is_err());
+                        break _res;
+                    }
+                }
+            }
+        }
+    })
+}
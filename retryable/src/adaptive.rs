@@ -0,0 +1,109 @@
+//! Failure-rate tracking for [`RetryDelay::Adaptive`](crate::RetryDelay::Adaptive)
+//!
+//! Every attempt made under an adaptive delay records its outcome against the
+//! delay's label. All retry loops sharing a label (e.g. every call to the same
+//! upstream) see the same failure rate, so delays widen while that upstream
+//! struggles and tighten again as it recovers.
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::intern;
+
+/// Most outcomes kept per label
+const WINDOW_SIZE: usize = 100;
+/// Outcomes older than this no longer count
+const WINDOW_AGE: Duration = Duration::from_secs(60);
+
+type Window = VecDeque<(Instant, bool)>;
+
+static WINDOWS: Mutex<BTreeMap<&'static str, Window>> = Mutex::new(BTreeMap::new());
+
+/// Record the outcome of an attempt against a label
+pub fn record(label: &str, success: bool) {
+    let mut windows = WINDOWS.lock().unwrap_or_else(|e| e.into_inner());
+    let window = windows.entry(intern::intern(label)).or_default();
+    if window.len() == WINDOW_SIZE {
+        window.pop_front();
+    }
+    window.push_back((Instant::now(), success));
+}
+
+/// Fraction (0.0..=1.0) of recent attempts for a label that failed
+///
+/// A label without any recent attempts is considered healthy.
+pub fn failure_rate(label: &str) -> f64 {
+    let mut windows = WINDOWS.lock().unwrap_or_else(|e| e.into_inner());
+    let window = match windows.get_mut(label) {
+        Some(window) => window,
+        None => return 0.0,
+    };
+    while let Some((at, _)) = window.front() {
+        if at.elapsed() <= WINDOW_AGE {
+            break;
+        }
+        window.pop_front();
+    }
+    if window.is_empty() {
+        return 0.0;
+    }
+    let failures = window.iter().filter(|(_, success)| !success).count();
+    failures as f64 / window.len() as f64
+}
+
+/// Scale between `min` and `max` by the label's current failure rate
+pub(crate) fn delay(label: &str, min: Duration, max: Duration) -> Duration {
+    min + max.saturating_sub(min).mul_f64(failure_rate(label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_rate() {
+        assert_eq!(failure_rate("adaptive::unknown"), 0.0);
+        record("adaptive::rate", true);
+        record("adaptive::rate", false);
+        record("adaptive::rate", false);
+        record("adaptive::rate", false);
+        assert_eq!(failure_rate("adaptive::rate"), 0.75);
+
+        let min = Duration::from_millis(100);
+        let max = Duration::from_millis(500);
+        assert_eq!(delay("adaptive::rate", min, max), Duration::from_millis(400));
+        assert_eq!(delay("adaptive::unknown", min, max), min);
+    }
+
+    #[test]
+    fn test_window_size() {
+        for _ in 0..WINDOW_SIZE {
+            record("adaptive::recovering", false);
+        }
+        assert_eq!(failure_rate("adaptive::recovering"), 1.0);
+        for _ in 0..WINDOW_SIZE / 2 {
+            record("adaptive::recovering", true);
+        }
+        assert_eq!(failure_rate("adaptive::recovering"), 0.5);
+    }
+
+    #[test]
+    fn test_dynamic_label() {
+        use crate::RetryDelay;
+        use std::borrow::Cow;
+
+        let tenant = 42;
+        let delay = RetryDelay::adaptive(
+            format!("adaptive::tenant-{}", tenant),
+            Duration::ZERO,
+            Duration::ZERO,
+        );
+        let label = match delay {
+            RetryDelay::Adaptive { label, .. } => label,
+            _ => unreachable!(),
+        };
+        assert!(matches!(label, Cow::Borrowed(_)));
+        record(&label, false);
+        assert_eq!(failure_rate("adaptive::tenant-42"), 1.0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Circuit breaking, see [`CircuitBreaker`]
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Stops calls to an upstream once it keeps failing, and lets a single probe
+/// through now and then to find out whether it has recovered
+///
+/// Clones share the same circuit, so every caller of the upstream backs off
+/// together. Usually part of a [`ResiliencePolicy`](crate::ResiliencePolicy).
+/// ```
+/// use std::time::Duration;
+/// use retryable::{CircuitBreaker, CircuitState};
+///
+/// let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+/// breaker.record(false);
+/// breaker.record(false);
+/// assert_eq!(breaker.state(), CircuitState::Open);
+/// assert!(!breaker.allow());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    threshold: usize,
+    cool_down: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed {
+        failures: usize,
+    },
+    Open {
+        since: Instant,
+    },
+    /// A probe was let through at `since`, and hasn't reported back yet
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+/// Whether a [`CircuitBreaker`] lets calls through
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls are rejected until the cool-down is over
+    Open,
+    /// A probe is being let through, the others are rejected until it's done
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    /// Open the circuit after `threshold` failures in a row, and keep it open
+    /// for `cool_down` before letting a probe through
+    pub fn new(threshold: usize, cool_down: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                threshold: threshold.max(1),
+                cool_down,
+                state: Mutex::new(State::Closed { failures: 0 }),
+            }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match *self.lock() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a call can go through now
+    ///
+    /// Once the cool-down is over, the first caller to ask is let through as
+    /// the probe. A probe that never reports back (its caller panicked, say)
+    /// is given up on after another cool-down.
+    pub fn allow(&self) -> bool {
+        let mut state = self.lock();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { since } | State::HalfOpen { since }
+                if since.elapsed() >= self.inner.cool_down =>
+            {
+                *state = State::HalfOpen {
+                    since: Instant::now(),
+                };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    /// Report how a call that was allowed through went
+    pub fn record(&self, success: bool) {
+        let mut state = self.lock();
+        *state = match (&*state, success) {
+            (_, true) => State::Closed { failures: 0 },
+            (State::Closed { failures }, false) if failures + 1 < self.inner.threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, false) => State::Open {
+                since: Instant::now(),
+            },
+        };
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_and_probe() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(20));
+        breaker.record(false);
+        breaker.record(false);
+        // A success resets the count
+        breaker.record(true);
+        breaker.record(false);
+        breaker.record(false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.clone().record(false);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(25));
+        // Only one probe at a time
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // A failed probe opens it again
+        breaker.record(false);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow());
+        breaker.record(true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow() && breaker.allow());
+    }
+}
@@ -0,0 +1,204 @@
+//! Retry budgets, capping retries to a share of the calls made
+//!
+//! Retries are what turn an upstream's partial outage into a full one: every
+//! caller multiplies its load by its retry count just as the upstream can least
+//! take it. Attaching a [`RetryBudget`] to the retry loops calling the same
+//! upstream lets them retry at most a `ratio` of their calls (plus short bursts),
+//! so an outage adds no more than that to its load.
+//!
+//! In a multi-tenant service, [`RetryBudget::for_key`] gives each tenant a
+//! budget of its own, so one tenant's failures can't use up the retries of the
+//! others.
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How many keys a budget tracks unless told otherwise, see [`RetryBudget::with_key_capacity`]
+const KEY_CAPACITY: usize = 1024;
+
+/// A balance of retries, credited a `ratio` of a retry for each call and
+/// debited one retry for each retry
+///
+/// Clones share the same balance.
+/// ```
+/// use std::time::Duration;
+/// use retryable::{RetryBudget, RetryDelay, RetryStrategy, Retryable};
+///
+/// // Retry up to 10% of the calls, in bursts of up to 5 retries
+/// let budget = RetryBudget::new(0.1, 5);
+/// let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let mut r = Retryable::new(|| Err::<(), _>("unavailable"), strategy)
+///     .with_retry_budget(budget.clone());
+/// assert!(r.try_call().is_err());
+/// // The first attempt and the 3 retries the budget paid for
+/// assert_eq!(r.attempts_made(), 4);
+/// assert_eq!(budget.available(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    ratio: f64,
+    burst: f64,
+    balance: Mutex<f64>,
+    keys: Mutex<Keys>,
+}
+
+/// The budgets handed out by [`RetryBudget::for_key`], least recently used first
+#[derive(Debug)]
+struct Keys {
+    capacity: usize,
+    /// Bumped on every lookup, to order the keys by last use
+    tick: u64,
+    budgets: BTreeMap<String, (RetryBudget, u64)>,
+    by_use: BTreeMap<u64, String>,
+}
+
+impl RetryBudget {
+    /// Retry at most `ratio` (e.g. `0.1`) of the calls, but up to `burst`
+    /// retries in a row once enough calls have been credited
+    ///
+    /// A new budget starts with a full `burst` of retries.
+    pub fn new(ratio: f64, burst: usize) -> Self {
+        Self::from_parts(ratio, burst as f64, KEY_CAPACITY)
+    }
+
+    fn from_parts(ratio: f64, burst: f64, capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                ratio: ratio.max(0.0),
+                burst,
+                balance: Mutex::new(burst),
+                keys: Mutex::new(Keys {
+                    capacity: capacity.max(1),
+                    tick: 0,
+                    budgets: BTreeMap::new(),
+                    by_use: BTreeMap::new(),
+                }),
+            }),
+        }
+    }
+
+    /// Track up to `capacity` keys in [`for_key`](Self::for_key) (1024 by
+    /// default), forgetting the least recently used one past that
+    pub fn with_key_capacity(self, capacity: usize) -> Self {
+        self.keys().capacity = capacity.max(1);
+        self
+    }
+
+    /// The budget of `key` (a tenant, a user, ...), with the same `ratio` and
+    /// `burst` as this one but a balance of its own
+    /// ```
+    /// use retryable::RetryBudget;
+    ///
+    /// let budgets = RetryBudget::new(0.1, 2);
+    /// let noisy = budgets.for_key("tenant-1");
+    /// while noisy.withdraw() {}
+    /// assert_eq!(budgets.for_key("tenant-1").available(), 0);
+    /// assert_eq!(budgets.for_key("tenant-2").available(), 2);
+    /// ```
+    ///
+    /// Keys are tracked in memory, up to the [key capacity](Self::with_key_capacity):
+    /// past it, the least recently used key is forgotten, and starts over with a
+    /// full burst if it's seen again.
+    pub fn for_key(&self, key: &str) -> RetryBudget {
+        let mut keys = self.keys();
+        keys.tick += 1;
+        let tick = keys.tick;
+        if let Some((budget, used)) = keys.budgets.get_mut(key) {
+            let previous = std::mem::replace(used, tick);
+            let budget = budget.clone();
+            keys.by_use.remove(&previous);
+            keys.by_use.insert(tick, key.to_string());
+            return budget;
+        }
+        if keys.budgets.len() >= keys.capacity {
+            if let Some((_, oldest)) = keys.by_use.pop_first() {
+                keys.budgets.remove(&oldest);
+            }
+        }
+        let budget = Self::from_parts(self.inner.ratio, self.inner.burst, keys.capacity);
+        keys.budgets.insert(key.to_string(), (budget.clone(), tick));
+        keys.by_use.insert(tick, key.to_string());
+        budget
+    }
+
+    /// How many keys [`for_key`](Self::for_key) currently tracks
+    pub fn tracked_keys(&self) -> usize {
+        self.keys().budgets.len()
+    }
+
+    /// How many retries could be made right now
+    pub fn available(&self) -> usize {
+        *self.balance() as usize
+    }
+
+    /// Credit a call (its first attempt) to the budget
+    ///
+    /// Retry loops with a budget do this themselves, it's for loops driven by hand.
+    pub fn deposit(&self) {
+        let mut balance = self.balance();
+        *balance = (*balance + self.inner.ratio).min(self.inner.burst);
+    }
+
+    /// Take a retry from the budget, `false` if there isn't one left
+    pub fn withdraw(&self) -> bool {
+        let mut balance = self.balance();
+        if *balance < 1.0 {
+            return false;
+        }
+        *balance -= 1.0;
+        true
+    }
+
+    fn balance(&self) -> MutexGuard<'_, f64> {
+        self.inner.balance.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn keys(&self) -> MutexGuard<'_, Keys> {
+        self.inner.keys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_and_burst() {
+        let budget = RetryBudget::new(0.25, 2);
+        assert!(budget.withdraw() && budget.withdraw());
+        assert!(!budget.withdraw());
+        // A retry for every 4 calls
+        for _ in 0..3 {
+            budget.deposit();
+            assert!(!budget.clone().withdraw());
+        }
+        budget.deposit();
+        assert!(budget.withdraw());
+        // Credit doesn't pile up past the burst
+        for _ in 0..100 {
+            budget.deposit();
+        }
+        assert_eq!(budget.available(), 2);
+    }
+
+    #[test]
+    fn test_keys_are_lru_bounded() {
+        let budgets = RetryBudget::new(0.5, 1).with_key_capacity(2);
+        assert!(budgets.for_key("a").withdraw());
+        assert!(budgets.for_key("b").withdraw());
+        // Using `a` again makes `b` the least recently used
+        assert_eq!(budgets.for_key("a").available(), 0);
+        assert!(budgets.for_key("c").withdraw());
+        assert_eq!(budgets.tracked_keys(), 2);
+        assert_eq!(budgets.for_key("a").available(), 0);
+        // `b` was forgotten, and starts over (pushing `c` out in turn)
+        assert_eq!(budgets.for_key("b").available(), 1);
+        assert_eq!(budgets.for_key("c").available(), 1);
+        // Keyed budgets don't draw from the one they came from
+        assert_eq!(budgets.available(), 1);
+    }
+}
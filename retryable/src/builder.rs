@@ -0,0 +1,146 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Jitter, RetryDelay, RetryStrategy};
+
+/// Builds a [`RetryStrategy`], rejecting incoherent combinations of options
+/// that would otherwise silently misbehave at runtime
+#[derive(Clone, Debug, Default)]
+pub struct RetryStrategyBuilder {
+    strategy: RetryStrategy,
+}
+
+impl RetryStrategyBuilder {
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.strategy.retries = retries;
+        self
+    }
+
+    pub fn delay(mut self, delay: RetryDelay) -> Self {
+        self.strategy.delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.strategy.max_delay = Some(max_delay);
+        self
+    }
+
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.strategy.jitter = jitter;
+        self
+    }
+
+    pub fn max_elapsed(mut self, max_elapsed: Duration) -> Self {
+        self.strategy.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    pub fn max_backoff_total(mut self, max_backoff_total: Duration) -> Self {
+        self.strategy.max_backoff_total = Some(max_backoff_total);
+        self
+    }
+
+    pub fn build(self) -> Result<RetryStrategy, StrategyError> {
+        validate(&self.strategy)?;
+        Ok(self.strategy)
+    }
+}
+
+/// Why a [`RetryStrategy`] was rejected
+#[derive(Clone, Debug, PartialEq)]
+pub enum StrategyError {
+    /// A growing delay capped at zero never actually waits
+    ZeroMaxDelay,
+    /// The max delay is shorter than the very first delay
+    MaxDelayBelowInitial {
+        max_delay: Duration,
+        initial: Duration,
+    },
+    /// Exponential delays must grow (multiplier of at least 1.0)
+    InvalidMultiplier(f64),
+    /// The first retry would already be past `max_elapsed`, so it never happens
+    MaxElapsedBeforeFirstRetry {
+        max_elapsed: Duration,
+        first_delay: Duration,
+    },
+}
+
+impl fmt::Display for StrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrategyError::ZeroMaxDelay => write!(f, "max_delay of zero with a growing delay"),
+            StrategyError::MaxDelayBelowInitial { max_delay, initial } => write!(
+                f,
+                "max_delay ({:?}) is shorter than the initial delay ({:?})",
+                max_delay, initial
+            ),
+            StrategyError::InvalidMultiplier(multiplier) => write!(
+                f,
+                "exponential multiplier must be at least 1.0, got {}",
+                multiplier
+            ),
+            StrategyError::MaxElapsedBeforeFirstRetry {
+                max_elapsed,
+                first_delay,
+            } => write!(
+                f,
+                "max_elapsed ({:?}) is shorter than the first delay ({:?})",
+                max_elapsed, first_delay
+            ),
+        }
+    }
+}
+
+impl Error for StrategyError {}
+
+pub(crate) fn validate(strategy: &RetryStrategy) -> Result<(), StrategyError> {
+    let initial = strategy.delay.initial();
+    if let RetryDelay::Adaptive { min, max, .. } = strategy.delay {
+        if max < min {
+            return Err(StrategyError::MaxDelayBelowInitial {
+                max_delay: max,
+                initial: min,
+            });
+        }
+    }
+    if let Jitter::Decorrelated { cap } = strategy.jitter {
+        if cap < initial {
+            return Err(StrategyError::MaxDelayBelowInitial {
+                max_delay: cap,
+                initial,
+            });
+        }
+    }
+    if let RetryDelay::FullJitter { base, cap } = strategy.delay {
+        if cap < base {
+            return Err(StrategyError::MaxDelayBelowInitial {
+                max_delay: cap,
+                initial: base,
+            });
+        }
+    }
+    if let RetryDelay::Exponential { multiplier, .. } = strategy.delay {
+        if !(multiplier >= 1.0 && multiplier.is_finite()) {
+            return Err(StrategyError::InvalidMultiplier(multiplier));
+        }
+        if strategy.max_delay == Some(Duration::from_secs(0)) {
+            return Err(StrategyError::ZeroMaxDelay);
+        }
+    }
+    if let Some(max_delay) = strategy.max_delay {
+        if max_delay < initial {
+            return Err(StrategyError::MaxDelayBelowInitial { max_delay, initial });
+        }
+    }
+    if let Some(max_elapsed) = strategy.max_elapsed {
+        if strategy.retries > 0 && max_elapsed < initial {
+            return Err(StrategyError::MaxElapsedBeforeFirstRetry {
+                max_elapsed,
+                first_delay: initial,
+            });
+        }
+    }
+    Ok(())
+}
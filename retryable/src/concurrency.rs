@@ -0,0 +1,221 @@
+//! Process-wide limits on concurrent attempts
+//!
+//! When an upstream starts failing, every caller retries at once. Attaching a
+//! [`ConcurrencyLimit`] to a `Retryable` bounds how many attempts for the same
+//! labeled operation can be running at any moment, across all threads.
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::intern;
+
+static LIMITS: Mutex<BTreeMap<&'static str, ConcurrencyLimit>> = Mutex::new(BTreeMap::new());
+
+/// A counting semaphore shared by every attempt using the same label
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimit {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    max: usize,
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_flight: usize,
+    /// One waker per pending [`Acquire`], by its id
+    waiting: BTreeMap<u64, Waker>,
+    next_id: u64,
+}
+
+impl ConcurrencyLimit {
+    /// The process-wide limit for `label`
+    ///
+    /// The first call for a label sets its `max`, later calls share it.
+    pub fn shared(label: &str, max: usize) -> Self {
+        let mut limits = LIMITS.lock().unwrap_or_else(|e| e.into_inner());
+        limits
+            .entry(intern::intern(label))
+            .or_insert_with(|| Self::new(max))
+            .clone()
+    }
+
+    /// A limit that isn't registered under any label
+    pub fn new(max: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                max: max.max(1),
+                state: Mutex::new(State::default()),
+                available: Condvar::new(),
+            }),
+        }
+    }
+
+    /// How many attempts currently hold a permit
+    pub fn in_flight(&self) -> usize {
+        self.state().in_flight
+    }
+
+    /// Block until a permit is available
+    pub fn acquire(&self) -> Permit {
+        let mut state = self.state();
+        while state.in_flight >= self.inner.max {
+            state = self
+                .inner
+                .available
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        state.in_flight += 1;
+        Permit {
+            limit: self.clone(),
+        }
+    }
+
+    /// Wait (without blocking the executor) until a permit is available
+    pub fn acquire_async(&self) -> Acquire<'_> {
+        Acquire {
+            limit: self,
+            id: None,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Held while an attempt runs, releasing its slot when dropped
+#[derive(Debug)]
+pub struct Permit {
+    limit: ConcurrencyLimit,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.limit.state();
+        state.in_flight -= 1;
+        // Waiters re-check the count, so waking all of them can't over-admit
+        for waker in state.waiting.values() {
+            waker.wake_by_ref();
+        }
+        self.limit.inner.available.notify_one();
+    }
+}
+
+/// Future returned by [`ConcurrencyLimit::acquire_async`]
+pub struct Acquire<'a> {
+    limit: &'a ConcurrencyLimit,
+    /// Where its waker is kept while it waits
+    id: Option<u64>,
+}
+
+impl Future for Acquire<'_> {
+    type Output = Permit;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let this = self.get_mut();
+        let mut state = this.limit.state();
+        if state.in_flight < this.limit.inner.max {
+            state.in_flight += 1;
+            if let Some(id) = this.id.take() {
+                state.waiting.remove(&id);
+            }
+            return Poll::Ready(Permit {
+                limit: this.limit.clone(),
+            });
+        }
+        let id = *this.id.get_or_insert_with(|| {
+            state.next_id += 1;
+            state.next_id
+        });
+        match state.waiting.get_mut(&id) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => {
+                state.waiting.insert(id, cx.waker().clone());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.limit.state().waiting.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_shared() {
+        let a = ConcurrencyLimit::shared("concurrency::shared", 2);
+        let b = ConcurrencyLimit::shared("concurrency::shared", 10);
+        let _permit = a.acquire();
+        assert_eq!(b.in_flight(), 1);
+        assert_eq!(b.inner.max, 2);
+    }
+
+    #[test]
+    fn test_acquire_bounds_concurrency() {
+        let limit = ConcurrencyLimit::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let (limit, running, peak) = (limit.clone(), running.clone(), peak.clone());
+                std::thread::spawn(move || {
+                    let _permit = limit.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(limit.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_acquire_async_keeps_one_waker() {
+        let limit = ConcurrencyLimit::new(1);
+        let permit = limit.acquire();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut first = limit.acquire_async();
+        let mut second = limit.acquire_async();
+        for _ in 0..3 {
+            assert!(Pin::new(&mut first).poll(&mut cx).is_pending());
+        }
+        assert!(Pin::new(&mut second).poll(&mut cx).is_pending());
+        assert_eq!(limit.state().waiting.len(), 2);
+
+        drop(second);
+        assert_eq!(limit.state().waiting.len(), 1);
+        drop(permit);
+        let permit = match Pin::new(&mut first).poll(&mut cx) {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => panic!("a permit was released"),
+        };
+        assert!(limit.state().waiting.is_empty());
+        assert_eq!(limit.in_flight(), 1);
+        drop((first, permit));
+        assert_eq!(limit.in_flight(), 0);
+    }
+}
@@ -0,0 +1,74 @@
+//! Thread-local deadlines, see [`deadline!`](crate::deadline)
+//!
+//! Every retry loop started on a thread while a deadline is in place caps its
+//! `max_elapsed` to the time left, so retries never push an operation past the
+//! deadline of its caller. Since deadlines are per thread, an `async` loop only
+//! sees the deadline of the thread it first runs on.
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Put a deadline `budget` from now in place on this thread, until the returned
+/// guard is dropped
+///
+/// Nested deadlines can only shorten the one in place, never extend it.
+pub fn enter(budget: Duration) -> DeadlineGuard {
+    enter_at(Instant::now() + budget)
+}
+
+/// Like [`enter`], with the deadline as an instant
+pub fn enter_at(deadline: Instant) -> DeadlineGuard {
+    let previous = DEADLINE.with(|current| {
+        let previous = current.get();
+        let earliest = previous.map_or(deadline, |previous| previous.min(deadline));
+        current.set(Some(earliest));
+        previous
+    });
+    DeadlineGuard { previous }
+}
+
+/// The deadline in place on this thread, if any
+pub fn current() -> Option<Instant> {
+    DEADLINE.with(Cell::get)
+}
+
+/// Time left until the deadline in place on this thread (zero once it's passed)
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Restores the previous deadline when dropped
+#[must_use = "the deadline is lifted as soon as the guard is dropped"]
+pub struct DeadlineGuard {
+    previous: Option<Instant>,
+}
+
+impl Drop for DeadlineGuard {
+    fn drop(&mut self) {
+        DEADLINE.with(|current| current.set(self.previous));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting() {
+        assert_eq!(remaining(), None);
+        let outer = enter(Duration::from_secs(10));
+        {
+            let _inner = enter(Duration::from_secs(1));
+            assert!(remaining().unwrap() <= Duration::from_secs(1));
+            // Can't extend the deadline of the caller
+            let _longer = enter(Duration::from_secs(60));
+            assert!(remaining().unwrap() <= Duration::from_secs(1));
+        }
+        assert!(remaining().unwrap() > Duration::from_secs(1));
+        drop(outer);
+        assert_eq!(remaining(), None);
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+/// What to do after an attempt, see [`Decide`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Return this result
+    Accept,
+    /// Try again after the strategy's delay
+    Retry,
+    /// Try again after the given delay instead of the strategy's
+    RetryAfter(Duration),
+    /// Return this result without any further attempts
+    Abort,
+}
+
+impl Decision {
+    /// Retry every `Err`, accept every `Ok` (the behavior without a [`Decide`] hook)
+    pub fn default_for<T, E>(res: &Result<T, E>) -> Self {
+        match res {
+            Ok(_) => Decision::Accept,
+            Err(_) => Decision::Retry,
+        }
+    }
+}
+
+/// Hook deciding whether an attempt's result should be retried
+///
+/// Unlike looking at the error alone, this sees `Ok` values too, so
+/// "successful but unacceptable" responses (an HTTP 202, an empty page of results)
+/// can be retried:
+/// ```
+/// use retryable::{Decision, RetryDelay, RetryStrategy, Retryable};
+/// use std::time::Duration;
+///
+/// let mut polls = 0;
+/// let poll_job = || -> Result<u16, ()> {
+///     polls += 1;
+///     Ok(if polls < 3 { 202 } else { 200 })
+/// };
+/// let strategy = RetryStrategy::new(5, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let mut r = Retryable::new(poll_job, strategy).with_decider(|res: &Result<u16, ()>| match res {
+///     Ok(202) => Decision::Retry,
+///     Ok(_) => Decision::Accept,
+///     Err(_) => Decision::Abort,
+/// });
+/// assert_eq!(r.try_call(), Ok(200));
+/// ```
+pub trait Decide<T, E> {
+    fn decide(&mut self, res: &Result<T, E>) -> Decision;
+}
+
+impl<F, T, E> Decide<T, E> for F
+where
+    F: FnMut(&Result<T, E>) -> Decision,
+{
+    fn decide(&mut self, res: &Result<T, E>) -> Decision {
+        self(res)
+    }
+}
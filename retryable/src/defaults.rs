@@ -0,0 +1,76 @@
+//! Thread-local default strategies, see [`with_default_strategy`]
+use std::cell::RefCell;
+
+use crate::RetryStrategy;
+
+thread_local! {
+    static DEFAULTS: RefCell<Vec<RetryStrategy>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run `f` with `strategy` as [`RetryStrategy::default()`] on this thread,
+/// which is what `retryable!` starts from
+///
+/// For tests and special code paths that need tighter (or looser) retries than
+/// the rest of the program, without changing them for every other thread:
+/// ```
+/// use std::time::Duration;
+/// use retryable::{retryable, with_default_strategy, RetryDelay, RetryStrategy};
+///
+/// let mut calls = 0;
+/// let no_retries = RetryStrategy::new(0, RetryDelay::Fixed(Duration::ZERO));
+/// let res: Result<(), ()> = with_default_strategy(no_retries, || {
+///     retryable!(|| { calls += 1; Err(()) })
+/// });
+/// assert_eq!((res, calls), (Err(()), 1));
+/// ```
+/// Scopes nest, the innermost one wins. Options given to `retryable!` still
+/// apply on top of the scoped default.
+pub fn with_default_strategy<R>(strategy: RetryStrategy, f: impl FnOnce() -> R) -> R {
+    DEFAULTS.with(|defaults| defaults.borrow_mut().push(strategy));
+    let _scope = Scope;
+    f()
+}
+
+/// The innermost scoped default on this thread
+pub(crate) fn current() -> Option<RetryStrategy> {
+    DEFAULTS.with(|defaults| defaults.borrow().last().cloned())
+}
+
+/// Pops the scoped default, even if `f` panics
+struct Scope;
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        DEFAULTS.with(|defaults| defaults.borrow_mut().pop());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryDelay;
+    use std::time::Duration;
+
+    #[test]
+    fn test_nesting() {
+        let retries = |strategy: RetryStrategy| strategy.retries;
+        assert_eq!(retries(RetryStrategy::default()), 3);
+        let outer = RetryStrategy::new(1, RetryDelay::Fixed(Duration::ZERO));
+        let inner = RetryStrategy::new(7, RetryDelay::Fixed(Duration::ZERO));
+        with_default_strategy(outer, || {
+            assert_eq!(retries(RetryStrategy::default()), 1);
+            with_default_strategy(inner, || {
+                assert_eq!(retries(RetryStrategy::default()), 7);
+            });
+            assert_eq!(retries(RetryStrategy::default()), 1);
+        });
+        assert_eq!(retries(RetryStrategy::default()), 3);
+
+        // Another thread still sees the built-in default
+        let outer = RetryStrategy::new(1, RetryDelay::Fixed(Duration::ZERO));
+        with_default_strategy(outer, || {
+            let elsewhere = std::thread::spawn(|| RetryStrategy::default().retries);
+            assert_eq!(elsewhere.join().unwrap(), 3);
+        });
+    }
+}
@@ -0,0 +1,171 @@
+//! The bookkeeping of a retry loop, see [`RetryLoop`]
+use std::panic::Location;
+use std::time::Duration;
+
+#[cfg(feature = "observability")]
+use crate::intern;
+use crate::{
+    CircuitBreaker, Decision, RetryBudget, RetryPolicy, RetryProgress, RetryState, RetryStrategy,
+};
+
+/// One run of a retry loop, whatever makes its attempts and waits out its backoffs
+///
+/// Every loop of this crate (the wrappers, the macros, the `tower` middleware)
+/// is driven through one of these, so they all keep [`RetryProgress`], honor a
+/// [`RetryBudget`] and a [`CircuitBreaker`], and report to `telemetry` and
+/// `observability` with those features on. Drive one by hand for loops of your
+/// own, like around an attempt that isn't a closure:
+/// ```
+/// use std::time::Duration;
+/// use retryable::{Decision, RetryDelay, RetryLoop, RetryStrategy};
+///
+/// let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let mut retry = RetryLoop::new(strategy);
+/// let mut calls = 0;
+/// let res = loop {
+///     calls += 1;
+///     let res: Result<u32, &str> = if calls < 3 { Err("busy") } else { Ok(calls) };
+///     match retry.after(Decision::default_for(&res)) {
+///         Some(delay) => {
+///             std::thread::sleep(delay);
+///             retry.resume();
+///         }
+///         None => {
+///             retry.finish(res.is_err());
+///             break res;
+///         }
+///     }
+/// };
+/// assert_eq!(res, Ok(3));
+/// ```
+/// A loop dropped without [`finish`](Self::finish) (an attempt panicked, a
+/// future was cancelled) isn't counted in `telemetry`.
+pub struct RetryLoop {
+    state: RetryState,
+    progress: RetryProgress,
+    budget: Option<RetryBudget>,
+    breaker: Option<CircuitBreaker>,
+    #[cfg(any(feature = "telemetry", feature = "observability"))]
+    site: &'static Location<'static>,
+    #[cfg(feature = "observability")]
+    in_flight: observability::InFlight,
+}
+
+impl RetryLoop {
+    /// Start a loop now, reported against the caller's `file:line:column`
+    #[track_caller]
+    pub fn new(strategy: RetryStrategy) -> Self {
+        Self::at(strategy, Location::caller())
+    }
+
+    /// Start a loop now, reported against `site`, for loops that start later
+    /// than their caller can be tracked (in an `async` body)
+    pub(crate) fn at(strategy: RetryStrategy, site: &'static Location<'static>) -> Self {
+        #[cfg(not(any(feature = "telemetry", feature = "observability")))]
+        let _ = site;
+        let progress = RetryProgress::default();
+        progress.start(strategy.retries);
+        Self {
+            #[cfg(feature = "observability")]
+            in_flight: observability::begin(observability::Kind::Retry, intern::site(site)),
+            #[cfg(any(feature = "telemetry", feature = "observability"))]
+            site,
+            state: RetryState::new(strategy),
+            progress,
+            budget: None,
+            breaker: None,
+        }
+    }
+
+    /// Keep `progress` (a handle given out beforehand) up to date rather than
+    /// a progress of its own
+    pub fn with_progress(mut self, progress: RetryProgress) -> Self {
+        progress.start(self.state.strategy.retries);
+        self.progress = progress;
+        self
+    }
+
+    /// Only retry while `budget` has retries left, crediting it with this call
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        budget.deposit();
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Tell `breaker` how each attempt went (accepted or not), and stop
+    /// retrying as soon as it opens
+    ///
+    /// Whether the first attempt may go through is up to the caller, so it can
+    /// answer an open circuit without starting a loop.
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// Apply the [`Decision`] made about an attempt, returning how long to
+    /// back off before the next one (or `None` if the loop is done)
+    pub fn after(&mut self, decision: Decision) -> Option<Duration> {
+        let next = self.state.after(decision);
+        self.settle(decision == Decision::Accept, next)
+    }
+
+    /// Let a [`RetryPolicy`] make the decision about an attempt, see [`after`](Self::after)
+    pub fn after_policy<T, E>(
+        &mut self,
+        policy: &mut dyn RetryPolicy<T, E>,
+        outcome: &Result<T, E>,
+    ) -> Option<Duration> {
+        let next = self.state.after_policy(policy, outcome);
+        self.settle(outcome.is_ok(), next)
+    }
+
+    fn settle(&mut self, accepted: bool, next: Option<Duration>) -> Option<Duration> {
+        if let Some(breaker) = &self.breaker {
+            breaker.record(accepted);
+        }
+        let next = next
+            .filter(|_| self.budget.as_ref().is_none_or(RetryBudget::withdraw))
+            .filter(|_| self.breaker.as_ref().is_none_or(CircuitBreaker::allow));
+        self.progress.attempted(self.state.attempts(), next);
+        #[cfg(feature = "observability")]
+        if let Some(delay) = next {
+            self.in_flight.backoff(delay);
+        }
+        next
+    }
+
+    /// The backoff is over, the next attempt starts
+    pub fn resume(&mut self) {
+        self.progress.resume();
+        #[cfg(feature = "observability")]
+        self.in_flight.next_attempt();
+    }
+
+    /// The loop is done, giving up with an error if `failed`
+    pub fn finish(self, failed: bool) {
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::record(
+            self.site,
+            self.state.attempts(),
+            failed,
+            self.progress.time_in_backoff(),
+        );
+        #[cfg(feature = "observability")]
+        observability::record(intern::site(self.site), self.state.attempt().elapsed);
+        #[cfg(feature = "observability")]
+        if failed {
+            self.in_flight.failed();
+        }
+        let _ = failed;
+    }
+
+    /// The progress so far, as handed to `on_retry` hooks
+    pub fn progress(&self) -> &RetryProgress {
+        &self.progress
+    }
+
+    /// How many attempts have been made
+    pub fn attempts(&self) -> usize {
+        self.state.attempts()
+    }
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use duration_grammar::parse_nanos;
+
+/// Parse a short duration like `500ms`, `1m30s`, `1.5h` (a bare number is seconds)
+///
+/// Supported units are `ns`, `us`, `ms`, `s`, `m` and `h`. It's the grammar of
+/// the macros' duration options too, like `#[retry(delay = "250ms")]`.
+/// ```
+/// use std::time::Duration;
+/// use retryable::parse_duration;
+///
+/// assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+/// assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+/// assert!(parse_duration("30 parsecs").is_err());
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, ParseDurationError> {
+    parse_nanos(s)
+        .map(Duration::from_nanos)
+        .ok_or_else(|| ParseDurationError(s.to_string()))
+}
+
+/// A duration literal of the `retryable!` options (`for=30s`), parsed while
+/// compiling so that an invalid one is a compile error, not a panic
+/// ```compile_fail
+/// use retryable::retryable;
+///
+/// let _ = retryable!(|| Err::<(), ()>(()); for=30parsecs);
+/// ```
+#[doc(hidden)]
+pub const fn __duration_literal(s: &str) -> Duration {
+    match parse_nanos(s) {
+        Some(nanos) => Duration::from_nanos(nanos),
+        None => panic!("invalid duration literal (expected e.g. 500ms, 30s, 1m30s, 1h)"),
+    }
+}
+
+/// A duration string that [`parse_duration`] couldn't understand
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseDurationError(String);
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid duration {:?} (expected e.g. 500ms, 30s, 1m30s, 1h)",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseDurationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250us"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("0.5s"), Ok(Duration::from_millis(500)));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("1.2.3s").is_err());
+    }
+}
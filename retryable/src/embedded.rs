@@ -0,0 +1,72 @@
+//! Pacing retries with an `embedded-hal` delay
+//!
+//! On a microcontroller there's no OS thread to put to sleep, so backoffs are
+//! waited out with a [`DelayNs`] timer instead:
+//! ```ignore
+//! let mut read = Retryable::new(|| sensor.read_temperature(), strategy)
+//!     .with_delay(timer);
+//! let celsius = read.try_call()?;
+//! ```
+use std::time::Duration;
+
+use embedded_hal::delay::DelayNs;
+
+use crate::Retryable;
+
+/// Wait out `duration`, which may be longer than a single `delay_ns` call can
+pub fn delay_for(delay: &mut impl DelayNs, duration: Duration) {
+    let millis = duration.as_millis();
+    for _ in 0..millis / u128::from(u32::MAX) {
+        delay.delay_ms(u32::MAX);
+    }
+    delay.delay_ms((millis % u128::from(u32::MAX)) as u32);
+    delay.delay_ns(duration.subsec_nanos() % 1_000_000);
+}
+
+impl<F, T, E> Retryable<F, T, E>
+where
+    F: FnMut() -> Result<T, E>,
+{
+    /// Wait out each backoff on a [`DelayNs`] timer
+    pub fn with_delay<D: DelayNs + 'static>(self, mut delay: D) -> Self {
+        self.with_sleeper(move |duration| delay_for(&mut delay, duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RetryDelay, RetryStrategy};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Adds up the requested delays instead of waiting
+    struct FakeTimer(Rc<Cell<u64>>);
+
+    impl DelayNs for FakeTimer {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.set(self.0.get() + u64::from(ns));
+        }
+    }
+
+    #[test]
+    fn test_with_delay() {
+        let waited = Rc::new(Cell::new(0));
+        let mut reads = 0;
+        let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_micros(1500)));
+        let mut r = Retryable::new(
+            || {
+                reads += 1;
+                if reads < 3 {
+                    Err("sensor busy")
+                } else {
+                    Ok(21)
+                }
+            },
+            strategy,
+        )
+        .with_delay(FakeTimer(waited.clone()));
+        assert_eq!(r.try_call(), Ok(21));
+        assert_eq!(waited.get(), 3_000_000);
+    }
+}
@@ -0,0 +1,152 @@
+//! Retrying against a different replica on each attempt, see [`EndpointRotation`]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{Attempt, RetryStrategy, Retryable};
+
+/// Hands out endpoints (addresses, URLs, clients...) in turn, so each attempt
+/// of a retry loop goes to the next replica instead of the one that just failed
+///
+/// Endpoints are picked round-robin, or in proportion to their weights with a
+/// smooth weighted round-robin (a weight 3 endpoint is picked 3 times as often,
+/// without being picked 3 times in a row).
+/// ```
+/// use std::time::Duration;
+/// use retryable::{EndpointRotation, RetryDelay, RetryStrategy};
+///
+/// let replicas = EndpointRotation::weighted(vec![("db-1", 2), ("db-2", 1)]);
+/// let strategy = RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let mut tried = Vec::new();
+/// let res = replicas.call(strategy, |replica| {
+///     tried.push(*replica);
+///     if *replica == "db-2" { Ok("row") } else { Err("down") }
+/// });
+/// assert_eq!(res, Ok("row"));
+/// assert_eq!(tried, vec!["db-1", "db-2"]);
+/// ```
+#[derive(Debug)]
+pub struct EndpointRotation<T> {
+    endpoints: Vec<T>,
+    /// Indices into `endpoints`, one full round of picks
+    schedule: Vec<usize>,
+    next: AtomicUsize,
+}
+
+impl<T> EndpointRotation<T> {
+    /// Pick each endpoint in turn
+    ///
+    /// Panics if there are no endpoints
+    pub fn round_robin(endpoints: impl IntoIterator<Item = T>) -> Self {
+        Self::weighted(endpoints.into_iter().map(|endpoint| (endpoint, 1)))
+    }
+
+    /// Pick endpoints in proportion to their weights, endpoints weighing 0 are never picked
+    ///
+    /// Panics if all of the weights are 0 (or there are no endpoints)
+    pub fn weighted(endpoints: impl IntoIterator<Item = (T, u32)>) -> Self {
+        let (endpoints, weights): (Vec<T>, Vec<u32>) = endpoints.into_iter().unzip();
+        let divisor = weights.iter().fold(0, |a, b| gcd(a, *b));
+        assert!(
+            divisor > 0,
+            "EndpointRotation needs an endpoint with a weight above 0"
+        );
+        let weights: Vec<i64> = weights.iter().map(|w| i64::from(w / divisor)).collect();
+        let total: i64 = weights.iter().sum();
+
+        // Smooth weighted round-robin (as in nginx)
+        let mut current = vec![0; weights.len()];
+        let schedule = (0..total)
+            .map(|_| {
+                for (current, weight) in current.iter_mut().zip(&weights) {
+                    *current += weight;
+                }
+                let (picked, _) = current
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(i, current)| (**current, std::cmp::Reverse(*i)))
+                    .expect("there's at least one endpoint");
+                current[picked] -= total;
+                picked
+            })
+            .collect();
+        Self {
+            endpoints,
+            schedule,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The next endpoint in the rotation, shared by every caller
+    pub fn next(&self) -> &T {
+        let pick = self.next.fetch_add(1, Ordering::Relaxed) % self.schedule.len();
+        &self.endpoints[self.schedule[pick]]
+    }
+
+    /// The endpoint for a given attempt (the first endpoint for the first
+    /// attempt), for a [`RetryPolicy`](crate::RetryPolicy) or anything else that
+    /// knows which attempt it's on, without advancing the shared rotation
+    pub fn for_attempt(&self, attempt: &Attempt) -> &T {
+        let pick = attempt.number.saturating_sub(1) % self.schedule.len();
+        &self.endpoints[self.schedule[pick]]
+    }
+
+    pub fn endpoints(&self) -> &[T] {
+        &self.endpoints
+    }
+
+    /// Retry `operation` with the strategy, moving on to the next endpoint for each attempt
+    #[track_caller]
+    pub fn call<R, E>(
+        &self,
+        strategy: RetryStrategy,
+        mut operation: impl FnMut(&T) -> Result<R, E>,
+    ) -> Result<R, E> {
+        Retryable::new(|| operation(self.next()), strategy).try_call()
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn attempt(number: usize) -> Attempt {
+        Attempt {
+            number,
+            elapsed: Duration::from_secs(0),
+            backoff: Duration::from_secs(0),
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let rotation = EndpointRotation::round_robin(vec!["a", "b", "c"]);
+        let picks: Vec<_> = (0..5).map(|_| *rotation.next()).collect();
+        assert_eq!(picks, vec!["a", "b", "c", "a", "b"]);
+        assert_eq!(*rotation.for_attempt(&attempt(1)), "a");
+        assert_eq!(*rotation.for_attempt(&attempt(6)), "c");
+    }
+
+    #[test]
+    fn test_weighted() {
+        let rotation = EndpointRotation::weighted(vec![("a", 50), ("b", 10), ("c", 20), ("d", 0)]);
+        let picks: Vec<_> = (1..=8)
+            .map(|n| *rotation.for_attempt(&attempt(n)))
+            .collect();
+        // Reduced to 5:1:2, and spread out rather than "a" 5 times in a row
+        assert_eq!(picks, vec!["a", "c", "a", "a", "b", "a", "c", "a"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "weight above 0")]
+    fn test_no_endpoints() {
+        EndpointRotation::<&str>::round_robin(vec![]);
+    }
+}
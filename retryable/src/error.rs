@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt;
+
+/// The final error of an exhausted retry, along with what was being attempted
+///
+/// Returned by `retryable!(...; context="...")`, the context is prefixed to the
+/// inner error's `Display` so logs explain which operation gave up.
+#[derive(Debug)]
+pub struct RetryError<E> {
+    context: String,
+    source: E,
+}
+
+impl<E> RetryError<E> {
+    pub fn new(context: impl Into<String>, source: E) -> Self {
+        Self {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// What the retried operation was doing
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    pub fn get_ref(&self) -> &E {
+        &self.source
+    }
+
+    pub fn into_inner(self) -> E {
+        self.source
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.source)
+    }
+}
+
+impl<E: Error + 'static> Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,89 @@
+//! Runtime support for the [`flaky_test`](crate::flaky_test) attribute
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+/// Whether a test body's return value means it failed
+pub trait TestOutcome {
+    fn failed(&self) -> bool;
+}
+
+impl TestOutcome for () {
+    fn failed(&self) -> bool {
+        false
+    }
+}
+
+impl<T, E> TestOutcome for Result<T, E> {
+    fn failed(&self) -> bool {
+        self.is_err()
+    }
+}
+
+/// Run a test body up to `attempts` times, returning the first passing run
+/// (or the last failure, re-raising its panic)
+pub fn run<T: TestOutcome>(
+    name: &str,
+    attempts: usize,
+    delay: Duration,
+    mut body: impl FnMut() -> T,
+) -> T {
+    let mut flakes = 0;
+    loop {
+        let res = panic::catch_unwind(AssertUnwindSafe(&mut body));
+        let passed = matches!(&res, Ok(outcome) if !outcome.failed());
+        if passed || flakes + 1 >= attempts {
+            if passed && flakes > 0 {
+                eprintln!("test {} passed after {} flake(s)", name, flakes);
+            }
+            return match res {
+                Ok(outcome) => outcome,
+                Err(panic) if attempts > 1 => {
+                    panic::resume_unwind(crate::unwind::enrich(panic, name, flakes + 1))
+                }
+                Err(panic) => panic::resume_unwind(panic),
+            };
+        }
+        flakes += 1;
+        eprintln!("test {} failed (attempt {} of {}), retrying", name, flakes, attempts);
+        std::thread::sleep(delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flaky_test;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static PANICS: AtomicUsize = AtomicUsize::new(0);
+    static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+    #[flaky_test(attempts = 3)]
+    fn test_flaky_panics() {
+        assert!(PANICS.fetch_add(1, Ordering::SeqCst) >= 2);
+    }
+
+    #[flaky_test(attempts = 4, delay_ms = 1)]
+    fn test_flaky_result() -> Result<(), String> {
+        match ERRORS.fetch_add(1, Ordering::SeqCst) {
+            0..=2 => Err("not yet".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    #[flaky_test(attempts = 2)]
+    #[should_panic(expected = "test_flaky_exhausted' panicked on attempt 2: always")]
+    fn test_flaky_exhausted() {
+        panic!("always");
+    }
+
+    #[test]
+    fn test_run_attempts() {
+        let mut calls = 0;
+        let res: Result<(), ()> = super::run("calls", 3, Default::default(), || {
+            calls += 1;
+            Err(())
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+    }
+}
@@ -0,0 +1,99 @@
+//! Filesystem operations that retry transient errors
+//!
+//! Drop-in replacements for their `std::fs` counterparts, for files on network
+//! shares or on Windows (where virus scanners and indexers briefly hold files open).
+//! Interrupted/busy operations and sharing violations are retried with a short
+//! backoff, see [`default_strategy`].
+//! ```no_run
+//! let config = retryable::fs::read("//fileserver/share/config.toml")?;
+//! retryable::fs::rename("report.tmp", "report.csv")?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{Decision, RetryDelay, RetryStrategy, Retryable};
+
+/// `EBUSY` (the same value on Linux, macOS and the BSDs)
+#[cfg(unix)]
+const TRANSIENT_OS_ERRORS: &[i32] = &[16];
+/// `ERROR_SHARING_VIOLATION`, `ERROR_LOCK_VIOLATION`
+#[cfg(windows)]
+const TRANSIENT_OS_ERRORS: &[i32] = &[32, 33];
+#[cfg(not(any(unix, windows)))]
+const TRANSIENT_OS_ERRORS: &[i32] = &[];
+
+/// Up to 5 retries, backing off from 10ms up to 200ms
+pub fn default_strategy() -> RetryStrategy {
+    let mut strategy = RetryStrategy::new(5, RetryDelay::exponential(Duration::from_millis(10)));
+    strategy.with_max_delay(Duration::from_millis(200));
+    strategy
+}
+
+/// Whether the filesystem operation could succeed if tried again
+pub fn is_transient(e: &io::Error) -> bool {
+    match e.kind() {
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => true,
+        _ => e
+            .raw_os_error()
+            .is_some_and(|code| TRANSIENT_OS_ERRORS.contains(&code)),
+    }
+}
+
+fn retry<T>(op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    Retryable::new(op, default_strategy())
+        .with_decider(|res: &io::Result<T>| match res {
+            Ok(_) => Decision::Accept,
+            Err(e) if is_transient(e) => Decision::Retry,
+            Err(_) => Decision::Abort,
+        })
+        .try_call()
+}
+
+/// Retrying [`std::fs::read`]
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    retry(|| std::fs::read(&path))
+}
+
+/// Retrying [`std::fs::write`]
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    retry(|| std::fs::write(&path, &contents))
+}
+
+/// Retrying [`std::fs::remove_file`]
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    retry(|| std::fs::remove_file(&path))
+}
+
+/// Retrying [`std::fs::rename`]
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    retry(|| std::fs::rename(&from, &to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&io::ErrorKind::Interrupted.into()));
+        assert!(!is_transient(&io::ErrorKind::NotFound.into()));
+        assert!(!is_transient(&io::ErrorKind::PermissionDenied.into()));
+        #[cfg(unix)]
+        assert!(is_transient(&io::Error::from_raw_os_error(16)));
+    }
+
+    #[test]
+    fn test_fs() {
+        let dir = std::env::temp_dir();
+        let from = dir.join(format!("retryable-fs-{}.tmp", std::process::id()));
+        let to = from.with_extension("txt");
+        write(&from, "contents").unwrap();
+        rename(&from, &to).unwrap();
+        assert_eq!(read(&to).unwrap(), b"contents");
+        remove_file(&to).unwrap();
+        // Not transient, so this fails straight away
+        assert_eq!(read(&to).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
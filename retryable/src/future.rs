@@ -0,0 +1,438 @@
+//! Retrying `async` functions
+//!
+//! Delays between attempts use a runtime-agnostic [`sleep`], so these work
+//! with any executor.
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::progress::SendRetryHook;
+use crate::timer;
+use crate::{
+    ConcurrencyLimit, Decide, Decision, RetryBudget, RetryLoop, RetryPolicy, RetryProgress,
+    RetryStrategy,
+};
+
+/// A single attempt at an async operation, awaited in place by [`AsyncRetryable`]
+///
+/// Closures returning a future (`|| async { ... }`, `|| client.fetch(url)`) implement
+/// this already. The returned future may also borrow from the attempt itself
+/// (a lending closure), so per-attempt state like a connection doesn't need to be
+/// cloned or boxed into each future:
+/// ```
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+/// use retryable::future::AsyncAttempt;
+///
+/// struct Reconnect {
+///     tries: usize,
+/// }
+///
+/// /// Borrows the `Reconnect` for the duration of one attempt
+/// struct Connecting<'a>(&'a mut Reconnect);
+///
+/// impl Future for Connecting<'_> {
+///     type Output = Result<usize, ()>;
+///
+///     fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+///         self.0.tries += 1;
+///         Poll::Ready(if self.0.tries < 3 { Err(()) } else { Ok(self.0.tries) })
+///     }
+/// }
+///
+/// impl AsyncAttempt for Reconnect {
+///     type Output = Result<usize, ()>;
+///     type Future<'a> = Connecting<'a>;
+///
+///     fn attempt(&mut self) -> Connecting<'_> {
+///         Connecting(self)
+///     }
+/// }
+/// ```
+pub trait AsyncAttempt {
+    type Output;
+    type Future<'a>: Future<Output = Self::Output>
+    where
+        Self: 'a;
+
+    fn attempt(&mut self) -> Self::Future<'_>;
+}
+
+impl<F, Fut> AsyncAttempt for F
+where
+    F: FnMut() -> Fut,
+    Fut: Future,
+{
+    type Output = Fut::Output;
+    type Future<'a>
+        = Fut
+    where
+        Self: 'a;
+
+    fn attempt(&mut self) -> Fut {
+        self()
+    }
+}
+
+/// Async counterpart to [`Retryable`](crate::Retryable)
+///
+/// Each [`AsyncAttempt`] is awaited in place, so no attempt is boxed. The
+/// decider, policy and hook have to be `Send`, so the future of
+/// [`try_call`](Self::try_call) is `Send` whenever the attempts' are, and can
+/// be handed to `tokio::spawn`.
+pub struct AsyncRetryable<A, T, E>
+where
+    A: AsyncAttempt<Output = Result<T, E>>,
+{
+    inner: A,
+    strategy: RetryStrategy,
+    decider: Option<Box<dyn Decide<T, E> + Send>>,
+    policy: Option<Box<dyn RetryPolicy<T, E> + Send>>,
+    limit: Option<ConcurrencyLimit>,
+    budget: Option<RetryBudget>,
+    progress: RetryProgress,
+    on_retry: Option<SendRetryHook>,
+}
+
+impl<A, T, E> AsyncRetryable<A, T, E>
+where
+    A: AsyncAttempt<Output = Result<T, E>>,
+{
+    /// Wrap a given async function/closure in a AsyncRetryable, with a given strategy
+    pub fn new(func: A, strategy: RetryStrategy) -> Self {
+        Self {
+            inner: func,
+            strategy,
+            decider: None,
+            policy: None,
+            limit: None,
+            budget: None,
+            progress: RetryProgress::default(),
+            on_retry: None,
+        }
+    }
+
+    /// Decide what to do with each result (including `Ok` values),
+    /// instead of retrying every `Err`
+    pub fn with_decider<D: Decide<T, E> + Send + 'static>(mut self, decider: D) -> Self {
+        self.decider = Some(Box::new(decider));
+        self
+    }
+
+    /// Hand every decision (classification, delay and when to stop) to a
+    /// [`RetryPolicy`], in place of the strategy and decider
+    pub fn with_policy<P: RetryPolicy<T, E> + Send + 'static>(mut self, policy: P) -> Self {
+        self.policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Called before each backoff, with the progress of the loop so far
+    pub fn on_retry<H: FnMut(&RetryProgress) + Send + 'static>(mut self, hook: H) -> Self {
+        self.on_retry = Some(Box::new(hook));
+        self
+    }
+
+    /// Shared handle to the progress of the current (or last) retry loop,
+    /// readable from another thread while `try_call` runs
+    pub fn progress(&self) -> RetryProgress {
+        self.progress.clone()
+    }
+
+    /// See [`RetryProgress::attempts_made`]
+    pub fn attempts_made(&self) -> usize {
+        self.progress.attempts_made()
+    }
+
+    /// See [`RetryProgress::elapsed`]
+    pub fn elapsed(&self) -> Duration {
+        self.progress.elapsed()
+    }
+
+    /// See [`RetryProgress::next_delay`]
+    pub fn next_delay(&self) -> Option<Duration> {
+        self.progress.next_delay()
+    }
+
+    /// See [`RetryProgress::remaining_retries`]
+    pub fn remaining_retries(&self) -> usize {
+        self.progress.remaining_retries()
+    }
+
+    /// See [`RetryProgress::time_in_attempts`]
+    pub fn time_in_attempts(&self) -> Duration {
+        self.progress.time_in_attempts()
+    }
+
+    /// See [`RetryProgress::time_in_backoff`]
+    pub fn time_in_backoff(&self) -> Duration {
+        self.progress.time_in_backoff()
+    }
+
+    /// Wait for a permit from the limit before each attempt, bounding how many
+    /// attempts sharing the limit run at once
+    pub fn with_concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Only retry while `budget` has retries left, see
+    /// [`Retryable::with_retry_budget`](crate::Retryable::with_retry_budget)
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Start calling the wrapped function, responding to Errors
+    /// as the specified strategy dictates
+    #[track_caller]
+    #[allow(clippy::manual_async_fn)]
+    pub fn try_call(&mut self) -> impl Future<Output = Result<T, E>> + '_ {
+        // `#[track_caller]` doesn't reach into async bodies, so grab it up front
+        let site = Location::caller();
+        async move {
+            let mut retry =
+                RetryLoop::at(self.strategy.clone(), site).with_progress(self.progress.clone());
+            if let Some(budget) = &self.budget {
+                retry = retry.with_retry_budget(budget.clone());
+            }
+            loop {
+                let permit = match &self.limit {
+                    Some(limit) => Some(limit.acquire_async().await),
+                    None => None,
+                };
+                let res = self.inner.attempt().await;
+                drop(permit);
+                let next = match (&mut self.policy, &mut self.decider) {
+                    (Some(policy), _) => retry.after_policy(policy.as_mut(), &res),
+                    (None, Some(decider)) => retry.after(decider.decide(&res)),
+                    (None, None) => retry.after(Decision::default_for(&res)),
+                };
+                match next {
+                    Some(delay) => {
+                        if let Some(hook) = &mut self.on_retry {
+                            hook(retry.progress());
+                        }
+                        sleep(delay).await;
+                        retry.resume();
+                    }
+                    None => {
+                        retry.finish(res.is_err());
+                        return res;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Wait for the given duration without blocking the executor
+///
+/// A single background thread, shared by every pending sleep, wakes the task
+/// once the time is up. Dropping the sleep early cancels its wakeup.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::now() + duration,
+        registration: None,
+    }
+}
+
+/// Future returned by [`sleep`]
+pub struct Sleep {
+    deadline: Instant,
+    /// The id of its wakeup on the timer thread, once it's been polled
+    registration: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            if let Some(id) = self.registration.take() {
+                timer::cancel(id);
+            }
+            return Poll::Ready(());
+        }
+        match self.registration {
+            // The task may have moved executors since the last poll
+            Some(id) => timer::update(id, cx.waker()),
+            None => self.registration = Some(timer::register(self.deadline, cx.waker())),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.registration.take() {
+            timer::cancel(id);
+        }
+    }
+}
+
+/// Async version of `retry!`, retrying an `.await`ed expression
+///
+/// The retry loop is expanded in place, so it must be used in an async context:
+/// ```ignore
+/// let body = retry_async!(fetch(url).await; retries=4; delay_ms=250);
+/// ```
+/// Default retry count is 3 with no delay between attempts
+#[macro_export]
+macro_rules! retry_async {
+    ($e:expr; retries=$r:expr; delay_ms=$d:expr) => {{
+        let _strategy = $crate::RetryStrategy::new(
+            $r,
+            $crate::RetryDelay::Fixed(std::time::Duration::from_millis($d)),
+        );
+        let mut _retry = $crate::RetryLoop::new(_strategy);
+        loop {
+            let _res = $e;
+            match _retry.after($crate::Decision::default_for(&_res)) {
+                Some(_delay) => {
+                    $crate::future::sleep(_delay).await;
+                    _retry.resume();
+                }
+                None => {
+                    _retry.finish(_res.is_err());
+                    break _res;
+                }
+            }
+        }
+    }};
+    ($e:expr; retries=$r:expr) => {{
+        $crate::retry_async!($e; retries=$r; delay_ms=0)
+    }};
+    ($e:expr; delay_ms=$d:expr) => {{
+        $crate::retry_async!($e; retries=3; delay_ms=$d)
+    }};
+    ($e:expr) => {{
+        $crate::retry_async!($e; retries=3; delay_ms=0)
+    }};
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::thread::Thread;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor for driving a single future to completion
+    pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = Box::pin(fut);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(out) => break out,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    async fn succeed_after(count: &AtomicUsize, failures: usize) -> Result<usize, ()> {
+        let attempt = count.fetch_add(1, Ordering::SeqCst);
+        if attempt < failures {
+            Err(())
+        } else {
+            Ok(attempt)
+        }
+    }
+
+    #[test]
+    fn test_sleep() {
+        let start = Instant::now();
+        block_on(sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_async_retryable() {
+        let count = AtomicUsize::new(0);
+        let strategy = RetryStrategy::new(3, crate::RetryDelay::Fixed(Duration::from_millis(5)));
+        let mut r = AsyncRetryable::new(|| succeed_after(&count, 2), strategy);
+        assert_eq!(block_on(r.try_call()), Ok(2));
+    }
+
+    #[test]
+    fn test_retry_async_macro() {
+        let count = AtomicUsize::new(0);
+        let res = block_on(async { retry_async!(succeed_after(&count, 3).await; retries=4; delay_ms=10) });
+        assert_eq!(res, Ok(3));
+
+        let count = AtomicUsize::new(0);
+        let start = Instant::now();
+        let res = block_on(async { retry_async!(succeed_after(&count, 5).await; retries=2; delay_ms=10) });
+        assert!(res.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let count = AtomicUsize::new(0);
+        let res = block_on(async { retry_async!(succeed_after(&count, 1).await) });
+        assert_eq!(res, Ok(1));
+    }
+
+    /// Each attempt borrows the connection state instead of owning a copy
+    struct Reconnect {
+        tries: usize,
+    }
+
+    struct Connecting<'a>(&'a mut Reconnect);
+
+    impl Future for Connecting<'_> {
+        type Output = Result<usize, ()>;
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.0.tries += 1;
+            Poll::Ready(if self.0.tries < 3 { Err(()) } else { Ok(self.0.tries) })
+        }
+    }
+
+    impl AsyncAttempt for Reconnect {
+        type Output = Result<usize, ()>;
+        type Future<'a> = Connecting<'a>;
+
+        fn attempt(&mut self) -> Connecting<'_> {
+            Connecting(self)
+        }
+    }
+
+    /// Compiles only while `try_call`'s future is `Send`, with and without the
+    /// boxed decider, policy and hook
+    #[test]
+    fn test_try_call_is_send() {
+        fn assert_send<F: Future + Send>(fut: F) -> F {
+            fut
+        }
+        // Owned by the attempts, as for `tokio::spawn`
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let strategy = RetryStrategy::new(1, crate::RetryDelay::Fixed(Duration::from_millis(1)));
+        let mut plain = AsyncRetryable::new(|| succeed_after(&COUNT, 0), strategy.clone());
+        assert_eq!(block_on(assert_send(plain.try_call())), Ok(0));
+
+        let mut boxed = AsyncRetryable::new(|| succeed_after(&COUNT, 0), strategy.clone())
+            .with_decider(|res: &Result<usize, ()>| Decision::default_for(res))
+            .with_policy(strategy)
+            .on_retry(|_| {});
+        assert_eq!(block_on(assert_send(boxed.try_call())), Ok(1));
+    }
+
+    #[test]
+    fn test_async_attempt_lending() {
+        let strategy = RetryStrategy::new(3, crate::RetryDelay::Fixed(Duration::from_millis(1)));
+        let mut r = AsyncRetryable::new(Reconnect { tries: 0 }, strategy);
+        assert_eq!(block_on(r.try_call()), Ok(3));
+    }
+}
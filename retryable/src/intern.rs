@@ -0,0 +1,72 @@
+//! Labels that live for the rest of the process
+//!
+//! The shared state behind labels (adaptive windows, concurrency limits) is
+//! keyed by an interned copy of each distinct label, so retrying under a dynamic
+//! label (a route name, a tenant id) only allocates the first time it's seen.
+//! The copies are never freed: keep dynamic labels to a bounded set.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+
+static LABELS: RwLock<BTreeSet<&'static str>> = RwLock::new(BTreeSet::new());
+
+/// The interned copy of `label`, made on first use
+pub(crate) fn intern(label: &str) -> &'static str {
+    if let Some(interned) = lookup(label) {
+        return interned;
+    }
+    let mut labels = LABELS.write().unwrap_or_else(|e| e.into_inner());
+    // Another thread may have got there between the locks
+    if let Some(interned) = labels.get(label) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(label.into());
+    labels.insert(interned);
+    interned
+}
+
+/// The interned copy of `label`, if there is one already
+pub(crate) fn lookup(label: &str) -> Option<&'static str> {
+    let labels = LABELS.read().unwrap_or_else(|e| e.into_inner());
+    labels.get(label).copied()
+}
+
+/// Borrowed labels as they are, owned ones [interned](intern)
+pub(crate) fn label<'a>(label: impl Into<Cow<'a, str>>) -> &'a str {
+    match label.into() {
+        Cow::Borrowed(label) => label,
+        Cow::Owned(label) => intern(&label),
+    }
+}
+
+/// `file:line:column` of a call site, formatted once per site
+#[cfg(feature = "observability")]
+pub(crate) fn site(location: &'static std::panic::Location<'static>) -> &'static str {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    type Site = (&'static str, u32, u32);
+    static SITES: Mutex<BTreeMap<Site, &'static str>> = Mutex::new(BTreeMap::new());
+
+    let key = (location.file(), location.line(), location.column());
+    let mut sites = SITES.lock().unwrap_or_else(|e| e.into_inner());
+    sites
+        .entry(key)
+        .or_insert_with(|| Box::leak(location.to_string().into_boxed_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let tenant = String::from("tenant-42");
+        assert_eq!(lookup(&tenant), None);
+        let first = label(tenant.clone());
+        assert_eq!(first, "tenant-42");
+        assert!(std::ptr::eq(first, intern(&tenant)));
+        assert_eq!(lookup(&tenant), Some(first));
+        assert!(std::ptr::eq(label("static"), "static"));
+    }
+}
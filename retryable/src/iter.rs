@@ -0,0 +1,178 @@
+//! Per-item retries for iterators of fallible items
+//!
+//! Streaming consumers (pagination, chunked downloads) keep their `for` loops,
+//! and each item is retried on its own attempt budget before it's yielded.
+use crate::{Decision, RetryLoop, RetryStrategy, Retryable};
+
+/// Adds [`retry_each`](Self::retry_each) and [`retry_each_call`](Self::retry_each_call)
+/// to every iterator
+pub trait RetryIteratorExt: Iterator + Sized {
+    /// Retry `Err` items by pulling the next item, for iterators that try the
+    /// same item again after failing (like a paginator that only advances its
+    /// cursor on success)
+    /// ```
+    /// use std::time::Duration;
+    /// use retryable::{RetryDelay, RetryIteratorExt, RetryStrategy};
+    ///
+    /// // Every page fails once before it's fetched
+    /// let mut failed = false;
+    /// let mut page = 0;
+    /// let pages = std::iter::from_fn(|| {
+    ///     failed = !failed;
+    ///     if failed {
+    ///         return Some(Err("timed out"));
+    ///     }
+    ///     page += 1;
+    ///     if page > 3 { None } else { Some(Ok(page)) }
+    /// });
+    /// let strategy = RetryStrategy::new(1, RetryDelay::Fixed(Duration::from_millis(1)));
+    /// let pages: Result<Vec<_>, _> = pages.retry_each(strategy).collect();
+    /// assert_eq!(pages, Ok(vec![1, 2, 3]));
+    /// ```
+    fn retry_each<T, E>(self, strategy: RetryStrategy) -> RetryEach<Self>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+    {
+        RetryEach {
+            inner: self,
+            strategy,
+        }
+    }
+
+    /// Call each item (a closure producing a `Result`) in its own retry loop,
+    /// yielding the final outcome
+    /// ```
+    /// use retryable::{RetryIteratorExt, RetryStrategy};
+    ///
+    /// let chunks = (0..4).map(|chunk| move || Ok::<_, ()>(chunk * 1024));
+    /// let offsets: Vec<_> = chunks.retry_each_call(RetryStrategy::default()).collect();
+    /// assert_eq!(offsets, vec![Ok(0), Ok(1024), Ok(2048), Ok(3072)]);
+    /// ```
+    fn retry_each_call<F, T, E>(self, strategy: RetryStrategy) -> RetryEachCall<Self>
+    where
+        Self: Iterator<Item = F>,
+        F: FnMut() -> Result<T, E>,
+    {
+        RetryEachCall {
+            inner: self,
+            strategy,
+        }
+    }
+}
+
+impl<I: Iterator> RetryIteratorExt for I {}
+
+/// Iterator returned by [`RetryIteratorExt::retry_each`]
+#[derive(Clone, Debug)]
+pub struct RetryEach<I> {
+    inner: I,
+    strategy: RetryStrategy,
+}
+
+impl<I, T, E> Iterator for RetryEach<I>
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    type Item = Result<T, E>;
+
+    /// The first `Ok`, or the last `Err` once out of retries. Ends early if the
+    /// inner iterator does, even while retrying.
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut item = self.inner.next()?;
+        let mut retry = RetryLoop::new(self.strategy.clone());
+        loop {
+            match retry.after(Decision::default_for(&item)) {
+                Some(delay) => {
+                    std::thread::sleep(delay);
+                    retry.resume();
+                }
+                None => {
+                    retry.finish(item.is_err());
+                    return Some(item);
+                }
+            }
+            item = match self.inner.next() {
+                Some(item) => item,
+                None => {
+                    retry.finish(true);
+                    return None;
+                }
+            };
+        }
+    }
+}
+
+/// Iterator returned by [`RetryIteratorExt::retry_each_call`]
+#[derive(Clone, Debug)]
+pub struct RetryEachCall<I> {
+    inner: I,
+    strategy: RetryStrategy,
+}
+
+impl<I, F, T, E> Iterator for RetryEachCall<I>
+where
+    I: Iterator<Item = F>,
+    F: FnMut() -> Result<T, E>,
+{
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let call = self.inner.next()?;
+        Some(Retryable::new(call, self.strategy.clone()).try_call())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryDelay;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn strategy(retries: usize) -> RetryStrategy {
+        RetryStrategy::new(retries, RetryDelay::Fixed(Duration::from_millis(1)))
+    }
+
+    #[test]
+    fn test_retry_each() {
+        // Page 2 fails three times in a row, more than the 2 retries allowed
+        let mut outcomes = vec![Ok(1), Err(1), Err(2), Err(3), Err(4), Ok(2), Err(5), Ok(3)];
+        outcomes.reverse();
+        let pages: Vec<_> = std::iter::from_fn(|| outcomes.pop())
+            .retry_each(strategy(2))
+            .collect();
+        assert_eq!(pages, vec![Ok(1), Err(3), Ok(2), Ok(3)]);
+
+        // Running out of items mid-retry ends the iterator
+        let mut pages = vec![Ok(1), Err(1)].into_iter().retry_each(strategy(2));
+        assert_eq!(pages.next(), Some(Ok(1)));
+        assert_eq!(pages.next(), None);
+    }
+
+    #[test]
+    fn test_retry_each_call() {
+        let calls = vec![Cell::new(0); 3];
+        let results: Vec<_> = (0..3)
+            .map(|chunk| {
+                let calls = &calls[chunk];
+                // Each chunk fails `chunk` times before succeeding
+                move || {
+                    calls.set(calls.get() + 1);
+                    if calls.get() > chunk {
+                        Ok(chunk)
+                    } else {
+                        Err(chunk)
+                    }
+                }
+            })
+            .retry_each_call(strategy(1))
+            .collect();
+        assert_eq!(results, vec![Ok(0), Ok(1), Err(2)]);
+        let calls: Vec<_> = calls.iter().map(Cell::get).collect();
+        assert_eq!(calls, vec![1, 2, 2]);
+    }
+}
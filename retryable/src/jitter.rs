@@ -0,0 +1,160 @@
+//! Randomized delays
+//!
+//! A per-thread xorshift generator is plenty for spreading out retries, so
+//! there's no need for a `rand` dependency.
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+/// `RandomState` is randomly keyed per process (and per thread)
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    hasher.finish() | 1
+}
+
+/// xorshift64*
+fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// Make the jitter of this thread's retry loops repeatable, for tests comparing
+/// against recorded output (see `observability::golden`)
+pub fn seed_jitter(seed: u64) {
+    // xorshift never leaves zero
+    STATE.with(|state| state.set(seed.max(1)));
+}
+
+/// A uniformly random duration in `[0, max]`
+pub(crate) fn up_to(max: Duration) -> Duration {
+    // 53 random bits, as a fraction in [0, 1]
+    let fraction = (next_u64() >> 11) as f64 / ((1u64 << 53) - 1) as f64;
+    max.mul_f64(fraction)
+}
+
+/// Randomization applied on top of any [`RetryDelay`](crate::RetryDelay), so clients
+/// that failed at the same moment don't all retry in lockstep
+///
+/// With `d` the delay the strategy would otherwise wait (after `max_delay`):
+/// - `Full`: anywhere in `[0, d]`, spreads retries out the most
+/// - `Equal`: in `[d/2, d]`, keeps at least half of the backoff
+/// - `Decorrelated`: in `[d, 3 * previous]` (at most `cap`), each delay grows
+///   from the one actually waited before it rather than from the schedule
+///
+/// See the AWS Architecture Blog's "Exponential Backoff And Jitter" for how they compare.
+/// ```
+/// use std::time::Duration;
+/// use retryable::{Jitter, RetryDelay, RetryStrategy};
+///
+/// let strategy = RetryStrategy::builder()
+///     .delay(RetryDelay::exponential(Duration::from_millis(100)))
+///     .jitter(Jitter::Equal)
+///     .build();
+/// assert!(strategy.is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "persist", derive(serde::Serialize, serde::Deserialize))]
+pub enum Jitter {
+    #[default]
+    None,
+    Full,
+    Equal,
+    Decorrelated {
+        cap: Duration,
+    },
+}
+
+impl Jitter {
+    /// Randomize `delay`, given the delay waited before the previous retry
+    pub(crate) fn apply(&self, delay: Duration, previous: Option<Duration>) -> Duration {
+        match self {
+            Jitter::None => delay,
+            Jitter::Full => up_to(delay),
+            Jitter::Equal => delay / 2 + up_to(delay - delay / 2),
+            Jitter::Decorrelated { cap } => {
+                let high = previous.map_or(delay, |p| p.saturating_mul(3)).max(delay);
+                (delay + up_to(high - delay)).min(*cap)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_up_to() {
+        let max = Duration::from_millis(100);
+        let samples: Vec<_> = (0..1000).map(|_| up_to(max)).collect();
+        assert!(samples.iter().all(|d| *d <= max));
+        // Spread over the whole range
+        assert!(samples.iter().any(|d| *d < Duration::from_millis(10)));
+        assert!(samples.iter().any(|d| *d > Duration::from_millis(90)));
+        assert_eq!(up_to(Duration::from_secs(0)), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let d = Duration::from_millis(100);
+        let sample = |jitter: Jitter, previous| -> Vec<_> {
+            (0..1000).map(|_| jitter.apply(d, previous)).collect()
+        };
+        let within = |samples: &[Duration], low: u64, high: u64| {
+            samples
+                .iter()
+                .all(|s| *s >= Duration::from_millis(low) && *s <= Duration::from_millis(high))
+        };
+        let mean = |samples: &[Duration]| samples.iter().sum::<Duration>() / samples.len() as u32;
+
+        assert!(sample(Jitter::None, None).iter().all(|s| *s == d));
+
+        let full = sample(Jitter::Full, None);
+        assert!(within(&full, 0, 100));
+        assert!(mean(&full) > Duration::from_millis(40) && mean(&full) < Duration::from_millis(60));
+
+        let equal = sample(Jitter::Equal, None);
+        assert!(within(&equal, 50, 100));
+        assert!(
+            mean(&equal) > Duration::from_millis(65) && mean(&equal) < Duration::from_millis(85)
+        );
+
+        let cap = Duration::from_millis(250);
+        let decorrelated = sample(
+            Jitter::Decorrelated { cap },
+            Some(Duration::from_millis(80)),
+        );
+        assert!(within(&decorrelated, 100, 240));
+        assert!(decorrelated.iter().any(|s| *s > Duration::from_millis(200)));
+        // The first retry has nothing to decorrelate from
+        assert!(sample(Jitter::Decorrelated { cap }, None)
+            .iter()
+            .all(|s| *s == d));
+        // Capped
+        let capped = sample(Jitter::Decorrelated { cap }, Some(Duration::from_secs(1)));
+        assert!(within(&capped, 100, 250));
+        assert!(capped.contains(&cap));
+    }
+
+    #[test]
+    fn test_seed_jitter() {
+        let d = Duration::from_secs(1);
+        seed_jitter(42);
+        let first: Vec<_> = (0..10).map(|_| Jitter::Full.apply(d, None)).collect();
+        seed_jitter(42);
+        let again: Vec<_> = (0..10).map(|_| Jitter::Full.apply(d, None)).collect();
+        assert_eq!(first, again);
+    }
+}
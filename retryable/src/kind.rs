@@ -0,0 +1,37 @@
+use std::io;
+
+/// Errors that can be matched against the `retry_on=` patterns of `retryable!`
+///
+/// The kind is usually a small `Copy` enum describing the error, implement this
+/// for your own error types to use them with `retry_on=`:
+/// ```
+/// use retryable::RetryKind;
+///
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum ApiError {
+///     Throttled,
+///     Unavailable,
+///     BadRequest,
+/// }
+///
+/// impl RetryKind for ApiError {
+///     type Kind = ApiError;
+///
+///     fn retry_kind(&self) -> ApiError {
+///         *self
+///     }
+/// }
+/// ```
+pub trait RetryKind {
+    type Kind;
+
+    fn retry_kind(&self) -> Self::Kind;
+}
+
+impl RetryKind for io::Error {
+    type Kind = io::ErrorKind;
+
+    fn retry_kind(&self) -> io::ErrorKind {
+        self.kind()
+    }
+}
@@ -1,8 +1,85 @@
-use std::time::Duration;
+use std::borrow::Cow;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+// Lets the attribute macros use `::retryable` paths inside this crate too
+extern crate self as retryable;
+
+pub mod adaptive;
+/// Attribute macros, kept apart from the `retry!` macro sharing a name
+pub mod attr {
+    pub use retryable_macros::retry;
+}
+mod breaker;
+mod budget;
+mod builder;
+mod concurrency;
+mod decide;
+pub mod deadline;
+mod defaults;
+mod driver;
+mod duration;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+mod endpoints;
+mod error;
+pub mod flaky;
+pub mod fs;
+pub mod future;
+mod intern;
+mod iter;
+mod jitter;
+mod kind;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "persist")]
+mod persist;
+mod policy;
+mod progress;
+pub mod process;
+mod resilience;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "reqwest")]
+pub mod reqwest;
+pub mod scope;
+mod shared;
+#[cfg(feature = "sqlx")]
+pub mod sqlx;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+mod timer;
+#[cfg(feature = "tower")]
+pub mod tower;
+mod unwind;
+
+pub use breaker::{CircuitBreaker, CircuitState};
+pub use budget::RetryBudget;
+pub use builder::{RetryStrategyBuilder, StrategyError};
+pub use concurrency::{Acquire, ConcurrencyLimit, Permit};
+pub use decide::{Decide, Decision};
+pub use defaults::with_default_strategy;
+pub use driver::RetryLoop;
+#[doc(hidden)]
+pub use duration::__duration_literal;
+pub use duration::{parse_duration, ParseDurationError};
+pub use endpoints::EndpointRotation;
+pub use error::RetryError;
+pub use iter::{RetryEach, RetryEachCall, RetryIteratorExt};
+pub use jitter::{seed_jitter, Jitter};
+pub use kind::RetryKind;
+#[cfg(feature = "persist")]
+pub use persist::PersistError;
+pub use policy::{Attempt, RetryPolicy};
+pub use progress::RetryProgress;
+pub use resilience::{ResilienceError, ResiliencePolicy};
+use progress::RetryHook;
+pub use retryable_macros::flaky_test;
+pub use shared::{SharedRetryable, SharedStats};
 
 /// Expand a variadic number of macro args to a function call w/ args
 ///
-/// ```
+/// ```ignore
 /// fn double_sum(a: u32, b: u32) -> u32 {
 ///     (a + b) * 2
 /// }
@@ -10,6 +87,8 @@ use std::time::Duration;
 /// assert_eq!(_wrapper!(double_sum, 4, 2), 12);
 /// assert_eq!(_wrapper!(double_sum, 4, 2,), 12);
 /// ```
+#[doc(hidden)]
+#[macro_export]
 macro_rules! _wrapper {
     // Single expression (like a function name or closure)
     ($f:expr) => {{
@@ -21,6 +100,29 @@ macro_rules! _wrapper {
     }};
 }
 
+/// Like [`_wrapper!`], but evaluate the args once, returning a closure that calls
+/// the function with a clone of each (for the `args=once` macro option)
+///
+/// ```ignore
+/// let mut call = _once!(double_sum, next_a(), 2);
+/// assert_eq!(call(), call());
+/// ```
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _once {
+    ($f:expr, $($args:expr),+ $(,)?) => {
+        $crate::_once!(@bind $f; []; [_a0 _a1 _a2 _a3 _a4 _a5 _a6 _a7 _a8 _a9 _a10 _a11]; $($args,)*)
+    };
+    // Give each arg a name, one at a time
+    (@bind $f:expr; [$($bound:tt)*]; [$name:ident $($names:ident)*]; $arg:expr, $($rest:expr,)*) => {
+        $crate::_once!(@bind $f; [$($bound)* $name = $arg,]; [$($names)*]; $($rest,)*)
+    };
+    (@bind $f:expr; [$($name:ident = $arg:expr,)*]; [$($unused:ident)*];) => {{
+        $(let $name = $arg;)*
+        move || $f($(::std::clone::Clone::clone(&$name),)*)
+    }};
+}
+
 /// A simple retry macro to immediately attempt a function call after failure
 ///
 /// To use, pass a function and arguments:
@@ -33,12 +135,38 @@ macro_rules! _wrapper {
 /// ```ignore
 /// retry!(my_fallible_func, 0, "something"; retries=5);
 /// ```
+///
+/// The function can also be a closure taking the arguments. Argument expressions
+/// are evaluated again before every attempt, so each attempt can go elsewhere:
+/// ```ignore
+/// retry!(|endpoint| send_to(endpoint), next_endpoint());
+/// ```
+/// Or pass `args=once` to evaluate them a single time up front, calling the
+/// function with a clone of each on every attempt:
+/// ```ignore
+/// retry!(|token| send_signed(token), sign_request(); retries=5; args=once);
+/// ```
+/// `args=each_attempt` spells out the default. Options can be given in any order.
 #[macro_export]
 macro_rules! retry {
-    ($( $args:expr$(,)? )+; retries=$r:literal) => {{
-        let mut retries = $r;
+    ($( $args:expr$(,)? )+ $(; $($opts:tt)*)?) => {{
+        $crate::retry!(@args [$($($opts)*)?] $($args),+; $($($opts)*)?)
+    }};
+    // Whether the args are evaluated for each attempt (the default) or once
+    (@args [] $($args:expr),+; $($opts:tt)*) => {
+        $crate::retry!(@run [$crate::_wrapper!($($args,)*)]; $($opts)*)
+    };
+    (@args [args=once $($rest:tt)*] $f:expr, $($args:expr),+; $($opts:tt)*) => {{
+        let mut _call = $crate::_once!($f, $($args,)*);
+        $crate::retry!(@run [_call()]; $($opts)*)
+    }};
+    (@args [$skip:tt $($rest:tt)*] $($args:expr),+; $($opts:tt)*) => {
+        $crate::retry!(@args [$($rest)*] $($args),+; $($opts)*)
+    };
+    (@run [$($call:tt)*]; $($opts:tt)*) => {{
+        let mut retries: usize = $crate::retry!(@retries 3; $($opts)*);
         loop {
-            let res = _wrapper!($( $args, )*);
+            let res = $($call)*;
             if res.is_ok() {
                 break res;
             }
@@ -49,10 +177,23 @@ macro_rules! retry {
             break res;
         }
     }};
-    // Function & args only, use default of 3 retries
-    ($( $args:expr$(,)? )+) => {{
-        retry!($( $args, )*; retries = 3)
-    }};
+    // The number of retries, 3 unless a `retries=` option says otherwise
+    (@retries $n:expr;) => {
+        $n
+    };
+    (@retries $n:expr; retries=$r:expr $(; $($rest:tt)*)?) => {
+        $crate::retry!(@retries $r; $($($rest)*)?)
+    };
+    // Already applied while wrapping the function, see `@args`
+    (@retries $n:expr; args=once $(; $($rest:tt)*)?) => {
+        $crate::retry!(@retries $n; $($($rest)*)?)
+    };
+    (@retries $n:expr; args=each_attempt $(; $($rest:tt)*)?) => {
+        $crate::retry!(@retries $n; $($($rest)*)?)
+    };
+    (@retries $n:expr; $($unknown:tt)+) => {
+        compile_error!("unknown retry! option, expected `retries=N` or `args=once|each_attempt`")
+    };
 }
 
 /// Retryable is an step up from the `retry!()` macro in that it allows for even more
@@ -66,8 +207,20 @@ where
 {
     inner: F,
     strategy: RetryStrategy,
+    decider: Option<Box<dyn Decide<T, E>>>,
+    policy: Option<Box<dyn RetryPolicy<T, E>>>,
+    limit: Option<ConcurrencyLimit>,
+    budget: Option<RetryBudget>,
+    progress: RetryProgress,
+    on_retry: Option<RetryHook>,
+    sleeper: Option<Sleeper>,
+    /// Label of the operation, when panics are caught
+    catch_panics: Option<&'static str>,
 }
 
+/// Waits out a backoff, in place of `std::thread::sleep`
+type Sleeper = Box<dyn FnMut(Duration)>;
+
 impl<F, T, E> Retryable<F, T, E>
 where
     F: FnMut() -> Result<T, E>,
@@ -77,33 +230,274 @@ where
         Self {
             inner: func,
             strategy,
+            decider: None,
+            policy: None,
+            limit: None,
+            budget: None,
+            progress: RetryProgress::default(),
+            on_retry: None,
+            sleeper: None,
+            catch_panics: None,
         }
     }
 
+    /// Decide what to do with each result (including `Ok` values),
+    /// instead of retrying every `Err`
+    pub fn with_decider<D: Decide<T, E> + 'static>(mut self, decider: D) -> Self {
+        self.decider = Some(Box::new(decider));
+        self
+    }
+
+    /// Hand every decision (classification, delay and when to stop) to a
+    /// [`RetryPolicy`], in place of the strategy and decider
+    pub fn with_policy<P: RetryPolicy<T, E> + 'static>(mut self, policy: P) -> Self {
+        self.policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Called before each backoff, with the progress of the loop so far
+    pub fn on_retry<H: FnMut(&RetryProgress) + 'static>(mut self, hook: H) -> Self {
+        self.on_retry = Some(Box::new(hook));
+        self
+    }
+
+    /// Wait out each backoff with `sleeper` instead of blocking the thread, e.g.
+    /// with a hardware timer (see [`with_delay`](Self::with_delay) for `embedded-hal`)
+    /// or a simulated clock in tests
+    pub fn with_sleeper<S: FnMut(Duration) + 'static>(mut self, sleeper: S) -> Self {
+        self.sleeper = Some(Box::new(sleeper));
+        self
+    }
+
+    /// Retry attempts that panic, like attempts that return an `Err`
+    ///
+    /// A panic from the last attempt is resumed with the operation's `label` and
+    /// the attempt number prepended to its message, so a crash report says which
+    /// attempt finally blew up:
+    /// ```
+    /// use std::time::Duration;
+    /// use retryable::{RetryDelay, RetryStrategy, Retryable};
+    ///
+    /// let strategy = RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(1)));
+    /// let mut r = Retryable::new(|| -> Result<(), ()> { panic!("connection reset") }, strategy)
+    ///     .catch_panics("sync users");
+    /// let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| r.try_call())).unwrap_err();
+    /// assert_eq!(
+    ///     panic.downcast_ref::<String>().unwrap(),
+    ///     "'sync users' panicked on attempt 3: connection reset"
+    /// );
+    /// ```
+    pub fn catch_panics(mut self, label: impl Into<Cow<'static, str>>) -> Self {
+        self.catch_panics = Some(intern::label(label));
+        self
+    }
+
+    /// Shared handle to the progress of the current (or last) retry loop,
+    /// readable from another thread while `try_call` runs
+    pub fn progress(&self) -> RetryProgress {
+        self.progress.clone()
+    }
+
+    /// See [`RetryProgress::attempts_made`]
+    pub fn attempts_made(&self) -> usize {
+        self.progress.attempts_made()
+    }
+
+    /// See [`RetryProgress::elapsed`]
+    pub fn elapsed(&self) -> Duration {
+        self.progress.elapsed()
+    }
+
+    /// See [`RetryProgress::next_delay`]
+    pub fn next_delay(&self) -> Option<Duration> {
+        self.progress.next_delay()
+    }
+
+    /// See [`RetryProgress::remaining_retries`]
+    pub fn remaining_retries(&self) -> usize {
+        self.progress.remaining_retries()
+    }
+
+    /// See [`RetryProgress::time_in_attempts`]
+    pub fn time_in_attempts(&self) -> Duration {
+        self.progress.time_in_attempts()
+    }
+
+    /// See [`RetryProgress::time_in_backoff`]
+    pub fn time_in_backoff(&self) -> Duration {
+        self.progress.time_in_backoff()
+    }
+
+    /// Adjust the strategy after wrapping
+    pub fn strategy_mut(&mut self) -> &mut RetryStrategy {
+        &mut self.strategy
+    }
+
+    /// Wait for a permit from the limit before each attempt, bounding how many
+    /// attempts sharing the limit run at once
+    /// ```
+    /// use retryable::{ConcurrencyLimit, RetryStrategy, Retryable};
+    ///
+    /// let limit = ConcurrencyLimit::shared("inventory-db", 8);
+    /// let mut r = Retryable::new(|| Ok::<_, ()>(1), RetryStrategy::default())
+    ///     .with_concurrency_limit(limit);
+    /// assert_eq!(r.try_call(), Ok(1));
+    /// ```
+    pub fn with_concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Only retry while `budget` has retries left, giving up with the last
+    /// result otherwise (see [`RetryBudget`])
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
     /// Start calling the wrapped function, responding to Errors
     /// as the specified strategy dictates
+    #[track_caller]
     pub fn try_call(&mut self) -> Result<T, E> {
-        let mut retries = self.strategy.retries;
-        let mut delay_time = Duration::from_millis(0);
+        let mut retry = RetryLoop::new(self.strategy.clone()).with_progress(self.progress.clone());
+        if let Some(budget) = &self.budget {
+            retry = retry.with_retry_budget(budget.clone());
+        }
         loop {
-            std::thread::sleep(delay_time);
-            let res = (self.inner)();
-            if res.is_ok() {
-                break res;
-            }
-            if retries > 0 {
-                retries -= 1;
-                delay_time = self.next_run_time();
-                continue;
+            // Only held for the attempt itself, not while backing off
+            let permit = self.limit.as_ref().map(ConcurrencyLimit::acquire);
+            let outcome = match self.catch_panics {
+                Some(_) => panic::catch_unwind(AssertUnwindSafe(&mut self.inner)),
+                None => Ok((self.inner)()),
+            };
+            drop(permit);
+            let next = match (&outcome, &mut self.policy, &mut self.decider) {
+                (Ok(res), Some(policy), _) => retry.after_policy(policy.as_mut(), res),
+                (Ok(res), None, Some(decider)) => retry.after(decider.decide(res)),
+                (Ok(res), None, None) => retry.after(Decision::default_for(res)),
+                // There's no result to classify, so panics are always retried
+                (Err(_), _, _) => retry.after(Decision::Retry),
+            };
+            match next {
+                Some(delay) => {
+                    if let Some(hook) = &mut self.on_retry {
+                        hook(retry.progress());
+                    }
+                    match &mut self.sleeper {
+                        Some(sleeper) => sleeper(delay),
+                        None => std::thread::sleep(delay),
+                    }
+                    retry.resume();
+                }
+                None => match outcome {
+                    Ok(res) => {
+                        retry.finish(res.is_err());
+                        return res;
+                    }
+                    Err(payload) => {
+                        let (label, attempts) =
+                            (self.catch_panics.unwrap_or_default(), retry.attempts());
+                        retry.finish(true);
+                        panic::resume_unwind(unwind::enrich(payload, label, attempts))
+                    }
+                },
             }
-            break res;
         }
     }
+}
+
+/// Progress of a single retry loop
+///
+/// Shared by the sync and async retry loops: after each failed attempt,
+/// ask for the delay before the next one.
+///
+/// With the `persist` feature, the state can be saved with
+/// [`to_bytes()`](RetryState::to_bytes) and picked up again after a restart.
+#[derive(Clone, Debug)]
+pub struct RetryState {
+    strategy: RetryStrategy,
+    attempts: usize,
+    started: Instant,
+    /// Total of the delays handed out
+    backoff: Duration,
+    /// When the retry that was last handed out was, and how long its delay
+    /// (kept apart, as a long enough delay would overflow an `Instant`)
+    next_at: Option<(Instant, Duration)>,
+}
 
-    fn next_run_time(&self) -> Duration {
-        match self.strategy.delay {
-            RetryDelay::Fixed(delay) => delay,
+impl RetryState {
+    /// Start a loop now, capping `max_elapsed` to the time left before the
+    /// thread's [`deadline`] (if there is one)
+    pub fn new(mut strategy: RetryStrategy) -> Self {
+        if let Some(remaining) = deadline::remaining() {
+            let max_elapsed = strategy.max_elapsed.map_or(remaining, |max| max.min(remaining));
+            strategy.max_elapsed = Some(max_elapsed);
         }
+        Self {
+            strategy,
+            attempts: 0,
+            started: Instant::now(),
+            backoff: Duration::from_secs(0),
+            next_at: None,
+        }
+    }
+
+    /// Record a failed attempt, returning how long to wait before the next one
+    /// (or `None` if there are no retries left, or the next attempt would start
+    /// after `max_elapsed`)
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        self.after(Decision::Retry)
+    }
+
+    /// Apply the [`Decision`] made about an attempt, returning how long to wait
+    /// before the next one (or `None` if the loop is done)
+    pub fn after(&mut self, decision: Decision) -> Option<Duration> {
+        self.attempts += 1;
+        let decision = self.strategy.resolve(&self.attempt(), decision);
+        self.next_wait(decision)
+    }
+
+    /// Let a [`RetryPolicy`] make the decision about an attempt, returning how long
+    /// to wait before the next one (or `None` if the loop is done)
+    pub fn after_policy<T, E>(
+        &mut self,
+        policy: &mut dyn RetryPolicy<T, E>,
+        outcome: &Result<T, E>,
+    ) -> Option<Duration> {
+        self.attempts += 1;
+        let decision = policy.decide(&self.attempt(), outcome);
+        self.next_wait(decision)
+    }
+
+    fn next_wait(&mut self, decision: Decision) -> Option<Duration> {
+        let wait = match decision {
+            Decision::Accept | Decision::Abort => None,
+            Decision::Retry => Some(Duration::from_secs(0)),
+            Decision::RetryAfter(delay) => Some(delay),
+        };
+        self.next_at = wait.map(|delay| (Instant::now(), delay));
+        self.backoff = self.backoff.saturating_add(wait.unwrap_or_default());
+        wait
+    }
+
+    /// How much of the latest delay is left before the next attempt is due
+    /// (zero once it is), or `None` if no retry is scheduled
+    pub fn remaining_wait(&self) -> Option<Duration> {
+        self.next_at.map(|(at, delay)| delay.saturating_sub(at.elapsed()))
+    }
+
+    /// The latest attempt
+    pub fn attempt(&self) -> Attempt {
+        Attempt {
+            number: self.attempts,
+            elapsed: self.started.elapsed(),
+            backoff: self.backoff,
+        }
+    }
+
+    /// How many attempts have been recorded
+    pub fn attempts(&self) -> usize {
+        self.attempts
     }
 }
 
@@ -111,15 +505,123 @@ where
 ///
 /// Retries: The number of times to retry after Err
 /// Delay: How long to wait after each Err before retrying
+/// Max Delay: Upper bound for a growing delay
+/// Max Elapsed: Stop retrying once this much time has passed since the first attempt
+/// Max Backoff Total: Stop retrying once the delays waited would add up to more than
+/// this, however long the attempts themselves take
+///
+/// The `with_*` setters don't check that the options make sense together,
+/// use [`RetryStrategy::builder()`] (or [`RetryStrategy::validate()`]) for that.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "persist", derive(serde::Serialize, serde::Deserialize))]
 pub struct RetryStrategy {
     retries: usize,
     delay: RetryDelay,
+    max_delay: Option<Duration>,
+    max_elapsed: Option<Duration>,
+    #[cfg_attr(feature = "persist", serde(default))]
+    max_backoff_total: Option<Duration>,
+    jitter: Jitter,
+    /// The last delay handed out, for decorrelated jitter
+    #[cfg_attr(feature = "persist", serde(skip))]
+    previous: Option<Duration>,
 }
 
 impl RetryStrategy {
     pub fn new(retries: usize, delay: RetryDelay) -> Self {
-        Self { retries, delay }
+        Self {
+            retries,
+            delay,
+            max_delay: None,
+            max_elapsed: None,
+            max_backoff_total: None,
+            jitter: Jitter::None,
+            previous: None,
+        }
+    }
+
+    /// Keep retrying (with no cap on attempts) until `total` has elapsed
+    /// ```
+    /// use std::time::Duration;
+    /// use retryable::{RetryDelay, RetryStrategy};
+    ///
+    /// let strategy = RetryStrategy::time_bounded(
+    ///     Duration::from_secs(30),
+    ///     RetryDelay::Fixed(Duration::from_millis(500)),
+    /// );
+    /// assert!(strategy.validate().is_ok());
+    /// ```
+    pub fn time_bounded(total: Duration, delay: RetryDelay) -> Self {
+        Self {
+            max_elapsed: Some(total),
+            ..Self::new(usize::MAX, delay)
+        }
+    }
+
+    /// Build a strategy, checking the options are coherent
+    /// ```
+    /// use std::time::Duration;
+    /// use retryable::{RetryDelay, RetryStrategy, StrategyError};
+    ///
+    /// let strategy = RetryStrategy::builder()
+    ///     .retries(5)
+    ///     .delay(RetryDelay::exponential(Duration::from_millis(100)))
+    ///     .max_delay(Duration::from_secs(2))
+    ///     .build();
+    /// assert!(strategy.is_ok());
+    ///
+    /// let strategy = RetryStrategy::builder()
+    ///     .delay(RetryDelay::Fixed(Duration::from_secs(2)))
+    ///     .max_elapsed(Duration::from_secs(1))
+    ///     .build();
+    /// assert!(matches!(strategy, Err(StrategyError::MaxElapsedBeforeFirstRetry { .. })));
+    /// ```
+    pub fn builder() -> RetryStrategyBuilder {
+        RetryStrategyBuilder::default()
+    }
+
+    /// Check that the options make sense together
+    pub fn validate(&self) -> Result<(), StrategyError> {
+        builder::validate(self)
+    }
+
+    /// Delay before the given retry (1 for the first retry)
+    fn delay_for(&self, retry: usize) -> Duration {
+        let delay = match &self.delay {
+            RetryDelay::Fixed(delay) => *delay,
+            RetryDelay::Exponential {
+                initial,
+                multiplier,
+            } => {
+                let factor = multiplier.powi(retry.saturating_sub(1).min(i32::MAX as usize) as i32);
+                // Clamp to avoid overflowing `Duration` on large retry counts
+                Duration::from_secs_f64((initial.as_secs_f64() * factor).min(u32::MAX as f64))
+            }
+            RetryDelay::Adaptive { label, min, max } => adaptive::delay(label, *min, *max),
+            RetryDelay::FullJitter { base, cap } => {
+                let exponent = retry.saturating_sub(1).min(i32::MAX as usize) as i32;
+                let ceiling = (base.as_secs_f64() * 2f64.powi(exponent)).min(cap.as_secs_f64());
+                Jitter::Full.apply(Duration::from_secs_f64(ceiling), None)
+            }
+        };
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+
+    /// [`delay_for`](Self::delay_for) with the jitter applied
+    fn jittered_delay_for(&mut self, retry: usize) -> Duration {
+        if retry <= 1 {
+            self.previous = None;
+        }
+        let delay = self.jitter.apply(self.delay_for(retry), self.previous);
+        let delay = match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        };
+        self.previous = Some(delay);
+        delay
     }
 
     pub fn with_retries(&mut self, retries: usize) -> &mut Self {
@@ -131,21 +633,117 @@ impl RetryStrategy {
         self.delay = delay;
         self
     }
+
+    pub fn with_jitter(&mut self, jitter: Jitter) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_max_delay(&mut self, max_delay: Duration) -> &mut Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    pub fn with_max_elapsed(&mut self, max_elapsed: Duration) -> &mut Self {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    pub fn with_max_backoff_total(&mut self, max_backoff_total: Duration) -> &mut Self {
+        self.max_backoff_total = Some(max_backoff_total);
+        self
+    }
 }
 
+/// 3 retries, 2 seconds apart, unless another default is in place on this
+/// thread (see [`with_default_strategy`])
 impl Default for RetryStrategy {
     fn default() -> Self {
-        Self {
-            retries: 3,
-            delay: RetryDelay::Fixed(std::time::Duration::from_secs(2)),
-        }
+        defaults::current()
+            .unwrap_or_else(|| Self::new(3, RetryDelay::Fixed(std::time::Duration::from_secs(2))))
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "persist", derive(serde::Serialize, serde::Deserialize))]
 pub enum RetryDelay {
     Fixed(std::time::Duration),
-    // TODO?: Exponential { initial_delay: std::time::Duration },
+    /// Start at `initial`, multiplying the delay after each retry
+    Exponential {
+        initial: std::time::Duration,
+        multiplier: f64,
+    },
+    /// Between `min` and `max`, scaled by the recent failure rate of all
+    /// attempts sharing `label` (see the [`adaptive`] module)
+    Adaptive {
+        label: Cow<'static, str>,
+        min: std::time::Duration,
+        max: std::time::Duration,
+    },
+    /// AWS-style "full jitter": a random delay between zero and an exponentially
+    /// growing ceiling, see [`RetryDelay::full_jitter`]
+    FullJitter {
+        base: std::time::Duration,
+        cap: std::time::Duration,
+    },
+}
+
+impl RetryDelay {
+    /// Exponential delay, doubling after each retry
+    pub fn exponential(initial: Duration) -> Self {
+        RetryDelay::Exponential {
+            initial,
+            multiplier: 2.0,
+        }
+    }
+
+    /// Delay scaled by the failure rate of all attempts sharing the `label`
+    /// ```
+    /// use std::time::Duration;
+    /// use retryable::{RetryDelay, RetryStrategy};
+    ///
+    /// let delay = RetryDelay::adaptive("billing-api", Duration::from_millis(50), Duration::from_secs(5));
+    /// let strategy = RetryStrategy::builder().retries(5).delay(delay).build();
+    /// assert!(strategy.is_ok());
+    /// ```
+    pub fn adaptive(label: impl Into<Cow<'static, str>>, min: Duration, max: Duration) -> Self {
+        RetryDelay::Adaptive {
+            // Cloned along with the strategy on every call, so make that cheap
+            label: Cow::Borrowed(intern::label(label)),
+            min,
+            max,
+        }
+    }
+
+    /// Exponential backoff with "full jitter", as described in the AWS
+    /// Architecture Blog's "Exponential Backoff And Jitter":
+    ///
+    /// `sleep = random(0, min(cap, base * 2^attempt))`
+    ///
+    /// with `attempt` starting at 0 for the first retry. Randomizing over the whole
+    /// range spreads out clients that failed at the same moment, instead of having
+    /// them retry in lockstep.
+    /// ```
+    /// use std::time::Duration;
+    /// use retryable::{RetryDelay, RetryStrategy};
+    ///
+    /// let delay = RetryDelay::full_jitter(Duration::from_millis(100), Duration::from_secs(10));
+    /// let strategy = RetryStrategy::builder().retries(8).delay(delay).build();
+    /// assert!(strategy.is_ok());
+    /// ```
+    pub fn full_jitter(base: Duration, cap: Duration) -> Self {
+        RetryDelay::FullJitter { base, cap }
+    }
+
+    /// Shortest delay before the first retry
+    fn initial(&self) -> Duration {
+        match self {
+            RetryDelay::Fixed(delay) => *delay,
+            RetryDelay::Exponential { initial, .. } => *initial,
+            RetryDelay::Adaptive { min, .. } => *min,
+            RetryDelay::FullJitter { .. } => Duration::from_secs(0),
+        }
+    }
 }
 
 /// A simple retry macro to immediately attempt a function call after failure
@@ -170,78 +768,237 @@ pub enum RetryDelay {
 /// ```ignore
 /// retryable!(|| { do_something(1, 2, 3, 4) }; retries=2; delay=3);
 /// ```
+///
+/// Closures can take the arguments too. As with [`retry!`], the argument
+/// expressions are evaluated again for every attempt (after any backoff), so
+/// rotating endpoints or signing a fresh token happens per attempt:
+/// ```ignore
+/// retryable!(|endpoint| send_to(endpoint), next_endpoint(); retries=3);
+/// ```
+///
+/// With `args=once` they're evaluated a single time instead, and each attempt gets
+/// a clone. A token signed up front is then reused, rather than re-signed per attempt:
+/// ```ignore
+/// retryable!(send_signed, sign_request(); retries=3; args=once);
+/// ```
+///
+/// Or a pre-built `RetryStrategy` (from config, a preset, etc.)
+/// ```ignore
+/// retryable!(my_fallible_func, 0, "something"; strategy=my_strategy.clone());
+/// ```
+///
+/// Only retry errors whose [`RetryKind`] matches a pattern, failing immediately otherwise
+/// ```ignore
+/// retryable!(read_file, &path; retries=5; retry_on=io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock);
+/// ```
+///
+/// Or stop once the delays alone would add up to more than a total, for attempts
+/// that are slow themselves (like long uploads) where only the waiting in between
+/// should be bounded (takes the same durations as `for`)
+/// ```ignore
+/// retryable!(upload, &chunk; retries=10; delay=5; max_backoff_total=30s);
+/// ```
+///
+/// Or keep retrying until a total time has passed, with no cap on attempts
+/// (`for` takes a duration literal like `500ms`, `30s`, `1m30s`, checked at
+/// compile time, or a `Duration` expression)
+/// ```ignore
+/// retryable!(ping, host; for=30s; delay_ms=500);
+/// ```
+///
+/// Attach a context to the final error, which is then wrapped in a [`RetryError`]
+/// ```ignore
+/// retryable!(fetch_user, id; retries=3; context="loading user profile");
+/// ```
 #[macro_export]
 macro_rules! retryable {
-    // Take a closure with retry count
-    // ```ignore
-    // retryable!(|| { do_something(1, 2, 3, 4) }; retries=2);
-    // ```
-    ($f:expr; retries=$r:expr) => {{
-        let _strategy = RetryStrategy::default().with_retries($r).to_owned();
-        let mut _r = Retryable::new($f, _strategy);
-        _r.try_call()
-    }};
-    // Take a closure with delay time (seconds)
-    // ```ignore
-    // retryable!(|| { do_something(1, 2, 3, 4) }; delay=2);
-    // ```
-    ($f:expr; delay=$d:expr) => {{
-        let _delay = RetryDelay::Fixed(Duration::from_secs($d));
-        let _strategy = RetryStrategy::default().with_delay(_delay).to_owned();
-        let mut _r = Retryable::new($f, _strategy);
-        _r.try_call()
-    }};
-    // Take a closure with retry count & delay time (seconds)
+    // Take a closure (or function ptr) and options
     // ```ignore
     // retryable!(|| { do_something(1, 2, 3, 4) }; retries=2; delay=2);
     // ```
-    ($f:expr; retries=$r:expr; delay=$d:expr) => {{
-        let _delay = RetryDelay::Fixed(Duration::from_secs($d));
-        let _strategy = RetryStrategy::default().with_delay(_delay).to_owned();
-        let mut _r = Retryable::new($f, _strategy);
-        _r.try_call()
+    ($f:expr $(; $($opts:tt)*)?) => {{
+        $crate::retryable!(@run $f; $($($opts)*)?)
     }};
-    // Take a closure (default of 3 retries)
-    // ```ignore
-    // retryable!(|| { do_something(1, 2, 3, 4) });
-    // ```
-    ($f:expr) => {{
-        retryable!($f; retries = 3)
-    }};
-    // Take a function ptr with variadic args (default of 3 retries)
+    // Take a function ptr, variadic args, and options
     // ```ignore
     // retryable!(my_fallible_func, 0, "something"; retries=5);
     // ```
-    ($($args:expr$(,)?)+) => {{
-        retryable($($args)*; retries=3)
+    ($f:expr, $($args:expr),+ $(,)? $(; $($opts:tt)*)?) => {{
+        $crate::retryable!(@args [$($($opts)*)?] $f, ($($args),+); $($($opts)*)?)
     }};
-    // Take a function ptr, variadic args, and retry count
-    // ```ignore
-    // retryable!(my_fallible_func, 0, "something"; retries=5);
-    // ```
-    ($($args:expr$(,)?)+; retries=$r:expr) => {{
-        retryable!(|| { _wrapper!($($args,)*)}; retries=$r)
-
-        // This rule can hit the recursion limit for macros
-        // If that's a problem, we can remove some recursion like:
-        // let _strategy = RetryStrategy::default().with_retries($r).to_owned();
-        // let mut _r = Retryable::new(|| { _wrapper!($($args,)*)}, _strategy);
-        // _r.try_call()
+    // Whether the args are evaluated for each attempt (the default) or once
+    (@args [] $f:expr, ($($args:expr),+); $($opts:tt)*) => {
+        $crate::retryable!(@run || { $crate::_wrapper!($f, $($args,)*) }; $($opts)*)
+    };
+    (@args [args=once $($rest:tt)*] $f:expr, ($($args:expr),+); $($opts:tt)*) => {
+        $crate::retryable!(@run $crate::_once!($f, $($args,)*); $($opts)*)
+    };
+    (@args [$skip:tt $($rest:tt)*] $f:expr, ($($args:expr),+); $($opts:tt)*) => {
+        $crate::retryable!(@args [$($rest)*] $f, ($($args),+); $($opts)*)
+    };
+    (@run $f:expr; $($opts:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut _r = $crate::Retryable::new($f, $crate::RetryStrategy::default());
+        $crate::retryable!(@opts _r; $($opts)*);
+        $crate::retryable!(@call _r; $($opts)*)
     }};
-    // Take a function ptr, variadic args, and delay time (seconds)
-    // ```ignore
-    // retryable!(my_fallible_func, 0, "something"; delay=5);
-    // ```
-    ($($args:expr$(,)?)+; delay=$d:expr) => {{
-        retryable!(|| { _wrapper!($($args,)*)}; delay=$d)
+    // A `context=` option wraps the final error, otherwise it's returned as-is
+    (@call $r:ident;) => {
+        $r.try_call()
+    };
+    (@call $r:ident; context=$c:expr $(; $($rest:tt)*)?) => {
+        $r.try_call().map_err(|e| $crate::RetryError::new($c, e))
+    };
+    (@call $r:ident; $skip:tt $($rest:tt)*) => {
+        $crate::retryable!(@call $r; $($rest)*)
+    };
+    // Options can be given in any order, each one adjusts the `Retryable`
+    (@opts $r:ident;) => {};
+    (@opts $r:ident; retries=$n:expr $(; $($rest:tt)*)?) => {
+        $r.strategy_mut().with_retries($n);
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; delay=$d:expr $(; $($rest:tt)*)?) => {
+        $r.strategy_mut()
+            .with_delay($crate::RetryDelay::Fixed(std::time::Duration::from_secs($d)));
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; delay_ms=$d:expr $(; $($rest:tt)*)?) => {
+        $r.strategy_mut()
+            .with_delay($crate::RetryDelay::Fixed(std::time::Duration::from_millis($d)));
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; for=$d:literal $(; $($rest:tt)*)?) => {
+        let _total = const { $crate::__duration_literal(stringify!($d)) };
+        $crate::retryable!(@opts $r; for=_total $(; $($rest)*)?);
+    };
+    (@opts $r:ident; for=$d:expr $(; $($rest:tt)*)?) => {
+        $r.strategy_mut().with_retries(usize::MAX).with_max_elapsed($d);
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; max_backoff_total=$d:literal $(; $($rest:tt)*)?) => {
+        let _total = const { $crate::__duration_literal(stringify!($d)) };
+        $crate::retryable!(@opts $r; max_backoff_total=_total $(; $($rest)*)?);
+    };
+    (@opts $r:ident; max_backoff_total=$d:expr $(; $($rest:tt)*)?) => {
+        $r.strategy_mut().with_max_backoff_total($d);
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; strategy=$s:expr $(; $($rest:tt)*)?) => {
+        *$r.strategy_mut() = $s;
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; context=$c:expr $(; $($rest:tt)*)?) => {
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    // Already applied while wrapping the function, see `@args`
+    (@opts $r:ident; args=once $(; $($rest:tt)*)?) => {
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; args=each_attempt $(; $($rest:tt)*)?) => {
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+    (@opts $r:ident; retry_on=$($p:path)|+ $(; $($rest:tt)*)?) => {
+        $r = $r.with_decider(|_res: &Result<_, _>| match _res {
+            Ok(_) => $crate::Decision::Accept,
+            Err(_e) if matches!($crate::RetryKind::retry_kind(_e), $($p)|+) => $crate::Decision::Retry,
+            Err(_) => $crate::Decision::Abort,
+        });
+        $crate::retryable!(@opts $r; $($($rest)*)?);
+    };
+}
+
+/// Run a block under a deadline, capping every retry loop started inside it
+///
+/// Retries that would still be backing off past the deadline aren't made, so the
+/// block can't overrun its caller's budget because of them:
+/// ```
+/// use std::time::{Duration, Instant};
+/// use retryable::{deadline, RetryDelay, RetryStrategy, Retryable};
+///
+/// let start = Instant::now();
+/// let res = deadline!(Duration::from_millis(50), {
+///     let strategy = RetryStrategy::new(10, RetryDelay::Fixed(Duration::from_millis(20)));
+///     Retryable::new(|| Err::<(), _>("unavailable"), strategy).try_call()
+/// });
+/// assert!(res.is_err());
+/// assert!(start.elapsed() < Duration::from_millis(200));
+/// ```
+/// Nested deadlines can only shorten the one in place, see [`deadline::enter`].
+/// The block runs in place, so `?` and `return` work as usual.
+#[macro_export]
+macro_rules! deadline {
+    ($budget:expr, $body:block) => {{
+        let _deadline = $crate::deadline::enter($budget);
+        $body
     }};
-    // Take a function ptr, variadic args, retry count, and delay time (seconds)
-    // ```ignore
-    // retryable!(my_fallible_func, 0, "something"; retries=2; delay=5);
-    // ```
-    ($($args:expr$(,)?)+; retries=$r:expr; delay=$d:expr) => {{
-        retryable!(|| { _wrapper!($($args,)*)}; retries=$r; delay=$d)
+}
+
+/// Retry a whole block as a unit, for multi-step operations that have to start
+/// over when any step fails
+///
+/// Any `Err` returned from the block (including through `?`) retries it from the top:
+/// ```ignore
+/// let reply = retry_scope!(retries=3, delay_ms=200, {
+///     let mut c = connect()?;
+///     c.send(msg)?;
+///     Ok(c.recv()?)
+/// });
+/// ```
+/// Use [`retryme!()`](crate::retryme) to start over without an error (on an
+/// unexpected reply, for instance). Running out of retries that way panics, as
+/// there's no error to return: return an `Err` instead if that can happen.
+///
+/// Options are `retries` (default 3), `delay_ms` (default 0) or a whole `strategy`.
+#[macro_export]
+macro_rules! retry_scope {
+    ($($key:ident = $val:expr,)* $body:block) => {{
+        #[allow(unused_mut)]
+        let mut _strategy = $crate::RetryStrategy::new(
+            3,
+            $crate::RetryDelay::Fixed(std::time::Duration::from_millis(0)),
+        );
+        $($crate::retry_scope!(@opt _strategy; $key = $val);)*
+        let mut _retry = $crate::RetryLoop::new(_strategy);
+        loop {
+            let _res = $crate::scope::attempt(|| $body);
+            let _decision = match &_res {
+                Some(_res) => $crate::Decision::default_for(_res),
+                None => $crate::Decision::Retry,
+            };
+            match (_retry.after(_decision), _res) {
+                (Some(_delay), _) => {
+                    std::thread::sleep(_delay);
+                    _retry.resume();
+                }
+                (None, Some(_res)) => {
+                    _retry.finish(_res.is_err());
+                    break _res;
+                }
+                (None, None) => {
+                    _retry.finish(true);
+                    panic!("retryme!() with no retries left")
+                }
+            }
+        }
     }};
+    (@opt $s:ident; retries = $n:expr) => {
+        $s.with_retries($n);
+    };
+    (@opt $s:ident; delay_ms = $d:expr) => {
+        $s.with_delay($crate::RetryDelay::Fixed(std::time::Duration::from_millis($d)));
+    };
+    (@opt $s:ident; strategy = $strategy:expr) => {
+        $s = $strategy;
+    };
+}
+
+/// Start the enclosing [`retry_scope!`] block over, as if it had returned an `Err`
+#[macro_export]
+macro_rules! retryme {
+    () => {
+        $crate::scope::retry_now()
+    };
 }
 
 #[cfg(test)]
@@ -259,7 +1016,6 @@ mod tests {
     /// assert!(eventually_succeed().is_err());
     /// assert!(eventually_succeed().is_ok());
     /// ```
-    #[macro_use]
     macro_rules! succeed_after {
         ($count:expr) => {{
             let mut _iter = (0..$count).into_iter();
@@ -348,6 +1104,84 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn test_retry_closure_args() {
+        use std::cell::Cell;
+
+        // Arguments are evaluated again for every attempt
+        let endpoints = ["a.example", "b.example", "c.example"];
+        let next = Cell::new(0);
+        let next_endpoint = || {
+            next.set(next.get() + 1);
+            endpoints[next.get() - 1]
+        };
+        fn connect(endpoint: &str, port: u16) -> Result<u16, ()> {
+            endpoint.strip_prefix("c.").map(|_| port).ok_or(())
+        }
+        let res = retry!(|endpoint| connect(endpoint, 443), next_endpoint());
+        assert_eq!(res, Ok(443));
+        assert_eq!(next.get(), 3);
+
+        next.set(0);
+        let res = retryable!(
+            |endpoint: &str, port| if endpoint == "b.example" { Ok(port) } else { Err(()) },
+            next_endpoint(),
+            443;
+            retries = 3;
+            delay_ms = 1
+        );
+        assert_eq!(res, Ok(443));
+        assert_eq!(next.get(), 2);
+    }
+
+    #[test]
+    fn test_retry_args_once() {
+        use std::cell::Cell;
+
+        let signed = Cell::new(0);
+        let sign = || {
+            signed.set(signed.get() + 1);
+            format!("token-{}", signed.get())
+        };
+        // Only accepts the third token signed
+        let send = |token: String, _port: u16| {
+            if token == "token-3" {
+                Ok(token)
+            } else {
+                Err(token)
+            }
+        };
+
+        let res = retry!(send, sign(), 443; retries=5; args=once);
+        assert_eq!(res, Err("token-1".to_string()));
+        assert_eq!(signed.get(), 1);
+
+        signed.set(0);
+        let res = retry!(send, sign(), 443; args=each_attempt);
+        assert_eq!(res, Ok("token-3".to_string()));
+        assert_eq!(signed.get(), 3);
+
+        // In any order, or with the default retries
+        signed.set(0);
+        let res = retry!(send, sign(), 443; args=once; retries=5);
+        assert_eq!(res, Err("token-1".to_string()));
+        assert_eq!(signed.get(), 1);
+        signed.set(0);
+        let res = retry!(send, sign(), 443; args=once);
+        assert_eq!(res, Err("token-1".to_string()));
+        assert_eq!(signed.get(), 1);
+
+        signed.set(0);
+        let res = retryable!(send, sign(), 443; retries=5; args=once; delay_ms=1);
+        assert_eq!(res, Err("token-1".to_string()));
+        assert_eq!(signed.get(), 1);
+
+        signed.set(0);
+        let res = retryable!(send, sign(), 443; delay_ms=1; args=each_attempt);
+        assert_eq!(res, Ok("token-3".to_string()));
+        assert_eq!(signed.get(), 3);
+    }
+
     #[test]
     fn test_retryable_simple() {
         let strategy = RetryStrategy::default().with_retries(3).to_owned();
@@ -385,4 +1219,526 @@ mod tests {
         assert!(res.is_ok());
         assert!(start.elapsed() > Duration::from_secs(6));
     }
+
+    #[test]
+    fn test_exponential_delay() {
+        let strategy = RetryStrategy::builder()
+            .retries(4)
+            .delay(RetryDelay::exponential(Duration::from_millis(10)))
+            .max_delay(Duration::from_millis(30))
+            .build()
+            .unwrap();
+        let mut state = RetryState::new(strategy);
+        assert_eq!(state.next_delay(), Some(Duration::from_millis(10)));
+        assert_eq!(state.next_delay(), Some(Duration::from_millis(20)));
+        assert_eq!(state.next_delay(), Some(Duration::from_millis(30)));
+        assert_eq!(state.next_delay(), Some(Duration::from_millis(30)));
+        assert_eq!(state.next_delay(), None);
+    }
+
+    #[test]
+    fn test_max_elapsed() {
+        let strategy = RetryStrategy::builder()
+            .retries(100)
+            .delay(RetryDelay::Fixed(Duration::from_millis(10)))
+            .max_elapsed(Duration::from_millis(35))
+            .build()
+            .unwrap();
+        let mut r = Retryable::new(|| Err::<(), ()>(()), strategy);
+        let start = Instant::now();
+        assert!(r.try_call().is_err());
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // Given up on rather than overflowing
+        let mut strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::MAX));
+        strategy.with_max_elapsed(Duration::from_secs(60));
+        let mut r = Retryable::new(|| Err::<(), ()>(()), strategy);
+        assert!(r.try_call().is_err());
+        assert_eq!(r.attempts_made(), 1);
+        strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::MAX));
+        strategy.with_max_backoff_total(Duration::from_secs(60));
+        let mut state = RetryState::new(strategy);
+        let second = Duration::from_secs(1);
+        assert_eq!(state.after(Decision::RetryAfter(second)), Some(second));
+        assert_eq!(state.after(Decision::RetryAfter(Duration::MAX)), None);
+    }
+
+    #[test]
+    fn test_huge_delay() {
+        let mut state = RetryState::new(RetryStrategy::new(3, RetryDelay::Fixed(Duration::MAX)));
+        assert_eq!(state.next_delay(), Some(Duration::MAX));
+        assert!(state.remaining_wait().unwrap() > Duration::from_secs(1 << 40));
+        assert_eq!(state.next_delay(), Some(Duration::MAX));
+        assert_eq!(state.attempt().backoff, Duration::MAX);
+    }
+
+    #[test]
+    fn test_strategy_validation() {
+        let res = RetryStrategy::builder()
+            .delay(RetryDelay::exponential(Duration::from_millis(10)))
+            .max_delay(Duration::from_millis(0))
+            .build();
+        assert_eq!(res.unwrap_err(), StrategyError::ZeroMaxDelay);
+
+        let res = RetryStrategy::builder()
+            .delay(RetryDelay::Exponential {
+                initial: Duration::from_millis(10),
+                multiplier: 0.5,
+            })
+            .build();
+        assert_eq!(res.unwrap_err(), StrategyError::InvalidMultiplier(0.5));
+
+        let res = RetryStrategy::builder()
+            .delay(RetryDelay::Fixed(Duration::from_secs(1)))
+            .max_delay(Duration::from_millis(10))
+            .build();
+        assert!(matches!(
+            res,
+            Err(StrategyError::MaxDelayBelowInitial { .. })
+        ));
+
+        let strategy = RetryStrategy::default()
+            .with_max_elapsed(Duration::from_millis(1))
+            .to_owned();
+        assert!(strategy.validate().is_err());
+        assert!(RetryStrategy::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_decider() {
+        // Retry "successful" empty pages, abort on a hard error
+        let mut pages = vec![Err("gone"), Ok(vec![]), Ok(vec![1, 2])];
+        let strategy = RetryStrategy::new(5, RetryDelay::Fixed(Duration::from_millis(1)));
+        let decider = |res: &Result<Vec<u32>, &str>| match res {
+            Ok(page) if page.is_empty() => Decision::RetryAfter(Duration::from_millis(2)),
+            Ok(_) => Decision::Accept,
+            Err(_) => Decision::Abort,
+        };
+        let mut r = Retryable::new(|| pages.pop().unwrap(), strategy.clone()).with_decider(decider);
+        assert_eq!(r.try_call(), Ok(vec![1, 2]));
+
+        let mut pages = vec![Err("gone"), Ok(vec![])];
+        let mut r = Retryable::new(|| pages.pop().unwrap(), strategy).with_decider(decider);
+        assert_eq!(r.try_call(), Err("gone"));
+    }
+
+    #[test]
+    fn test_adaptive_delay() {
+        let delay = RetryDelay::adaptive(
+            "tests::adaptive",
+            Duration::from_millis(1),
+            Duration::from_millis(50),
+        );
+        let strategy = RetryStrategy::builder()
+            .retries(3)
+            .delay(delay)
+            .build()
+            .unwrap();
+        let mut r = Retryable::new(succeed_after!(3), strategy.clone());
+        assert!(r.try_call().is_ok());
+        assert_eq!(adaptive::failure_rate("tests::adaptive"), 0.75);
+
+        // The next loop backs off further since the upstream looks unhealthy
+        let mut state = RetryState::new(strategy);
+        assert!(state.after(Decision::Retry) > Some(Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn test_deadline() {
+        let strategy = RetryStrategy::new(10, RetryDelay::Fixed(Duration::from_millis(10)));
+        let mut r = Retryable::new(succeed_after!(100), strategy.clone());
+        let start = Instant::now();
+        assert!(deadline!(Duration::from_millis(35), { r.try_call() }).is_err());
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(r.attempts_made(), 4);
+
+        // A shorter max_elapsed of its own is kept
+        let mut strategy = strategy;
+        strategy.with_max_elapsed(Duration::from_millis(15));
+        let mut r = Retryable::new(succeed_after!(100), strategy);
+        let _ = deadline!(Duration::from_secs(10), { r.try_call() });
+        assert_eq!(r.attempts_made(), 2);
+    }
+
+    #[cfg(feature = "telemetry")]
+    #[test]
+    fn test_telemetry() {
+        let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)));
+        let mut r = Retryable::new(succeed_after!(2), strategy.clone());
+        let _ = r.try_call();
+        let line = line!() - 1;
+        let stats = telemetry::sites()
+            .into_iter()
+            .find(|(l, _)| l.file() == file!() && l.line() == line)
+            .map(|(_, s)| s)
+            .unwrap();
+        assert_eq!(stats.calls, 1);
+        assert_eq!(stats.attempts, 3);
+        assert_eq!(stats.retries, 2);
+        assert_eq!(stats.give_ups, 0);
+        assert!(stats.backoff >= Duration::from_millis(2));
+        assert!(telemetry::dump().contains("src/lib.rs"));
+    }
+
+    #[test]
+    fn test_retryable_macro_strategy() {
+        let strategy = RetryStrategy::builder()
+            .retries(5)
+            .delay(RetryDelay::Fixed(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+        let res = retryable!(succeed_after!(4); strategy=strategy.clone());
+        assert!(res.is_ok());
+        let res = retryable!(succeed_after!(6); strategy=strategy.clone());
+        assert!(res.is_err());
+        let res = retryable!(sometimes_fail, 10; strategy=strategy);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_retryable_macro_retry_on() {
+        use std::io;
+
+        let mut errors = vec![
+            io::Error::from(io::ErrorKind::NotFound),
+            io::Error::from(io::ErrorKind::WouldBlock),
+            io::Error::from(io::ErrorKind::Interrupted),
+        ];
+        let mut read = || -> io::Result<()> { Err(errors.pop().unwrap()) };
+        let res = retryable!(
+            &mut read;
+            retries=5;
+            retry_on=io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock;
+            delay=0
+        );
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(errors.is_empty());
+
+        fn interrupted_once(calls: &mut u32) -> io::Result<u32> {
+            *calls += 1;
+            match calls {
+                1 => Err(io::ErrorKind::Interrupted.into()),
+                _ => Ok(*calls),
+            }
+        }
+        let mut calls = 0;
+        let res =
+            retryable!(interrupted_once, &mut calls; retry_on=io::ErrorKind::Interrupted; delay=0);
+        assert_eq!(res.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_retryable_macro_context() {
+        use std::error::Error;
+        use std::io;
+
+        fn fetch_user(_id: u32) -> io::Result<String> {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "connection timed out",
+            ))
+        }
+        let err = retryable!(fetch_user, 7; retries=1; context="loading user profile"; delay=0)
+            .unwrap_err();
+        assert_eq!(err.context(), "loading user profile");
+        assert_eq!(
+            err.to_string(),
+            "loading user profile: connection timed out"
+        );
+        assert!(err.source().is_some());
+        assert_eq!(err.into_inner().kind(), io::ErrorKind::TimedOut);
+
+        let ok: Result<u32, RetryError<()>> = retryable!(|| Ok(1); context="unused");
+        assert_eq!(ok.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_retryable_macro_for() {
+        let started = Instant::now();
+        let mut calls = 0;
+        let res: Result<(), usize> =
+            retryable!(|| { calls += 1; Err(calls) }; for=300ms; delay_ms=50);
+        // Retried until the next delay would end past `for`, however slow the
+        // machine: at most 6 delays of 50ms fit in 300ms
+        let calls = res.unwrap_err();
+        assert!((2..=7).contains(&calls), "{} calls", calls);
+        assert!(started.elapsed() >= Duration::from_millis(50) * (calls as u32 - 1));
+
+        let mut calls = 0;
+        let res = retryable!(|| { calls += 1; if calls < 20 { Err(()) } else { Ok(calls) } }; for=Duration::from_secs(5); delay_ms=1);
+        assert_eq!(res, Ok(20));
+    }
+
+    #[test]
+    fn test_max_backoff_total() {
+        // Slow attempts don't count, only the delays in between
+        let mut strategy = RetryStrategy::new(10, RetryDelay::Fixed(Duration::from_millis(10)));
+        strategy
+            .with_max_elapsed(Duration::from_secs(60))
+            .with_max_backoff_total(Duration::from_millis(25));
+        let mut calls = 0;
+        let mut r = Retryable::new(
+            || {
+                calls += 1;
+                std::thread::sleep(Duration::from_millis(15));
+                Err::<(), _>(calls)
+            },
+            strategy,
+        );
+        assert_eq!(r.try_call(), Err(3));
+
+        let mut calls = 0;
+        let res: Result<(), usize> = retryable!(|| { calls += 1; Err(calls) }; retries=10; delay_ms=10; max_backoff_total=35ms);
+        assert_eq!(res, Err(4));
+        let res: Result<(), ()> = retryable!(|| Err(()); retries=10; max_backoff_total=Duration::ZERO);
+        assert_eq!(res, Err(()));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        // A strategy behaves the same whether it's the strategy or the policy
+        let strategy = RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(1)));
+        let mut r = Retryable::new(succeed_after!(2), RetryStrategy::default())
+            .with_policy(strategy.clone());
+        assert!(r.try_call().is_ok());
+        let mut r = Retryable::new(succeed_after!(3), RetryStrategy::default())
+            .with_policy(strategy);
+        assert!(r.try_call().is_err());
+
+        // The policy replaces the decider
+        let mut expected = 0;
+        let mut r = Retryable::new(succeed_after!(4), RetryStrategy::default())
+            .with_decider(|_: &Result<(), ()>| Decision::Abort)
+            .with_policy(move |attempt: &Attempt, res: &Result<(), ()>| {
+                expected += 1;
+                assert_eq!(attempt.number, expected);
+                match res {
+                    Ok(_) => Decision::Accept,
+                    Err(_) => Decision::Retry,
+                }
+            });
+        assert!(r.try_call().is_ok());
+    }
+
+    #[crate::attr::retry(retries = 4, delay = "1ms")]
+    fn attribute_retry(calls: &mut usize) -> Result<usize, usize> {
+        *calls += 1;
+        if *calls < 3 {
+            return Err(*calls);
+        }
+        Ok(*calls)
+    }
+
+    #[crate::attr::retry(retries = 100, delay = "20ms", max_elapsed = "50ms")]
+    async fn attribute_retry_async(calls: &mut usize) -> Result<(), ()> {
+        *calls += 1;
+        Err(())
+    }
+
+    #[test]
+    fn test_retry_attribute() {
+        let mut calls = 0;
+        assert_eq!(attribute_retry(&mut calls), Ok(3));
+
+        let mut calls = 0;
+        let res = future::tests::block_on(attribute_retry_async(&mut calls));
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[cfg(feature = "observability")]
+    #[test]
+    fn test_observability() {
+        let strategy = RetryStrategy::new(1, RetryDelay::Fixed(Duration::from_millis(300)));
+        let handle = std::thread::spawn(move || {
+            let mut r = Retryable::new(succeed_after!(1), strategy);
+            r.try_call()
+        });
+        let file = file!();
+        std::thread::sleep(Duration::from_millis(100));
+        let snapshot = observability::snapshot();
+        let (op, until) = snapshot
+            .backoffs()
+            .find(|(op, _)| op.kind == observability::Kind::Retry && op.label.starts_with(file))
+            .unwrap();
+        assert_eq!(op.attempt, 1);
+        assert!(until > Instant::now());
+
+        assert!(handle.join().unwrap().is_ok());
+        assert!(observability::snapshot()
+            .latency
+            .iter()
+            .any(|(label, _)| label.starts_with(file)));
+    }
+
+    #[cfg(feature = "observability")]
+    #[test]
+    fn test_golden() {
+        let render = || {
+            let capture = observability::golden::capture();
+            seed_jitter(7);
+            let strategy = RetryStrategy::builder()
+                .retries(3)
+                .delay(RetryDelay::exponential(Duration::from_millis(100)))
+                .jitter(Jitter::Full)
+                .build()
+                .unwrap();
+            let mut r = Retryable::new(succeed_after!(2), strategy).with_sleeper(|_| {});
+            assert!(r.try_call().is_ok());
+            capture.render()
+        };
+        let golden = render();
+        let events: Vec<_> = golden.lines().collect();
+        assert_eq!(events.len(), 3);
+        assert!(events[0].starts_with("event=backoff kind=retry label=\"src/lib.rs:"));
+        assert!(events[2].contains(" id=1 attempt=3 elapsed_ms="));
+        assert_eq!(render(), golden);
+    }
+
+    #[test]
+    fn test_retry_scope() {
+        use std::io;
+
+        let mut connects = 0;
+        let mut replies = vec!["ok", "busy"];
+        let res: io::Result<&str> = retry_scope!(retries = 3, delay_ms = 1, {
+            connects += 1;
+            if connects == 1 {
+                return Err(io::ErrorKind::ConnectionRefused.into());
+            }
+            let reply = replies.pop().ok_or(io::ErrorKind::UnexpectedEof)?;
+            if reply == "busy" {
+                retryme!();
+            }
+            Ok(reply)
+        });
+        assert_eq!(res.unwrap(), "ok");
+        assert_eq!(connects, 3);
+
+        let mut attempts = 0;
+        let res: Result<(), ()> = retry_scope!(retries = 2, {
+            attempts += 1;
+            Err(())
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of a retry_scope!")]
+    fn test_retryme_outside_scope() {
+        retryme!();
+    }
+
+    #[test]
+    fn test_progress() {
+        let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(200)));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut r = Retryable::new(succeed_after!(1), strategy);
+            tx.send(r.progress()).unwrap();
+            r.try_call()
+        });
+        let progress = rx.recv().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(progress.attempts_made(), 1);
+        assert_eq!(progress.remaining_retries(), 3);
+        assert_eq!(progress.next_delay(), Some(Duration::from_millis(200)));
+        assert!(progress.elapsed() >= Duration::from_millis(100));
+
+        assert!(handle.join().unwrap().is_ok());
+        assert_eq!(progress.attempts_made(), 2);
+        assert_eq!(progress.remaining_retries(), 2);
+        assert_eq!(progress.next_delay(), None);
+        let elapsed = progress.elapsed();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(progress.elapsed(), elapsed);
+    }
+
+    #[test]
+    fn test_catch_panics() {
+        let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)));
+        let mut calls = 0;
+        let mut r = Retryable::new(
+            || {
+                calls += 1;
+                if calls < 3 {
+                    panic!("flaky");
+                }
+                Ok::<_, ()>(calls)
+            },
+            strategy,
+        )
+        .catch_panics("flaky op");
+        assert_eq!(r.try_call(), Ok(3));
+        assert_eq!(r.attempts_made(), 3);
+    }
+
+    #[test]
+    fn test_time_in_backoff() {
+        let strategy = RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(50)));
+        let mut r = Retryable::new(
+            || {
+                std::thread::sleep(Duration::from_millis(10));
+                Err::<(), _>("slow and down")
+            },
+            strategy,
+        );
+        assert!(r.try_call().is_err());
+        let (attempts, backoff) = (r.time_in_attempts(), r.time_in_backoff());
+        assert!(backoff >= Duration::from_millis(100));
+        assert!(attempts >= Duration::from_millis(30));
+        assert_eq!(attempts + backoff, r.elapsed());
+    }
+
+    #[test]
+    fn test_full_jitter() {
+        let base = Duration::from_millis(10);
+        let cap = Duration::from_millis(50);
+        let strategy = RetryStrategy::new(10, RetryDelay::full_jitter(base, cap));
+        for (retry, ceiling) in [(1, 10), (2, 20), (3, 40), (4, 50), (60, 50)] {
+            let delays: Vec<_> = (0..200).map(|_| strategy.delay_for(retry)).collect();
+            assert!(delays.iter().all(|d| *d <= Duration::from_millis(ceiling)));
+            // Not stuck at the ceiling (or at zero)
+            assert!(delays.iter().any(|d| *d < Duration::from_millis(ceiling / 2)));
+            assert!(delays.iter().any(|d| *d > Duration::from_millis(ceiling / 2)));
+        }
+
+        let res = RetryStrategy::builder()
+            .delay(RetryDelay::full_jitter(cap, base))
+            .build();
+        assert!(matches!(res, Err(StrategyError::MaxDelayBelowInitial { .. })));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let mut strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(100)));
+        strategy.with_jitter(Jitter::Equal);
+        for retry in 1..=3 {
+            let delay = strategy.jittered_delay_for(retry);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+
+        let cap = Duration::from_millis(500);
+        strategy
+            .with_jitter(Jitter::Decorrelated { cap })
+            .with_max_delay(Duration::from_millis(300));
+        let mut previous = strategy.jittered_delay_for(1);
+        assert_eq!(previous, Duration::from_millis(100));
+        for retry in 2..=20 {
+            let delay = strategy.jittered_delay_for(retry);
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= (previous * 3).min(Duration::from_millis(300)));
+            previous = delay;
+        }
+        // A new loop starts over from the schedule
+        assert_eq!(strategy.jittered_delay_for(1), Duration::from_millis(100));
+
+        let res = RetryStrategy::builder()
+            .jitter(Jitter::Decorrelated {
+                cap: Duration::from_millis(1),
+            })
+            .build();
+        assert!(matches!(res, Err(StrategyError::MaxDelayBelowInitial { .. })));
+    }
 }
@@ -0,0 +1,129 @@
+//! Waiting for a service to come up
+//!
+//! Connect to a TCP address, retrying on the given strategy while the service
+//! starts (connection refused, timed out, not resolvable yet):
+//! ```no_run
+//! use std::time::Duration;
+//! use retryable::{net::connect_with_retry, RetryDelay, RetryStrategy};
+//!
+//! let mut strategy = RetryStrategy::time_bounded(
+//!     Duration::from_secs(30),
+//!     RetryDelay::exponential(Duration::from_millis(100)),
+//! );
+//! strategy.with_max_delay(Duration::from_secs(2));
+//! let stream = connect_with_retry("localhost:5432", strategy)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::{Decision, RetryStrategy, Retryable};
+
+/// How long a single connection attempt may take
+pub const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Any connect error is worth retrying, except for a malformed address
+fn decide<T>(res: &io::Result<T>) -> Decision {
+    match res {
+        Ok(_) => Decision::Accept,
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => Decision::Abort,
+        Err(_) => Decision::Retry,
+    }
+}
+
+/// Connect to `addr`, retrying failed connections as the strategy allows
+///
+/// The address is resolved again on each attempt (the service may not be in DNS yet),
+/// and each resolved address is tried with a timeout of [`ATTEMPT_TIMEOUT`].
+pub fn connect_with_retry<A: ToSocketAddrs>(
+    addr: A,
+    strategy: RetryStrategy,
+) -> io::Result<TcpStream> {
+    Retryable::new(|| connect(&addr), strategy)
+        .with_decider(decide)
+        .try_call()
+}
+
+fn connect<A: ToSocketAddrs>(addr: &A) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, ATTEMPT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "address resolved to nothing")
+    }))
+}
+
+/// Async [`connect_with_retry`], returning a `tokio` stream
+#[cfg(feature = "tokio")]
+pub async fn connect_with_retry_async<A>(
+    addr: A,
+    strategy: RetryStrategy,
+) -> io::Result<tokio::net::TcpStream>
+where
+    A: tokio::net::ToSocketAddrs + Clone,
+{
+    let connect = || {
+        let addr = addr.clone();
+        async move {
+            tokio::time::timeout(ATTEMPT_TIMEOUT, tokio::net::TcpStream::connect(addr))
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+        }
+    };
+    crate::future::AsyncRetryable::new(connect, strategy)
+        .with_decider(decide)
+        .try_call()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryDelay;
+    use std::net::TcpListener;
+
+    fn strategy() -> RetryStrategy {
+        RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(1)))
+    }
+
+    /// An address nothing is listening on
+    fn closed_addr() -> std::net::SocketAddr {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    #[test]
+    fn test_connect_with_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(connect_with_retry(addr, strategy()).is_ok());
+
+        let err = connect_with_retry(closed_addr(), strategy()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+        let err = connect_with_retry("no port", strategy()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_connect_with_retry_async() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        rt.block_on(async {
+            assert!(connect_with_retry_async(addr, strategy()).await.is_ok());
+            let err = connect_with_retry_async(closed_addr(), strategy())
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        });
+    }
+}
@@ -0,0 +1,108 @@
+//! Saving a retry loop's progress across process restarts
+//!
+//! A job runner can store "attempt 3, next eligible at T" alongside the job,
+//! and resume the same strategy (and remaining attempt budget) when the
+//! job is picked up again:
+//! ```ignore
+//! let mut state = RetryState::new(strategy);
+//! if let Some(delay) = state.next_delay() {
+//!     queue.reschedule(job_id, delay, state.to_bytes())?;
+//! }
+//! // ... possibly in another process
+//! let mut state = RetryState::from_bytes(&job.retry_state)?;
+//! ```
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{RetryState, RetryStrategy};
+
+/// Instants mean nothing outside the process, so they're stored as wall-clock times
+#[derive(Serialize, Deserialize)]
+struct Persisted {
+    strategy: RetryStrategy,
+    attempts: usize,
+    started: SystemTime,
+    #[serde(default)]
+    backoff: Duration,
+    next_eligible: Option<SystemTime>,
+}
+
+/// Saved retry state that couldn't be read back
+#[derive(Debug)]
+pub struct PersistError(serde_json::Error);
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid retry state: {}", self.0)
+    }
+}
+
+impl Error for PersistError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl RetryState {
+    /// Serialize the strategy, attempts made so far, and when the next one is due
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let now = SystemTime::now();
+        let persisted = Persisted {
+            strategy: self.strategy.clone(),
+            attempts: self.attempts,
+            started: now - self.started.elapsed(),
+            backoff: self.backoff,
+            next_eligible: self.remaining_wait().map(|wait| now + wait),
+        };
+        serde_json::to_vec(&persisted).expect("retry state is always serializable")
+    }
+
+    /// Resume a state saved with [`to_bytes()`](Self::to_bytes)
+    ///
+    /// Time spent while the state was saved counts towards `max_elapsed`, and
+    /// [`remaining_wait()`](Self::remaining_wait) is however much of the delay is left.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PersistError> {
+        let persisted: Persisted = serde_json::from_slice(bytes).map_err(PersistError)?;
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+        let since = |time: SystemTime| wall_now.duration_since(time).unwrap_or_default();
+        let until = |time: SystemTime| time.duration_since(wall_now).unwrap_or_default();
+        Ok(Self {
+            strategy: persisted.strategy,
+            attempts: persisted.attempts,
+            // Shortly after boot, the monotonic clock may not reach back that far
+            started: now.checked_sub(since(persisted.started)).unwrap_or(now),
+            backoff: persisted.backoff,
+            next_at: persisted.next_eligible.map(|time| (now, until(time))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RetryDelay, RetryState, RetryStrategy};
+    use std::time::Duration;
+
+    #[test]
+    fn test_round_trip() {
+        let strategy = RetryStrategy::new(3, RetryDelay::exponential(Duration::from_secs(60)))
+            .with_max_elapsed(Duration::from_secs(3600))
+            .clone();
+        let mut state = RetryState::new(strategy);
+        assert_eq!(state.next_delay(), Some(Duration::from_secs(60)));
+        assert_eq!(state.next_delay(), Some(Duration::from_secs(120)));
+
+        let mut resumed = RetryState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(resumed.attempts(), 2);
+        let wait = resumed.remaining_wait().unwrap();
+        assert!(wait > Duration::from_secs(119) && wait <= Duration::from_secs(120));
+        // The attempt budget carries over
+        assert_eq!(resumed.next_delay(), Some(Duration::from_secs(240)));
+        assert_eq!(resumed.next_delay(), None);
+        assert_eq!(resumed.remaining_wait(), None);
+
+        assert!(RetryState::from_bytes(b"attempt 3").is_err());
+    }
+}
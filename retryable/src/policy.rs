@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use crate::{adaptive, Decision, RetryDelay, RetryStrategy};
+
+/// Where a retry loop is at, passed to each [`RetryPolicy::decide`] call
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attempt {
+    /// The attempt just made (1 for the first call)
+    pub number: usize,
+    /// Time since the first attempt started
+    pub elapsed: Duration,
+    /// Total of the delays waited between attempts so far
+    pub backoff: Duration,
+}
+
+/// A single hook making every decision of a retry loop: whether the outcome
+/// should be retried, how long to wait, and when to give up
+///
+/// [`RetryStrategy`] is the canonical policy (retry every `Err` on its delay
+/// schedule), implement this instead when those pieces need to be decided together.
+///
+/// Return [`Decision::RetryAfter`] to retry, or [`Decision::Accept`]/[`Decision::Abort`]
+/// to return the outcome. [`Decision::Retry`] retries immediately, since there's no
+/// other strategy to take a delay from.
+/// ```
+/// use std::time::Duration;
+/// use retryable::{Attempt, Decision, RetryPolicy, RetryStrategy, Retryable};
+///
+/// /// Retry "busy" errors quickly and for a while, anything else just once
+/// struct Busy;
+///
+/// impl RetryPolicy<u32, &'static str> for Busy {
+///     fn decide(&mut self, attempt: &Attempt, outcome: &Result<u32, &'static str>) -> Decision {
+///         match outcome {
+///             Ok(_) => Decision::Accept,
+///             Err("busy") if attempt.number < 10 => Decision::RetryAfter(Duration::from_millis(1)),
+///             Err(_) if attempt.number < 2 => Decision::Retry,
+///             Err(_) => Decision::Abort,
+///         }
+///     }
+/// }
+///
+/// let mut calls = 0;
+/// let mut r = Retryable::new(
+///     || {
+///         calls += 1;
+///         if calls < 5 { Err("busy") } else { Ok(calls) }
+///     },
+///     RetryStrategy::default(),
+/// )
+/// .with_policy(Busy);
+/// assert_eq!(r.try_call(), Ok(5));
+/// ```
+pub trait RetryPolicy<T, E> {
+    fn decide(&mut self, attempt: &Attempt, outcome: &Result<T, E>) -> Decision;
+}
+
+impl<F, T, E> RetryPolicy<T, E> for F
+where
+    F: FnMut(&Attempt, &Result<T, E>) -> Decision,
+{
+    fn decide(&mut self, attempt: &Attempt, outcome: &Result<T, E>) -> Decision {
+        self(attempt, outcome)
+    }
+}
+
+impl<T, E> RetryPolicy<T, E> for RetryStrategy {
+    fn decide(&mut self, attempt: &Attempt, outcome: &Result<T, E>) -> Decision {
+        self.resolve(attempt, Decision::default_for(outcome))
+    }
+}
+
+impl RetryStrategy {
+    /// Turn a classification of an attempt into a final decision,
+    /// filling in the delay for `Retry` or giving up when out of retries/time
+    pub(crate) fn resolve(&mut self, attempt: &Attempt, decision: Decision) -> Decision {
+        if let RetryDelay::Adaptive { label, .. } = &self.delay {
+            adaptive::record(label, decision == Decision::Accept);
+        }
+        let delay = match decision {
+            Decision::Accept | Decision::Abort => return decision,
+            Decision::Retry => None,
+            Decision::RetryAfter(delay) => Some(delay),
+        };
+        if attempt.number > self.retries {
+            return Decision::Abort;
+        }
+        let delay = delay.unwrap_or_else(|| self.jittered_delay_for(attempt.number));
+        // A delay too long to add up is past any limit
+        let past = |spent: Duration, max: Option<Duration>| {
+            max.is_some_and(|max| spent.checked_add(delay).is_none_or(|t| t > max))
+        };
+        let out_of_time = past(attempt.elapsed, self.max_elapsed);
+        if out_of_time || past(attempt.backoff, self.max_backoff_total) {
+            Decision::Abort
+        } else {
+            Decision::RetryAfter(delay)
+        }
+    }
+}
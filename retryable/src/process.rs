@@ -0,0 +1,163 @@
+//! Retrying external commands
+//!
+//! For wrapping flaky CLI tools (in build scripts, test harnesses, ...):
+//! ```no_run
+//! use std::process::Command;
+//! use retryable::{process::CommandRetry, RetryStrategy};
+//!
+//! let mut fetch = Command::new("git");
+//! fetch.args(&["fetch", "origin"]);
+//! let output = CommandRetry::new(&mut fetch, RetryStrategy::default())
+//!     .retry_codes(&[128])
+//!     .output()
+//!     .expect("git fetch");
+//! ```
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::process::{Command, Output};
+
+use crate::{Decision, RetryStrategy, Retryable};
+
+/// Run a [`Command`] until it exits successfully
+///
+/// Spawn errors and non-zero exits are retried, narrow down which exit codes
+/// are worth retrying with [`CommandRetry::retry_codes`].
+pub struct CommandRetry<'a> {
+    command: &'a mut Command,
+    strategy: RetryStrategy,
+    codes: Option<Vec<i32>>,
+}
+
+impl<'a> CommandRetry<'a> {
+    pub fn new(command: &'a mut Command, strategy: RetryStrategy) -> Self {
+        Self {
+            command,
+            strategy,
+            codes: None,
+        }
+    }
+
+    /// Only retry these exit codes, failing immediately on any other
+    /// (including being killed by a signal)
+    pub fn retry_codes(mut self, codes: &[i32]) -> Self {
+        self.codes = Some(codes.to_vec());
+        self
+    }
+
+    /// Run the command (capturing stdout and stderr) until it succeeds,
+    /// returning the output of the final attempt
+    pub fn output(self) -> Result<Output, CommandError> {
+        let command = self.command;
+        let codes = self.codes;
+        let mut r = Retryable::new(|| run(command), self.strategy).with_decider(
+            move |res: &Result<Output, CommandError>| match res {
+                Ok(_) => Decision::Accept,
+                Err(CommandError::Spawn(_)) => Decision::Retry,
+                Err(CommandError::Failed(output)) => match (&codes, output.status.code()) {
+                    (None, _) => Decision::Retry,
+                    (Some(codes), Some(code)) if codes.contains(&code) => Decision::Retry,
+                    _ => Decision::Abort,
+                },
+            },
+        );
+        r.try_call()
+    }
+}
+
+fn run(command: &mut Command) -> Result<Output, CommandError> {
+    let output = command.output().map_err(CommandError::Spawn)?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(CommandError::Failed(output))
+    }
+}
+
+/// Why the last attempt at running a command failed
+#[derive(Debug)]
+pub enum CommandError {
+    /// The command couldn't be started
+    Spawn(io::Error),
+    /// The command exited unsuccessfully, with its captured output
+    Failed(Output),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Spawn(e) => write!(f, "failed to spawn command: {}", e),
+            CommandError::Failed(output) => write!(
+                f,
+                "command failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ),
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CommandError::Spawn(e) => Some(e),
+            CommandError::Failed(_) => None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::RetryDelay;
+    use std::time::Duration;
+
+    fn strategy() -> RetryStrategy {
+        RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)))
+    }
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn test_command_retry() {
+        let marker = std::env::temp_dir().join(format!("retryable-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut flaky = sh("if [ -e \"$0\" ]; then echo ok; else touch \"$0\"; exit 1; fi");
+        flaky.arg(&marker);
+        let output = CommandRetry::new(&mut flaky, strategy()).output().unwrap();
+        assert_eq!(output.stdout, b"ok\n");
+        std::fs::remove_file(&marker).unwrap();
+
+        let mut broken = sh("echo oops >&2; exit 3");
+        match CommandRetry::new(&mut broken, strategy()).output() {
+            Err(CommandError::Failed(output)) => {
+                assert_eq!(output.status.code(), Some(3));
+                assert_eq!(output.stderr, b"oops\n");
+            }
+            res => panic!("unexpected {:?}", res),
+        }
+
+        let mut missing = Command::new("/nonexistent/command");
+        let err = CommandRetry::new(&mut missing, strategy()).output().unwrap_err();
+        assert!(matches!(err, CommandError::Spawn(_)));
+    }
+
+    #[test]
+    fn test_command_retry_codes() {
+        let counter = std::env::temp_dir().join(format!("retryable-codes-{}", std::process::id()));
+        let _ = std::fs::remove_file(&counter);
+        let mut counted = sh("echo x >> \"$0\"; exit 2");
+        counted.arg(&counter);
+        let err = CommandRetry::new(&mut counted, strategy())
+            .retry_codes(&[75])
+            .output()
+            .unwrap_err();
+        assert!(err.to_string().starts_with("command failed"));
+        assert_eq!(std::fs::read_to_string(&counter).unwrap().lines().count(), 1);
+        std::fs::remove_file(&counter).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Boxed `on_retry` hook
+pub(crate) type RetryHook = Box<dyn FnMut(&RetryProgress)>;
+
+/// Boxed `on_retry` hook of the async wrapper, `Send` so its future can be
+/// spawned on a multi-threaded runtime
+pub(crate) type SendRetryHook = Box<dyn FnMut(&RetryProgress) + Send>;
+
+/// Live view of a retry loop, for reporting the progress of long retries
+///
+/// Get one from [`Retryable::progress`](crate::Retryable::progress) to read it
+/// from another thread while the loop runs, it's also handed to the
+/// [`on_retry`](crate::Retryable::on_retry) hook before each backoff:
+/// ```
+/// use std::time::Duration;
+/// use retryable::{RetryDelay, RetryStrategy, Retryable};
+///
+/// let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let mut r = Retryable::new(|| Err::<(), _>("down"), strategy).on_retry(|progress| {
+///     eprintln!(
+///         "attempt {} failed after {:?}, {} retries left, next in {:?}",
+///         progress.attempts_made(),
+///         progress.elapsed(),
+///         progress.remaining_retries(),
+///         progress.next_delay(),
+///     );
+/// });
+/// assert!(r.try_call().is_err());
+/// assert_eq!(r.progress().attempts_made(), 4);
+/// ```
+///
+/// The elapsed time splits into [`time_in_attempts`](Self::time_in_attempts) and
+/// [`time_in_backoff`](Self::time_in_backoff), telling a slow upstream apart from
+/// time the loop spent waiting on itself.
+#[derive(Clone, Debug, Default)]
+pub struct RetryProgress {
+    inner: Arc<Mutex<Progress>>,
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    retries: usize,
+    attempts: usize,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    next_delay: Option<Duration>,
+    /// Total of the finished backoffs
+    backoff: Duration,
+    /// When the current backoff started
+    backing_off: Option<Instant>,
+}
+
+impl RetryProgress {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Progress> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Attempts made so far by the current (or last) loop
+    pub fn attempts_made(&self) -> usize {
+        self.lock().attempts
+    }
+
+    /// Time since the current (or last) loop started, up to when it finished
+    pub fn elapsed(&self) -> Duration {
+        let progress = self.lock();
+        match (progress.started, progress.finished) {
+            (Some(started), Some(finished)) => finished - started,
+            (Some(started), None) => started.elapsed(),
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    /// The backoff being waited out before the next attempt, if any
+    pub fn next_delay(&self) -> Option<Duration> {
+        self.lock().next_delay
+    }
+
+    /// Time spent sleeping between attempts, including the current backoff so far
+    pub fn time_in_backoff(&self) -> Duration {
+        let progress = self.lock();
+        let current = progress
+            .backing_off
+            .map_or(Duration::from_secs(0), |since| since.elapsed());
+        progress.backoff + current
+    }
+
+    /// Time spent in the attempts themselves (everything but the backoffs)
+    pub fn time_in_attempts(&self) -> Duration {
+        self.elapsed().saturating_sub(self.time_in_backoff())
+    }
+
+    /// How many more retries the strategy allows
+    pub fn remaining_retries(&self) -> usize {
+        let progress = self.lock();
+        let retried = progress.attempts.saturating_sub(1);
+        progress.retries.saturating_sub(retried)
+    }
+
+    pub(crate) fn start(&self, retries: usize) {
+        *self.lock() = Progress {
+            retries,
+            started: Some(Instant::now()),
+            ..Progress::default()
+        };
+    }
+
+    /// Record an attempt, and the delay before the next one (if there will be one)
+    pub(crate) fn attempted(&self, attempts: usize, next_delay: Option<Duration>) {
+        let mut progress = self.lock();
+        progress.attempts = attempts;
+        progress.next_delay = next_delay;
+        match next_delay {
+            Some(_) => progress.backing_off = Some(Instant::now()),
+            None => progress.finished = Some(Instant::now()),
+        }
+    }
+
+    /// The backoff is over
+    pub(crate) fn resume(&self) {
+        let mut progress = self.lock();
+        progress.next_delay = None;
+        if let Some(since) = progress.backing_off.take() {
+            progress.backoff += since.elapsed();
+        }
+    }
+}
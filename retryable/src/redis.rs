@@ -0,0 +1,52 @@
+//! Retry classification for [`redis`](::redis) errors
+//!
+//! Dropped connections and timeouts, a server still loading its dataset, and
+//! cluster failovers are retried, anything else (wrong types, bad commands,
+//! auth failures) fails immediately:
+//! ```ignore
+//! let mut r = Retryable::new(|| con.incr("hits", 1), RetryStrategy::default())
+//!     .with_decider(retryable::redis::classify);
+//! ```
+use ::redis::{ErrorKind, RedisError};
+
+use crate::Decision;
+
+/// Decide whether a `redis` result should be retried
+pub fn classify<T>(res: &Result<T, RedisError>) -> Decision {
+    match res {
+        Ok(_) => Decision::Accept,
+        Err(e) if is_transient(e) => Decision::Retry,
+        Err(_) => Decision::Abort,
+    }
+}
+
+/// Whether running the same command again could succeed
+pub fn is_transient(e: &RedisError) -> bool {
+    e.is_io_error()
+        || matches!(
+            e.kind(),
+            ErrorKind::BusyLoadingError
+                | ErrorKind::TryAgain
+                | ErrorKind::ClusterDown
+                | ErrorKind::MasterDown
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_classify() {
+        let error = |kind| Err(RedisError::from((kind, "test")));
+
+        assert_eq!(classify::<()>(&Ok(())), Decision::Accept);
+        let dropped = RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert_eq!(classify::<()>(&Err(dropped)), Decision::Retry);
+        assert_eq!(classify::<()>(&error(ErrorKind::TryAgain)), Decision::Retry);
+        assert_eq!(classify::<()>(&error(ErrorKind::BusyLoadingError)), Decision::Retry);
+        assert_eq!(classify::<()>(&error(ErrorKind::TypeError)), Decision::Abort);
+        assert_eq!(classify::<()>(&error(ErrorKind::AuthenticationFailed)), Decision::Abort);
+    }
+}
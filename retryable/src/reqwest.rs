@@ -0,0 +1,65 @@
+//! Retry classification for [`reqwest`](::reqwest) HTTP requests
+//!
+//! Transient failures (timeouts, connection errors, `5xx` and `429` responses) are
+//! retried, while errors that will just happen again (building the request,
+//! reading the body, other `4xx` responses) fail immediately.
+//!
+//! Only errors are classified, so use
+//! [`error_for_status()`](::reqwest::Response::error_for_status) to turn error
+//! responses into errors:
+//! ```ignore
+//! let mut r = AsyncRetryable::new(
+//!     || async { client.get(url).send().await?.error_for_status() },
+//!     RetryStrategy::default(),
+//! )
+//! .with_decider(retryable::reqwest::classify);
+//! ```
+use ::reqwest::{Error, StatusCode};
+
+use crate::Decision;
+
+/// Decide whether a `reqwest` result should be retried
+pub fn classify<T>(res: &Result<T, Error>) -> Decision {
+    match res {
+        Ok(_) => Decision::Accept,
+        Err(e) if is_transient(e) => Decision::Retry,
+        Err(_) => Decision::Abort,
+    }
+}
+
+/// Whether trying the same request again could succeed
+pub fn is_transient(e: &Error) -> bool {
+    if let Some(status) = e.status() {
+        return status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+    }
+    if e.is_builder() || e.is_body() || e.is_decode() || e.is_redirect() {
+        return false;
+    }
+    e.is_timeout() || e.is_connect() || e.is_request()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_error(status: u16) -> Result<(), Error> {
+        let res = ::reqwest::Response::from(
+            http::Response::builder()
+                .status(status)
+                .body("")
+                .unwrap(),
+        );
+        res.error_for_status().map(|_| ())
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify::<()>(&Ok(())), Decision::Accept);
+        assert_eq!(classify(&status_error(503)), Decision::Retry);
+        assert_eq!(classify(&status_error(429)), Decision::Retry);
+        assert_eq!(classify(&status_error(404)), Decision::Abort);
+
+        let builder = ::reqwest::Client::new().get("not a url").build();
+        assert_eq!(classify(&builder), Decision::Abort);
+    }
+}
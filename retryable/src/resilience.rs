@@ -0,0 +1,198 @@
+//! Retries, circuit breaking and a fallback in one policy, see [`ResiliencePolicy`]
+use std::error::Error;
+use std::fmt;
+
+use crate::{CircuitBreaker, Decision, RetryBudget, RetryLoop, RetryStrategy};
+
+type Fallback<T, E> = Box<dyn Fn(&ResilienceError<E>) -> Option<T> + Send + Sync>;
+
+/// Retry an operation, stop calling it once its upstream keeps failing, and
+/// fall back to a degraded answer when neither gets a result
+///
+/// The pieces are composed in the order that keeps them from working against
+/// each other:
+/// - The circuit breaker is asked before **each attempt**, retries included,
+///   and told how each one went: retries stop as soon as the circuit opens,
+///   instead of piling onto an upstream that's known to be down, and a call
+///   made while it's open doesn't make any attempt at all.
+/// - The fallback comes last, so it only answers once the retries have given
+///   up (or weren't allowed), never in place of a retry that could succeed.
+///
+/// ```
+/// use std::time::Duration;
+/// use retryable::{CircuitBreaker, ResilienceError, ResiliencePolicy, RetryDelay, RetryStrategy};
+///
+/// let strategy = RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let breaker = CircuitBreaker::new(5, Duration::from_secs(30));
+/// let prices = ResiliencePolicy::new(strategy, breaker)
+///     .with_fallback(|_: &ResilienceError<&str>| Some(vec![("cached", 9.99)]));
+///
+/// let live = prices.execute(|| Ok(vec![("live", 10.49)]));
+/// assert_eq!(live, Ok(vec![("live", 10.49)]));
+/// let degraded = prices.execute(|| Err("price service down"));
+/// assert_eq!(degraded, Ok(vec![("cached", 9.99)]));
+/// ```
+/// Like [`SharedRetryable`](crate::SharedRetryable), a policy is `Send + Sync`,
+/// and meant to be configured once and shared by every caller of the upstream.
+pub struct ResiliencePolicy<T, E> {
+    strategy: RetryStrategy,
+    breaker: CircuitBreaker,
+    budget: Option<RetryBudget>,
+    fallback: Option<Fallback<T, E>>,
+}
+
+/// Why [`ResiliencePolicy::execute`] didn't get a result
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResilienceError<E> {
+    /// The circuit was open, so no attempt was made
+    CircuitOpen,
+    /// The last error of the attempts made
+    Failed(E),
+}
+
+impl<T, E> ResiliencePolicy<T, E> {
+    pub fn new(strategy: RetryStrategy, breaker: CircuitBreaker) -> Self {
+        Self {
+            strategy,
+            breaker,
+            budget: None,
+            fallback: None,
+        }
+    }
+
+    /// Only retry while `budget` has retries left, see
+    /// [`Retryable::with_retry_budget`](crate::Retryable::with_retry_budget)
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Answer with `fallback` (a cached value, a default, ...) when the call
+    /// fails, or `None` to let the error through
+    pub fn with_fallback<F>(mut self, fallback: F) -> Self
+    where
+        F: Fn(&ResilienceError<E>) -> Option<T> + Send + Sync + 'static,
+    {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Run `operation` through the retries, the circuit breaker and the fallback
+    pub fn execute<F>(&self, mut operation: F) -> Result<T, ResilienceError<E>>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        if !self.breaker.allow() {
+            return self.fall_back(ResilienceError::CircuitOpen);
+        }
+        let mut retry =
+            RetryLoop::new(self.strategy.clone()).with_circuit_breaker(self.breaker.clone());
+        if let Some(budget) = &self.budget {
+            retry = retry.with_retry_budget(budget.clone());
+        }
+        let res = loop {
+            let res = operation();
+            match retry.after(Decision::default_for(&res)) {
+                Some(delay) => {
+                    std::thread::sleep(delay);
+                    retry.resume();
+                }
+                None => {
+                    retry.finish(res.is_err());
+                    break res;
+                }
+            }
+        };
+        res.or_else(|e| self.fall_back(ResilienceError::Failed(e)))
+    }
+
+    fn fall_back(&self, error: ResilienceError<E>) -> Result<T, ResilienceError<E>> {
+        match self.fallback.as_ref().and_then(|fallback| fallback(&error)) {
+            Some(value) => Ok(value),
+            None => Err(error),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ResilienceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResilienceError::CircuitOpen => write!(f, "circuit open, call not attempted"),
+            ResilienceError::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ResilienceError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResilienceError::CircuitOpen => None,
+            ResilienceError::Failed(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CircuitState, RetryDelay};
+    use std::time::Duration;
+
+    fn policy(retries: usize, threshold: usize) -> ResiliencePolicy<u32, &'static str> {
+        let strategy = RetryStrategy::new(retries, RetryDelay::Fixed(Duration::from_millis(1)));
+        ResiliencePolicy::new(
+            strategy,
+            CircuitBreaker::new(threshold, Duration::from_secs(60)),
+        )
+    }
+
+    #[test]
+    fn test_retries_stop_when_circuit_opens() {
+        let policy = policy(5, 3);
+        let mut attempts = 0;
+        let res = policy.execute(|| {
+            attempts += 1;
+            Err("down")
+        });
+        assert_eq!(res, Err(ResilienceError::Failed("down")));
+        // Out of 6 attempts allowed, the 3rd opened the circuit
+        assert_eq!(attempts, 3);
+        assert_eq!(policy.breaker().state(), CircuitState::Open);
+
+        let res = policy.execute(|| {
+            attempts += 1;
+            Ok(1)
+        });
+        assert_eq!(res, Err(ResilienceError::CircuitOpen));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_fallback_after_retries() {
+        let policy = policy(2, 10).with_fallback(|error| match error {
+            ResilienceError::Failed("fatal") => None,
+            _ => Some(0),
+        });
+        let mut attempts = 0;
+        let res = policy.execute(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err("flaky")
+            } else {
+                Ok(attempts)
+            }
+        });
+        // Retried to success, without falling back
+        assert_eq!(res, Ok(3));
+        assert_eq!(policy.execute(|| Err("flaky")), Ok(0));
+        assert_eq!(
+            policy.execute(|| Err("fatal")),
+            Err(ResilienceError::Failed("fatal"))
+        );
+        assert_eq!(policy.breaker().state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,47 @@
+//! Runtime support for [`retry_scope!`](crate::retry_scope) and [`retryme!`](crate::retryme)
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    /// How many `retry_scope!` blocks are running on this thread
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Unwinding payload of `retryme!()`, caught by the enclosing scope
+struct RetryMe;
+
+struct Enter;
+
+impl Enter {
+    fn new() -> Self {
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+        Enter
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Run one attempt of a scope's block, `None` if it called `retryme!()`
+#[doc(hidden)]
+pub fn attempt<R>(block: impl FnOnce() -> R) -> Option<R> {
+    let _enter = Enter::new();
+    match panic::catch_unwind(AssertUnwindSafe(block)) {
+        Ok(res) => Some(res),
+        Err(payload) if payload.is::<RetryMe>() => None,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Abandon the current attempt of the enclosing scope
+#[doc(hidden)]
+pub fn retry_now() -> ! {
+    if DEPTH.with(Cell::get) == 0 {
+        panic!("retryme!() used outside of a retry_scope! block");
+    }
+    // Unlike `panic!`, this doesn't run the panic hook (so nothing is printed)
+    panic::resume_unwind(Box::new(RetryMe))
+}
@@ -0,0 +1,309 @@
+//! A retry wrapper shared by many threads, see [`SharedRetryable`]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    CircuitBreaker, ConcurrencyLimit, Decision, RetryBudget, RetryLoop, RetryPolicy, RetryProgress,
+    RetryStrategy,
+};
+
+type SharedDecider<T, E> = Box<dyn Fn(&Result<T, E>) -> Decision + Send + Sync>;
+/// Makes the policy of each call, a clone of the one configured
+type SharedPolicy<T, E> = Box<dyn Fn() -> Box<dyn RetryPolicy<T, E>> + Send + Sync>;
+type SharedHook = Box<dyn Fn(&RetryProgress) + Send + Sync>;
+/// The circuit breaker, and the error to fail with while it's open
+type SharedBreaker<E> = (CircuitBreaker, Box<dyn Fn() -> E + Send + Sync>);
+
+/// A `Send + Sync` retry wrapper, configured once and shared (behind an `Arc`)
+/// by every thread of a pool
+///
+/// Unlike [`Retryable`](crate::Retryable), it doesn't own the operation: each
+/// [`call`](Self::call) runs its own retry loop (with its own [`RetryProgress`],
+/// handed to the [`on_retry`](Self::on_retry) hook), while the configuration,
+/// the concurrency limit, the budget, the circuit breaker and the [`SharedStats`]
+/// counters are shared by all of them.
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use retryable::{RetryDelay, RetryStrategy, SharedRetryable};
+///
+/// let strategy = RetryStrategy::new(2, RetryDelay::Fixed(Duration::from_millis(1)));
+/// let retry = Arc::new(SharedRetryable::<u32, String>::new(strategy));
+/// let workers: Vec<_> = (0..4)
+///     .map(|id| {
+///         let retry = Arc::clone(&retry);
+///         std::thread::spawn(move || retry.call(|| Ok(id)))
+///     })
+///     .collect();
+/// for worker in workers {
+///     assert!(worker.join().unwrap().is_ok());
+/// }
+/// assert_eq!(retry.stats().calls, 4);
+/// ```
+pub struct SharedRetryable<T, E> {
+    strategy: RetryStrategy,
+    decider: Option<SharedDecider<T, E>>,
+    policy: Option<SharedPolicy<T, E>>,
+    on_retry: Option<SharedHook>,
+    limit: Option<ConcurrencyLimit>,
+    budget: Option<RetryBudget>,
+    breaker: Option<SharedBreaker<E>>,
+    calls: AtomicU64,
+    attempts: AtomicU64,
+    give_ups: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Totals across every [`SharedRetryable::call`] so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SharedStats {
+    /// Retry loops run
+    pub calls: u64,
+    /// Total attempts across all loops
+    pub attempts: u64,
+    /// Attempts beyond the first of each loop
+    pub retries: u64,
+    /// Loops that ended with an `Err`
+    pub give_ups: u64,
+    /// Calls failed without an attempt, the circuit being open
+    pub rejected: u64,
+}
+
+impl<T, E> SharedRetryable<T, E> {
+    pub fn new(strategy: RetryStrategy) -> Self {
+        Self {
+            strategy,
+            decider: None,
+            policy: None,
+            on_retry: None,
+            limit: None,
+            budget: None,
+            breaker: None,
+            calls: AtomicU64::new(0),
+            attempts: AtomicU64::new(0),
+            give_ups: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Decide what to do with each result, like [`Retryable::with_decider`](crate::Retryable::with_decider)
+    /// (but callable from several threads at once)
+    pub fn with_decider<D>(mut self, decider: D) -> Self
+    where
+        D: Fn(&Result<T, E>) -> Decision + Send + Sync + 'static,
+    {
+        self.decider = Some(Box::new(decider));
+        self
+    }
+
+    /// Hand every decision to a [`RetryPolicy`], like [`Retryable::with_policy`](crate::Retryable::with_policy)
+    ///
+    /// Each call decides with a clone of `policy`, as it retries on a clone of
+    /// the strategy, so policies keeping count of attempts start over every time.
+    pub fn with_policy<P>(mut self, policy: P) -> Self
+    where
+        P: RetryPolicy<T, E> + Clone + Send + Sync + 'static,
+    {
+        self.policy = Some(Box::new(move || Box::new(policy.clone())));
+        self
+    }
+
+    /// Called before each backoff, with the progress of the call's loop so far
+    pub fn on_retry<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(&RetryProgress) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Box::new(hook));
+        self
+    }
+
+    /// Bound how many attempts run at once across all calls, see
+    /// [`Retryable::with_concurrency_limit`](crate::Retryable::with_concurrency_limit)
+    pub fn with_concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Only retry while `budget` has retries left, see
+    /// [`Retryable::with_retry_budget`](crate::Retryable::with_retry_budget)
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Tell `breaker` how each attempt went, stop retrying once it opens, and
+    /// fail calls made while it's open with `open()` without any attempt
+    ///
+    /// Clones of a breaker share their circuit, so one can also be shared with
+    /// other wrappers calling the same upstream.
+    pub fn with_circuit_breaker<O>(mut self, breaker: CircuitBreaker, open: O) -> Self
+    where
+        O: Fn() -> E + Send + Sync + 'static,
+    {
+        self.breaker = Some((breaker, Box::new(open)));
+        self
+    }
+
+    /// Run `operation` in a retry loop of its own
+    #[track_caller]
+    pub fn call<F>(&self, mut operation: F) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+    {
+        let mut retry = RetryLoop::new(self.strategy.clone());
+        if let Some((breaker, open)) = &self.breaker {
+            if !breaker.allow() {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(open());
+            }
+            retry = retry.with_circuit_breaker(breaker.clone());
+        }
+        if let Some(budget) = &self.budget {
+            retry = retry.with_retry_budget(budget.clone());
+        }
+        let mut policy = self.policy.as_ref().map(|policy| policy());
+        let res = loop {
+            let permit = self.limit.as_ref().map(ConcurrencyLimit::acquire);
+            let res = operation();
+            drop(permit);
+            let next = match (&mut policy, &self.decider) {
+                (Some(policy), _) => retry.after_policy(policy.as_mut(), &res),
+                (None, Some(decider)) => retry.after(decider(&res)),
+                (None, None) => retry.after(Decision::default_for(&res)),
+            };
+            match next {
+                Some(delay) => {
+                    if let Some(hook) = &self.on_retry {
+                        hook(retry.progress());
+                    }
+                    std::thread::sleep(delay);
+                    retry.resume();
+                }
+                None => break res,
+            }
+        };
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.attempts
+            .fetch_add(retry.attempts() as u64, Ordering::Relaxed);
+        if res.is_err() {
+            self.give_ups.fetch_add(1, Ordering::Relaxed);
+        }
+        retry.finish(res.is_err());
+        res
+    }
+
+    pub fn stats(&self) -> SharedStats {
+        let calls = self.calls.load(Ordering::Relaxed);
+        let attempts = self.attempts.load(Ordering::Relaxed);
+        SharedStats {
+            calls,
+            attempts,
+            retries: attempts.saturating_sub(calls),
+            give_ups: self.give_ups.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryDelay;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_shared_across_threads() {
+        fn assert_send_sync<S: Send + Sync>(_: &S) {}
+
+        let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(1)));
+        let retry =
+            SharedRetryable::new(strategy).with_decider(|res: &Result<u32, &str>| match res {
+                Err("fatal") => Decision::Abort,
+                res => Decision::default_for(res),
+            });
+        assert_send_sync(&retry);
+        let retry = Arc::new(retry);
+
+        let workers: Vec<_> = (0..8)
+            .map(|id| {
+                let retry = Arc::clone(&retry);
+                std::thread::spawn(move || {
+                    let mut calls = 0;
+                    retry.call(|| {
+                        calls += 1;
+                        match id {
+                            // Fails once before succeeding
+                            0..=3 if calls == 1 => Err("busy"),
+                            0..=3 => Ok(id),
+                            _ => Err("fatal"),
+                        }
+                    })
+                })
+            })
+            .collect();
+        let results: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(&results[..4], &[Ok(0), Ok(1), Ok(2), Ok(3)]);
+        assert!(results[4..].iter().all(|r| *r == Err("fatal")));
+        assert_eq!(
+            retry.stats(),
+            SharedStats {
+                calls: 8,
+                attempts: 12,
+                retries: 4,
+                give_ups: 4,
+                rejected: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_shared_policy_breaker_and_hook() {
+        use crate::Attempt;
+        use std::sync::atomic::AtomicUsize;
+
+        /// Retries `Err`s right away, up to 2 retries per call
+        #[derive(Clone)]
+        struct TwoRetries;
+
+        impl RetryPolicy<u32, &'static str> for TwoRetries {
+            fn decide(&mut self, attempt: &Attempt, res: &Result<u32, &'static str>) -> Decision {
+                match res {
+                    Err(_) if attempt.number <= 2 => Decision::RetryAfter(Duration::ZERO),
+                    Err(_) => Decision::Abort,
+                    Ok(_) => Decision::Accept,
+                }
+            }
+        }
+
+        static BACKOFFS: AtomicUsize = AtomicUsize::new(0);
+        let breaker = CircuitBreaker::new(5, Duration::from_secs(60));
+        let retry = SharedRetryable::new(RetryStrategy::default())
+            .with_policy(TwoRetries)
+            .on_retry(|progress| {
+                assert!(progress.next_delay().is_some());
+                BACKOFFS.fetch_add(1, Ordering::SeqCst);
+            })
+            .with_circuit_breaker(breaker.clone(), || "circuit open");
+
+        assert_eq!(retry.call(|| Err("down")), Err("down"));
+        assert_eq!(BACKOFFS.load(Ordering::SeqCst), 2);
+        // The circuit opens on the 5th failure in a row, ending the retries
+        let mut attempts = 0;
+        let res = retry.call(|| {
+            attempts += 1;
+            Err("down")
+        });
+        assert_eq!((res, attempts), (Err("down"), 2));
+        assert_eq!(retry.call(|| Ok(1)), Err("circuit open"));
+        assert_eq!(
+            retry.stats(),
+            SharedStats {
+                calls: 2,
+                attempts: 5,
+                retries: 3,
+                give_ups: 2,
+                rejected: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Retry classification for [`sqlx`](::sqlx) database errors
+//!
+//! Deadlocks, serialization failures, busy/locked databases and dropped
+//! connections are retried, anything else (constraint violations, bad queries,
+//! missing rows) fails immediately:
+//! ```ignore
+//! let mut r = AsyncRetryable::new(|| transfer(&pool, from, to, amount), RetryStrategy::default())
+//!     .with_decider(retryable::sqlx::classify);
+//! ```
+use ::sqlx::error::DatabaseError;
+use ::sqlx::Error;
+
+use crate::Decision;
+
+/// Decide whether a `sqlx` result should be retried
+pub fn classify<T>(res: &Result<T, Error>) -> Decision {
+    match res {
+        Ok(_) => Decision::Accept,
+        Err(e) if is_transient(e) => Decision::Retry,
+        Err(_) => Decision::Abort,
+    }
+}
+
+/// Whether running the same query (or transaction) again could succeed
+pub fn is_transient(e: &Error) -> bool {
+    match e {
+        Error::Io(_) | Error::PoolTimedOut | Error::WorkerCrashed => true,
+        Error::Database(e) => is_transient_database(e.as_ref()),
+        _ => false,
+    }
+}
+
+fn is_transient_database(e: &dyn DatabaseError) -> bool {
+    let code = match e.code() {
+        Some(code) => code,
+        None => return false,
+    };
+    // Postgres and MySQL report a SQLSTATE, SQLite its (extended) result code
+    if code.len() == 5 {
+        matches!(
+            &*code,
+            // serialization_failure, deadlock_detected
+            "40001" | "40P01"
+            // admin_shutdown, crash_shutdown, cannot_connect_now
+            | "57P01" | "57P02" | "57P03"
+        ) || code.starts_with("08")
+    } else {
+        // SQLITE_BUSY, SQLITE_LOCKED (and their extended codes)
+        matches!(code.parse::<u32>().map(|c| c & 0xff), Ok(5) | Ok(6))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::sqlx::error::ErrorKind;
+    use std::{borrow::Cow, fmt, io};
+
+    #[derive(Debug)]
+    struct Coded(&'static str);
+
+    impl fmt::Display for Coded {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for Coded {}
+
+    impl DatabaseError for Coded {
+        fn message(&self) -> &str {
+            self.0
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(self.0.into())
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    fn database(code: &'static str) -> Result<(), Error> {
+        Err(Error::Database(Box::new(Coded(code))))
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify::<()>(&Ok(())), Decision::Accept);
+        assert_eq!(classify::<()>(&Err(Error::PoolTimedOut)), Decision::Retry);
+        let dropped = Error::Io(io::ErrorKind::ConnectionReset.into());
+        assert_eq!(classify::<()>(&Err(dropped)), Decision::Retry);
+        assert_eq!(classify::<()>(&Err(Error::RowNotFound)), Decision::Abort);
+
+        assert_eq!(classify(&database("40P01")), Decision::Retry);
+        assert_eq!(classify(&database("40001")), Decision::Retry);
+        assert_eq!(classify(&database("08006")), Decision::Retry);
+        assert_eq!(classify(&database("23505")), Decision::Abort);
+        assert_eq!(classify(&database("5")), Decision::Retry);
+        assert_eq!(classify(&database("517")), Decision::Retry);
+        assert_eq!(classify(&database("19")), Decision::Abort);
+    }
+}
@@ -0,0 +1,135 @@
+//! Retry statistics per call site
+//!
+//! With the `telemetry` feature, every `try_call` records how many attempts it
+//! took against the `file:line:column` it was called from (via `#[track_caller]`),
+//! so the call sites responsible for most of the retry traffic are easy to find:
+//! ```ignore
+//! eprintln!("{}", retryable::telemetry::dump());
+//! ```
+//! ```text
+//! call site                 calls  attempts  retries  give-ups     backoff
+//! src/billing.rs:88:21         40       173      133         9      12.4s
+//! src/users.rs:12:5           510       512        2         0    200.0ms
+//! ```
+//!
+//! Batch jobs can print the same table once they're done, to show how flaky
+//! their dependencies were during the run:
+//! ```ignore
+//! fn main() {
+//!     let _summary = retryable::telemetry::summary_on_exit();
+//!     // ...
+//! }
+//! ```
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::panic::Location;
+use std::sync::Mutex;
+use std::time::Duration;
+
+static SITES: Mutex<BTreeMap<&'static Location<'static>, SiteStats>> = Mutex::new(BTreeMap::new());
+
+/// Aggregated retry activity of a single call site
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SiteStats {
+    /// Retry loops run
+    pub calls: u64,
+    /// Total attempts across all loops
+    pub attempts: u64,
+    /// Attempts beyond the first of each loop
+    pub retries: u64,
+    /// Loops that ended with an `Err`
+    pub give_ups: u64,
+    /// Total time spent waiting between attempts
+    pub backoff: Duration,
+}
+
+pub(crate) fn record(
+    site: &'static Location<'static>,
+    attempts: usize,
+    gave_up: bool,
+    backoff: Duration,
+) {
+    let mut sites = SITES.lock().unwrap_or_else(|e| e.into_inner());
+    let stats = sites.entry(site).or_default();
+    stats.calls += 1;
+    stats.attempts += attempts as u64;
+    stats.retries += attempts.saturating_sub(1) as u64;
+    stats.give_ups += gave_up as u64;
+    stats.backoff += backoff;
+}
+
+/// Every call site seen so far, busiest (most retries) first
+pub fn sites() -> Vec<(&'static Location<'static>, SiteStats)> {
+    let sites = SITES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sites: Vec<_> = sites.iter().map(|(l, s)| (*l, *s)).collect();
+    sites.sort_by_key(|(_, s)| Reverse(s.retries));
+    sites
+}
+
+/// Statistics for a single call site
+pub fn site(location: &Location) -> Option<SiteStats> {
+    sites()
+        .into_iter()
+        .find(|(l, _)| *l == location)
+        .map(|(_, s)| s)
+}
+
+/// Render [`sites()`] as a table
+pub fn dump() -> String {
+    let sites = sites();
+    let names: Vec<_> = sites.iter().map(|(l, _)| l.to_string()).collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(9);
+    let mut out = format!(
+        "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10}\n",
+        "call site",
+        "calls",
+        "attempts",
+        "retries",
+        "give-ups",
+        "backoff",
+        width = width
+    );
+    for (name, (_, stats)) in names.iter().zip(&sites) {
+        let _ = writeln!(
+            out,
+            "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10.1?}",
+            name,
+            stats.calls,
+            stats.attempts,
+            stats.retries,
+            stats.give_ups,
+            stats.backoff,
+            width = width
+        );
+    }
+    out
+}
+
+/// Print the [`dump()`] table to stderr when the returned guard is dropped,
+/// typically at the end of `main`
+///
+/// Nothing is printed if no retry loop ran. Like any destructor, it's skipped
+/// by `std::process::exit`.
+pub fn summary_on_exit() -> SummaryGuard {
+    SummaryGuard { _private: () }
+}
+
+/// Prints the retry summary when dropped, see [`summary_on_exit()`]
+#[must_use = "the summary is printed as soon as the guard is dropped"]
+pub struct SummaryGuard {
+    _private: (),
+}
+
+impl Drop for SummaryGuard {
+    fn drop(&mut self) {
+        if !SITES.lock().unwrap_or_else(|e| e.into_inner()).is_empty() {
+            eprint!("retry summary:\n{}", dump());
+        }
+    }
+}
+
+/// Forget all recorded statistics
+pub fn reset() {
+    SITES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
@@ -0,0 +1,160 @@
+//! One background thread waking every pending [`sleep`](crate::future::sleep)
+//!
+//! Deadlines are kept in a heap, so any number of tasks backing off at once
+//! share the thread, started on the first sleep that has to wait.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::sync::{Condvar, Mutex, MutexGuard, Once};
+use std::task::Waker;
+use std::time::Instant;
+
+static TIMER: Timer = Timer {
+    state: Mutex::new(State {
+        deadlines: BinaryHeap::new(),
+        wakers: BTreeMap::new(),
+        next_id: 0,
+    }),
+    changed: Condvar::new(),
+};
+static STARTED: Once = Once::new();
+
+struct Timer {
+    state: Mutex<State>,
+    /// Signalled when a deadline earlier than the ones waited for may have come in
+    changed: Condvar,
+}
+
+struct State {
+    /// Deadlines by registration; those of cancelled sleeps stay until popped
+    /// (or pruned), without their waker
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: BTreeMap<u64, Waker>,
+    next_id: u64,
+}
+
+impl State {
+    /// Drop the deadlines of cancelled sleeps once they're most of the heap,
+    /// so sleeps that keep being cancelled early don't pile up
+    fn prune(&mut self) {
+        if self.deadlines.len() > 2 * self.wakers.len() + 16 {
+            let wakers = &self.wakers;
+            self.deadlines
+                .retain(|Reverse((_, id))| wakers.contains_key(id));
+        }
+    }
+}
+
+fn lock() -> MutexGuard<'static, State> {
+    TIMER.state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Wake `waker` at `deadline`, returns the id to update or cancel it by
+pub(crate) fn register(deadline: Instant, waker: &Waker) -> u64 {
+    STARTED.call_once(|| {
+        std::thread::Builder::new()
+            .name("retryable-timer".into())
+            .spawn(run)
+            .expect("failed to spawn the retryable timer thread");
+    });
+    let mut state = lock();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.deadlines.push(Reverse((deadline, id)));
+    state.wakers.insert(id, waker.clone());
+    drop(state);
+    TIMER.changed.notify_one();
+    id
+}
+
+/// Wake `waker` instead, the task having moved since it registered
+pub(crate) fn update(id: u64, waker: &Waker) {
+    let mut state = lock();
+    if let Some(registered) = state.wakers.get_mut(&id) {
+        if !registered.will_wake(waker) {
+            *registered = waker.clone();
+        }
+    }
+}
+
+/// Forget a registration, releasing its waker
+pub(crate) fn cancel(id: u64) {
+    let mut state = lock();
+    state.wakers.remove(&id);
+    state.prune();
+}
+
+fn run() {
+    let mut state = lock();
+    loop {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        while let Some(&Reverse((deadline, id))) = state.deadlines.peek() {
+            if deadline > now {
+                break;
+            }
+            state.deadlines.pop();
+            due.extend(state.wakers.remove(&id));
+        }
+        let next = state
+            .deadlines
+            .peek()
+            .map(|Reverse((deadline, _))| *deadline);
+        if !due.is_empty() {
+            // Woken without the lock, as a waker may poll (and register) in place
+            drop(state);
+            due.into_iter().for_each(Waker::wake);
+            state = lock();
+            continue;
+        }
+        state = match next {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(now);
+                let (state, _) = TIMER
+                    .changed
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(|e| e.into_inner());
+                state
+            }
+            None => TIMER.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::time::Duration;
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_cancel_releases_waker() {
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&count));
+        let later = Instant::now() + Duration::from_secs(60);
+        let ids: Vec<_> = (0..100).map(|_| register(later, &waker)).collect();
+        // The test's own `waker` holds one too
+        assert_eq!(Arc::strong_count(&count), 102);
+        ids.into_iter().for_each(cancel);
+        assert_eq!(Arc::strong_count(&count), 2);
+
+        let soon = register(Instant::now() + Duration::from_millis(5), &waker);
+        let start = Instant::now();
+        while count.0.load(Ordering::SeqCst) == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // Already woken, so there's nothing left to cancel
+        cancel(soon);
+        assert_eq!(Arc::strong_count(&count), 2);
+    }
+}
@@ -0,0 +1,229 @@
+//! Retry middleware for [`tower`](https://docs.rs/tower) services
+//!
+//! [`RetryLayer`] applies the same [`RetryStrategy`] (and [`Decide`] hook) used for
+//! plain closures to every request going through a service, so axum or tonic
+//! clients and servers share the retry policies of the rest of the code:
+//! ```ignore
+//! let strategy = RetryStrategy::new(3, RetryDelay::Fixed(Duration::from_millis(100)));
+//! let client = ServiceBuilder::new()
+//!     .layer(RetryLayer::new(strategy).with_decider(|res: &Result<Response, Error>| {
+//!         match res {
+//!             Ok(res) if res.status().is_server_error() => Decision::Retry,
+//!             Ok(_) => Decision::Accept,
+//!             Err(_) => Decision::Retry,
+//!         }
+//!     }))
+//!     .service(http_client);
+//! ```
+//!
+//! Requests must be `Clone`, as each attempt sends its own copy. Backoffs use the
+//! runtime-agnostic [`sleep`](crate::future::sleep).
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::future::sleep;
+use crate::{Decide, Decision, RetryLoop, RetryStrategy};
+
+/// Retries every `Err` and accepts every `Ok`, the default [`Decide`] hook of [`Retry`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryErrors;
+
+impl<T, E> Decide<T, E> for RetryErrors {
+    fn decide(&mut self, res: &Result<T, E>) -> Decision {
+        Decision::default_for(res)
+    }
+}
+
+/// Wraps services in [`Retry`]
+#[derive(Clone, Debug)]
+pub struct RetryLayer<D = RetryErrors> {
+    strategy: RetryStrategy,
+    decider: D,
+}
+
+impl RetryLayer {
+    pub fn new(strategy: RetryStrategy) -> Self {
+        Self {
+            strategy,
+            decider: RetryErrors,
+        }
+    }
+}
+
+impl<D> RetryLayer<D> {
+    /// Decide what to do with each response (including `Ok` ones), instead of
+    /// retrying every `Err`. It's cloned for each request.
+    pub fn with_decider<N>(self, decider: N) -> RetryLayer<N> {
+        RetryLayer {
+            strategy: self.strategy,
+            decider,
+        }
+    }
+}
+
+impl<S, D: Clone> Layer<S> for RetryLayer<D> {
+    type Service = Retry<S, D>;
+
+    fn layer(&self, inner: S) -> Retry<S, D> {
+        Retry {
+            inner,
+            strategy: self.strategy.clone(),
+            decider: self.decider.clone(),
+        }
+    }
+}
+
+/// A service retrying the requests it forwards to `S`, see [`RetryLayer`]
+#[derive(Clone, Debug)]
+pub struct Retry<S, D = RetryErrors> {
+    inner: S,
+    strategy: RetryStrategy,
+    decider: D,
+}
+
+impl<S> Retry<S> {
+    pub fn new(inner: S, strategy: RetryStrategy) -> Self {
+        RetryLayer::new(strategy).layer(inner)
+    }
+}
+
+impl<S, D> Retry<S, D> {
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+type BoxFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
+
+impl<S, D, Req> Service<Req> for Retry<S, D>
+where
+    S: Service<Req> + Clone + Send + 'static,
+    S::Response: Send,
+    S::Error: Send,
+    S::Future: Send,
+    D: Decide<S::Response, S::Error> + Clone + Send + 'static,
+    Req: Clone + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // Keep the service that was just polled ready for the first attempt
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let mut decider = self.decider.clone();
+        let mut retry = RetryLoop::new(self.strategy.clone());
+        Box::pin(async move {
+            let mut res = inner.call(req.clone()).await;
+            while let Some(delay) = retry.after(decider.decide(&res)) {
+                sleep(delay).await;
+                retry.resume();
+                if let Err(e) = poll_fn(|cx| inner.poll_ready(cx)).await {
+                    retry.finish(true);
+                    return Err(e);
+                }
+                res = inner.call(req.clone()).await;
+            }
+            retry.finish(res.is_err());
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RetryDelay;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Fails the first `failures` requests, then echoes them back
+    #[derive(Clone)]
+    struct Flaky {
+        calls: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    impl Service<u32> for Flaky {
+        type Response = u32;
+        type Error = &'static str;
+        type Future = std::future::Ready<Result<u32, &'static str>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: u32) -> Self::Future {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(if call < self.failures {
+                Err("unavailable")
+            } else {
+                Ok(req)
+            })
+        }
+    }
+
+    fn flaky(failures: usize) -> (Flaky, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = Flaky {
+            calls: Arc::clone(&calls),
+            failures,
+        };
+        (service, calls)
+    }
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(fut)
+    }
+
+    fn strategy(retries: usize) -> RetryStrategy {
+        RetryStrategy::new(retries, RetryDelay::Fixed(Duration::from_millis(1)))
+    }
+
+    #[test]
+    fn test_retry_service() {
+        let (service, calls) = flaky(2);
+        let mut retry = Retry::new(service, strategy(3));
+        assert_eq!(block_on(retry.call(7)), Ok(7));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let (service, calls) = flaky(5);
+        let mut retry = RetryLayer::new(strategy(1)).layer(service);
+        assert_eq!(block_on(retry.call(7)), Err("unavailable"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_retry_layer_decider() {
+        let (service, calls) = flaky(0);
+        // Odd responses are "successful but unacceptable"
+        let layer =
+            RetryLayer::new(strategy(3)).with_decider(|res: &Result<u32, &str>| match res {
+                Ok(n) if n % 2 == 1 => Decision::Retry,
+                Ok(_) => Decision::Accept,
+                Err(_) => Decision::Abort,
+            });
+        let mut retry = layer.layer(service);
+        assert_eq!(block_on(retry.call(3)), Ok(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(block_on(retry.call(4)), Ok(4));
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+}
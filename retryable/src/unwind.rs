@@ -0,0 +1,46 @@
+//! Panics escaping a retry loop
+use std::any::Any;
+
+/// The message a panic was raised with, if it was a string
+pub(crate) fn message(payload: &(dyn Any + Send)) -> Option<&str> {
+    match payload.downcast_ref::<&'static str>() {
+        Some(message) => Some(message),
+        None => payload.downcast_ref::<String>().map(String::as_str),
+    }
+}
+
+/// Payload to resume a caught panic with, naming the operation and attempt
+/// it came from: `'sync users' panicked on attempt 3: connection reset`
+pub(crate) fn enrich(
+    payload: Box<dyn Any + Send>,
+    label: &str,
+    attempt: usize,
+) -> Box<dyn Any + Send> {
+    let message = message(payload.as_ref()).unwrap_or("Box<dyn Any>");
+    Box::new(format!(
+        "'{}' panicked on attempt {}: {}",
+        label, attempt, message
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enrich() {
+        let payload = std::panic::catch_unwind(|| panic!("reset after {} bytes", 12)).unwrap_err();
+        let enriched = enrich(payload, "upload", 3);
+        assert_eq!(
+            message(enriched.as_ref()),
+            Some("'upload' panicked on attempt 3: reset after 12 bytes")
+        );
+
+        let payload = std::panic::catch_unwind(|| std::panic::panic_any(7)).unwrap_err();
+        let enriched = enrich(payload, "upload", 1);
+        assert_eq!(
+            message(enriched.as_ref()),
+            Some("'upload' panicked on attempt 1: Box<dyn Any>")
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, Expr, ExprLit, Lit, LitStr};
+
+use duration_grammar::parse_nanos;
+
+/// A `key = "250ms"` option value, as a `Duration` expression
+pub(crate) fn duration_option(value: &Expr) -> syn::Result<TokenStream> {
+    let lit = match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        }) => lit,
+        value => {
+            return Err(Error::new_spanned(
+                value,
+                "expected a duration like \"250ms\"",
+            ))
+        }
+    };
+    let nanos = parse(lit)?;
+    Ok(quote!(::std::time::Duration::from_nanos(#nanos)))
+}
+
+fn parse(lit: &LitStr) -> syn::Result<u64> {
+    let s = lit.value();
+    parse_nanos(&s).ok_or_else(|| {
+        let msg = format!(
+            "invalid duration {:?} (expected e.g. \"250ms\", \"1m30s\")",
+            s
+        );
+        Error::new_spanned(lit, msg)
+    })
+}
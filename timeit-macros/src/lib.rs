@@ -0,0 +1,182 @@
+//! Attribute macros for `timeit`, use them through the re-exports in that crate
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, Block, Error, Expr, ExprLit, ImplItem, Item, ItemFn, ItemImpl, Lit, LitStr,
+    MetaNameValue, ReturnType, Signature, Token,
+};
+
+mod duration;
+
+use duration::duration_option;
+
+fn str_option(option: &MetaNameValue) -> syn::Result<LitStr> {
+    match &option.value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit), ..
+        }) => Ok(lit.clone()),
+        value => Err(Error::new_spanned(value, "expected a string")),
+    }
+}
+
+/// Time every call of a function, like wrapping each call site in `timeit!`
+///
+/// ```ignore
+/// #[timeit::attr::timeit(threshold = "10ms", level = "warn", label = "db::fetch_user")]
+/// fn fetch_user(id: u64) -> Result<User, Error> {
+///     ...
+/// }
+/// ```
+/// > [WARN] 'db::fetch_user' took 12 ms (Ok)
+///
+/// Every option is optional, with the same meaning as the `timeit!` option of the same name:
+/// - `threshold`: only report calls taking at least this long (checked at compile time)
+/// - `level`: `"error"`, `"warn"`, `"info"`, `"debug"` or `"trace"`
+/// - `label`: name in the output, defaults to the function name
+///
+/// On an `impl` block, every method is timed (labeled `Type::method`) with the
+/// same options, so a whole type is instrumented in one place:
+/// ```ignore
+/// #[timeit::attr::timeit(threshold = "1ms")]
+/// impl UserStore {
+///     pub fn fetch(&self, id: u64) -> Option<User> { ... }
+///     pub fn save(&mut self, user: User) { ... }
+/// }
+/// ```
+/// > 'UserStore::fetch' took 3 ms
+///
+/// Methods with a `#[timeit]` attribute of their own keep their own options, and
+/// `const fn` methods are left as they are (timing isn't possible in a const context).
+#[proc_macro_attribute]
+pub fn timeit(args: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as Item);
+    let options = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse(args);
+    let expanded = options
+        .and_then(parse_options)
+        .and_then(|options| match item {
+            Item::Fn(func) => Ok(expand_fn(&options, func)),
+            Item::Impl(imp) => expand_impl(&options, imp),
+            item => Err(Error::new_spanned(
+                item,
+                "#[timeit] can only be used on functions and impl blocks",
+            )),
+        });
+    match expanded {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct Options {
+    label: Option<LitStr>,
+    /// `key = value` options of the `timeit!` macro
+    opts: Vec<proc_macro2::TokenStream>,
+}
+
+fn parse_options(options: Punctuated<MetaNameValue, Token![,]>) -> syn::Result<Options> {
+    let mut label = None;
+    let mut opts = Vec::new();
+    for option in options {
+        if option.path.is_ident("threshold") {
+            let threshold = duration_option(&option.value)?;
+            opts.push(quote!(threshold = #threshold));
+        } else if option.path.is_ident("level") {
+            let lit = str_option(&option)?;
+            let level = match lit.value().as_str() {
+                "error" => quote!(Error),
+                "warn" => quote!(Warn),
+                "info" => quote!(Info),
+                "debug" => quote!(Debug),
+                "trace" => quote!(Trace),
+                _ => {
+                    return Err(Error::new_spanned(
+                        lit,
+                        "expected \"error\", \"warn\", \"info\", \"debug\" or \"trace\"",
+                    ))
+                }
+            };
+            opts.push(quote!(level = ::timeit::Level::#level));
+        } else if option.path.is_ident("label") {
+            label = Some(str_option(&option)?);
+        } else {
+            return Err(Error::new_spanned(
+                &option.path,
+                "unknown option, expected `threshold`, `level` or `label`",
+            ));
+        }
+    }
+    Ok(Options { label, opts })
+}
+
+fn expand_fn(options: &Options, func: ItemFn) -> proc_macro2::TokenStream {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = func;
+    let label = match &options.label {
+        Some(label) => label.clone(),
+        None => LitStr::new(&sig.ident.to_string(), Span::call_site()),
+    };
+    let body = timed_body(options, &label, &sig, &block);
+    quote! {
+        #(#attrs)*
+        #vis #sig #body
+    }
+}
+
+fn expand_impl(options: &Options, mut imp: ItemImpl) -> syn::Result<proc_macro2::TokenStream> {
+    if let Some(label) = &options.label {
+        return Err(Error::new_spanned(
+            label,
+            "`label` can't be used on an impl block, methods are labeled `Type::method`",
+        ));
+    }
+    let self_ty = &imp.self_ty;
+    let ty = quote!(#self_ty).to_string().replace(' ', "");
+    for item in &mut imp.items {
+        if let ImplItem::Fn(method) = item {
+            let has_own = method.attrs.iter().any(|attr| {
+                let path = attr.path();
+                path.segments.last().is_some_and(|s| s.ident == "timeit")
+            });
+            if has_own || method.sig.constness.is_some() {
+                continue;
+            }
+            let label = LitStr::new(&format!("{}::{}", ty, method.sig.ident), Span::call_site());
+            let body = timed_body(options, &label, &method.sig, &method.block);
+            method.block = syn::parse2(body)?;
+        }
+    }
+    Ok(quote!(#imp))
+}
+
+/// A function body timing the original one
+fn timed_body(
+    options: &Options,
+    label: &LitStr,
+    sig: &Signature,
+    block: &Block,
+) -> proc_macro2::TokenStream {
+    let output = match &sig.output {
+        ReturnType::Type(_, ty) => quote!(#ty),
+        ReturnType::Default => quote!(()),
+    };
+    // `return` in the body has to end the timed call, not skip the report
+    let call = match sig.asyncness {
+        Some(_) => quote!((async { let _res: #output = #block; _res }).await),
+        None => quote!((|| -> #output { #block })()),
+    };
+    let opts = &options.opts;
+    quote! {{
+        ::timeit::timeit!(
+            @run ::timeit::__private::Label::Function(#label),
+            [#(#opts);*],
+            #call
+        )
+    }}
+}
@@ -0,0 +1,214 @@
+//! Statistics for the repeated-measurement (`iterations = N`) mode
+use std::fmt;
+use std::time::Duration;
+
+/// Fewest samples `iterations = auto` will stop at
+const MIN_AUTO_SAMPLES: usize = 10;
+
+/// Modified z-score above which a sample is an outlier (Iglewicz & Hoaglin)
+const OUTLIER_Z: f64 = 3.5;
+
+/// Aggregate of all measured (non-warmup) runs
+///
+/// Outliers are detected with the median absolute deviation (MAD) and left out
+/// of `min`/`max`/`mean`/`stddev`, since a single stall would otherwise skew the mean.
+/// The percentiles keep them: the stalls are the tail they're there to show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Summary {
+    pub runs: usize,
+    /// Unmeasured runs made before the measured ones
+    pub warmup: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    /// Sample standard deviation, zero for a single run
+    pub stddev: Duration,
+    pub median: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub outliers: usize,
+    /// The outlier furthest from the median
+    pub worst_outlier: Option<Duration>,
+}
+
+impl Summary {
+    /// `None` if there were no samples
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let median = median_of(&sorted)?;
+
+        let mut deviations: Vec<_> = sorted.iter().map(|s| s.abs_diff(median)).collect();
+        deviations.sort();
+        let mad = median_of(&deviations)?;
+
+        // With a MAD of zero (most samples identical) every other sample would be
+        // infinitely far off, so skip outlier detection entirely
+        let is_outlier = |s: &Duration| {
+            mad > Duration::from_secs(0)
+                && 0.6745 * s.abs_diff(median).as_secs_f64() / mad.as_secs_f64() > OUTLIER_Z
+        };
+        let (outliers, inliers): (Vec<Duration>, Vec<Duration>) =
+            sorted.iter().partition(|s| is_outlier(s));
+        let mean = mean(inliers.iter().sum(), inliers.len() as u64);
+        Some(Self {
+            runs: samples.len(),
+            warmup: 0,
+            min: *inliers.first()?,
+            max: *inliers.last()?,
+            mean,
+            stddev: stddev(&inliers, mean),
+            median,
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            p99: percentile(&sorted, 99),
+            outliers: outliers.len(),
+            worst_outlier: outliers.into_iter().max_by_key(|s| s.abs_diff(median)),
+        })
+    }
+}
+
+/// Whether the 95% confidence interval of the mean is within `target_error`
+/// (relative to the mean) of it
+pub(crate) fn is_confident(samples: &[Duration], target_error: f64) -> bool {
+    if samples.len() < MIN_AUTO_SAMPLES {
+        return false;
+    }
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(Duration::as_secs_f64).sum::<f64>() / n;
+    let variance = samples
+        .iter()
+        .map(|s| (s.as_secs_f64() - mean).powi(2))
+        .sum::<f64>()
+        / (n - 1.0);
+    let half_width = 1.96 * variance.sqrt() / n.sqrt();
+    half_width <= mean * target_error
+}
+
+/// `total` spread over `count` runs (or `total` for none), without truncating
+/// `count` to a `u32` as `Duration`'s division does
+pub(crate) fn mean(total: Duration, count: u64) -> Duration {
+    Duration::from_nanos((total.as_nanos() / u128::from(count.max(1))) as u64)
+}
+
+fn stddev(samples: &[Duration], mean: Duration) -> Duration {
+    if samples.len() < 2 {
+        return Duration::from_secs(0);
+    }
+    let mean = mean.as_secs_f64();
+    let variance = samples
+        .iter()
+        .map(|s| (s.as_secs_f64() - mean).powi(2))
+        .sum::<f64>()
+        / (samples.len() - 1) as f64;
+    Duration::from_secs_f64(variance.sqrt())
+}
+
+/// The nearest-rank percentile of at least one sorted sample: the smallest
+/// one that `p`% of them are at most
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
+
+fn median_of(sorted: &[Duration]) -> Option<Duration> {
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((sorted[mid - 1] + sorted[mid]) / 2),
+        _ => Some(sorted[mid]),
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mean {:?} over {} runs", self.mean, self.runs)?;
+        if self.warmup > 0 {
+            write!(f, " after {} warmup", self.warmup)?;
+        }
+        write!(
+            f,
+            " (min {:?}, max {:?}, stddev {:.2?}), p50 {:?}, p90 {:?}, p99 {:?}",
+            self.min, self.max, self.stddev, self.p50, self.p90, self.p99
+        )?;
+        if let Some(worst) = self.worst_outlier {
+            write!(
+                f,
+                ", {} outlier{} up to {:.1}x median",
+                self.outliers,
+                if self.outliers == 1 { "" } else { "s" },
+                worst.as_secs_f64() / self.median.as_secs_f64()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let samples: Vec<_> = [4, 1, 7].iter().map(|ms| Duration::from_millis(*ms)).collect();
+        let summary = Summary::from_samples(&samples).unwrap();
+        assert_eq!(summary.runs, 3);
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.max, Duration::from_millis(7));
+        assert_eq!(summary.mean, Duration::from_millis(4));
+        assert_eq!(summary.median, Duration::from_millis(4));
+        assert!(summary.stddev.abs_diff(Duration::from_millis(3)) < Duration::from_nanos(10));
+        assert_eq!(summary.outliers, 0);
+        assert_eq!(summary.p50, Duration::from_millis(4));
+        assert_eq!(summary.p99, Duration::from_millis(7));
+        assert!(Summary::from_samples(&[]).is_none());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let samples: Vec<_> = (1..=100).rev().map(Duration::from_millis).collect();
+        let summary = Summary::from_samples(&samples).unwrap();
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        let one = Summary::from_samples(&samples[..1]).unwrap();
+        assert_eq!((one.p50, one.p99), (samples[0], samples[0]));
+    }
+
+    #[test]
+    fn test_is_confident() {
+        let steady = vec![Duration::from_millis(10); 10];
+        assert!(is_confident(&steady, 0.01));
+        assert!(!is_confident(&steady[..5], 0.01));
+
+        let noisy: Vec<_> = (0..10).map(|i| Duration::from_millis(1 + i * 20)).collect();
+        assert!(!is_confident(&noisy, 0.05));
+        assert!(is_confident(&noisy, 1.0));
+    }
+
+    #[test]
+    fn test_summary_outliers() {
+        let samples: Vec<_> = [10, 11, 9, 10, 12, 180, 10, 11, 9, 90]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        let summary = Summary::from_samples(&samples).unwrap();
+        assert_eq!(summary.runs, 10);
+        assert_eq!(summary.outliers, 2);
+        assert_eq!(summary.worst_outlier, Some(Duration::from_millis(180)));
+        assert_eq!(summary.max, Duration::from_millis(12));
+        assert_eq!(summary.mean, Duration::from_micros(10_250));
+        assert_eq!(
+            summary.to_string(),
+            "mean 10.25ms over 10 runs (min 9ms, max 12ms, stddev 1.04ms), p50 10ms, p90 90ms, p99 180ms, 2 outliers up to 17.1x median"
+        );
+
+        let warmed_up = Summary {
+            warmup: 3,
+            ..Summary::from_samples(&samples[..5]).unwrap()
+        };
+        let shown = warmed_up.to_string();
+        assert!(shown.starts_with("mean 10.4ms over 5 runs after 3 warmup (min 9ms"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Where measurements read the time from, so timing-dependent code can be
+//! tested without sleeping
+//!
+//! Measurements read the [`SystemClock`] unless another [`Clock`] is set for
+//! their thread with [`set_thread_clock`], usually a [`FakeClock`] that a test
+//! moves forward by hand:
+//! ```
+//! use std::time::Duration;
+//! use timeit::clock::{self, FakeClock};
+//! use timeit::timed;
+//!
+//! fn fetch_user(clock: &FakeClock, id: u32) -> u32 {
+//!     // Stands in for the 2 seconds a slow upstream would take
+//!     clock.advance(Duration::from_secs(2));
+//!     id
+//! }
+//!
+//! let clock = FakeClock::new();
+//! let _guard = clock::set_thread_clock(clock.clone());
+//! let (user, took) = timed!(fetch_user(&clock, 42));
+//! assert_eq!((user, took), (42, Duration::from_secs(2)));
+//! ```
+//! The clock is per thread, so tests running in parallel each keep their
+//! own. Measurements that span threads (the `http` layer's, when a request
+//! moves between the threads of its runtime) read whichever clock the thread
+//! they end on has.
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static CLOCK: RefCell<Option<Box<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// A source of the current time
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic clock of the OS, read with [`Instant::now`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// Clones share the same time, so a test can keep one to [`advance`](Self::advance)
+/// and hand the other to [`set_thread_clock`].
+#[derive(Clone, Debug)]
+pub struct FakeClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl FakeClock {
+    /// A clock stopped at the current time
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Read the time from `clock` on this thread, until the returned guard is dropped
+pub fn set_thread_clock(clock: impl Clock + 'static) -> ClockGuard {
+    let previous = CLOCK.with(|current| current.replace(Some(Box::new(clock))));
+    ClockGuard { previous }
+}
+
+/// The current time on this thread's clock
+pub fn now() -> Instant {
+    CLOCK.with(|clock| match &*clock.borrow() {
+        Some(clock) => clock.now(),
+        None => Instant::now(),
+    })
+}
+
+/// The time passed on this thread's clock since `start`, like [`Instant::elapsed`]
+pub fn elapsed(start: Instant) -> Duration {
+    now().saturating_duration_since(start)
+}
+
+/// Puts back the clock the thread had before when dropped
+#[must_use = "the clock is put back as soon as the guard is dropped"]
+pub struct ClockGuard {
+    previous: Option<Box<dyn Clock>>,
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CLOCK.with(|clock| *clock.borrow_mut() = previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_clock() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        {
+            let _guard = set_thread_clock(clock.clone());
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(elapsed(start), Duration::from_secs(60));
+            assert_eq!(now(), clock.now());
+
+            // Guards nest
+            let later = FakeClock::new();
+            later.advance(Duration::from_secs(120));
+            let inner = set_thread_clock(later.clone());
+            assert_eq!(now(), later.now());
+            drop(inner);
+            assert_eq!(now(), clock.now());
+
+            // Other threads keep the system clock
+            let other = std::thread::spawn(now).join().unwrap();
+            assert!(other < clock.now());
+        }
+        assert!(now() < clock.now());
+    }
+}
@@ -0,0 +1,114 @@
+//! The CPU time of the current thread, see [`Options::clock`](crate::Options::clock)
+use std::ops::Add;
+use std::time::Duration;
+
+use crate::bench;
+
+/// CPU time a thread used, running its own code (`user`) and in the kernel on
+/// its behalf (`system`: syscalls, page faults)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct CpuTime {
+    pub(crate) user: Duration,
+    pub(crate) system: Duration,
+}
+
+impl CpuTime {
+    /// The CPU time used since `start`
+    pub(crate) fn since(self, start: CpuTime) -> Self {
+        Self {
+            user: self.user.saturating_sub(start.user),
+            system: self.system.saturating_sub(start.system),
+        }
+    }
+
+    /// The mean of `runs` runs that used this CPU time together
+    pub(crate) fn per_run(self, runs: u64) -> Self {
+        Self {
+            user: bench::mean(self.user, runs),
+            system: bench::mean(self.system, runs),
+        }
+    }
+}
+
+impl Add for CpuTime {
+    type Output = CpuTime;
+
+    fn add(self, other: CpuTime) -> CpuTime {
+        CpuTime {
+            user: self.user + other.user,
+            system: self.system + other.system,
+        }
+    }
+}
+
+/// CPU time the current thread has used so far, `None` where the platform
+/// can't tell it apart from other threads' or without `cpu-time`
+#[cfg(all(
+    feature = "cpu-time",
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")
+))]
+pub(crate) fn thread_time() -> Option<CpuTime> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: `usage` is valid to write a rusage to, and only read once written
+    let usage = unsafe {
+        if libc::getrusage(libc::RUSAGE_THREAD, usage.as_mut_ptr()) != 0 {
+            return None;
+        }
+        usage.assume_init()
+    };
+    let duration = |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+    Some(CpuTime {
+        user: duration(usage.ru_utime),
+        system: duration(usage.ru_stime),
+    })
+}
+
+#[cfg(not(all(
+    feature = "cpu-time",
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")
+)))]
+pub(crate) fn thread_time() -> Option<CpuTime> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_run() {
+        let total = CpuTime {
+            user: Duration::from_secs(10_000),
+            system: Duration::from_millis(3),
+        };
+        // More runs than fit in a `u32`
+        let mean = total.per_run(5_000_000_000);
+        assert_eq!(mean.user, Duration::from_micros(2));
+        assert_eq!(mean.system, Duration::from_nanos(0));
+        assert_eq!(total.since(total + total), CpuTime::default());
+    }
+
+    #[cfg(all(
+        feature = "cpu-time",
+        any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")
+    ))]
+    #[test]
+    fn test_thread_time() {
+        use std::time::Instant;
+
+        let before = thread_time().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let slept = thread_time().unwrap().since(before);
+
+        let spin = Instant::now();
+        let mut n = 0u64;
+        while spin.elapsed() < Duration::from_millis(20) {
+            n = std::hint::black_box(n + 1);
+        }
+        let spun = thread_time().unwrap().since(before);
+        // Sleeping takes next to no CPU time, spinning takes about all of it,
+        // and mostly in user mode
+        assert!(slept.user + slept.system < Duration::from_millis(10));
+        assert!(spun.user >= Duration::from_millis(10), "{:?}", spun);
+    }
+}
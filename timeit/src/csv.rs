@@ -0,0 +1,163 @@
+//! Measurements appended to a CSV file, see [`CsvReporter`]
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::Reporter;
+
+/// Appends a `label,elapsed_us,timestamp` row per measurement to a CSV file,
+/// for post-processing in pandas and the like rather than parsing log lines
+/// ```
+/// use timeit::{timeit, CsvReporter};
+///
+/// # let path = std::env::temp_dir().join(format!("timeit-doc-{}.csv", std::process::id()));
+/// timeit::set_reporter(CsvReporter::new(&path));
+/// timeit!(|| 42);
+/// # timeit::set_reporter(timeit::Stderr);
+/// # let _ = std::fs::remove_file(&path);
+/// ```
+/// ```python
+/// timings = pandas.read_csv("timings.csv")
+/// timings["timestamp"] = pandas.to_datetime(timings["timestamp"], unit="ms")
+/// ```
+///
+/// `timestamp` is when the measurement was reported, in milliseconds since the
+/// Unix epoch, and the label is empty for anonymous expressions. The file is
+/// created on the first measurement (with the header row, unless it already
+/// has rows to append to). Each row is written out as it's reported, so none
+/// are lost when the process exits with the reporter still set: one set with
+/// [`set_reporter`](crate::set_reporter) is never dropped. Errors are kept
+/// for [`flush`](Self::flush) to return.
+#[derive(Debug)]
+pub struct CsvReporter {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    file: Option<BufWriter<File>>,
+    /// The first failure to open or write to the file, kept for [`CsvReporter::flush`]
+    error: Option<io::Error>,
+}
+
+impl CsvReporter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Make sure the rows so far are written out
+    ///
+    /// Fails with the first error met since the last flush: rows that couldn't
+    /// be written are lost.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(error) = state.error.take() {
+            return Err(error);
+        }
+        match state.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn open(&self) -> io::Result<BufWriter<File>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let mut file = BufWriter::new(file);
+        if is_new {
+            writeln!(file, "label,elapsed_us,timestamp")?;
+        }
+        Ok(file)
+    }
+}
+
+impl Reporter for CsvReporter {
+    fn report(&self, label: &str, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.file.is_none() {
+            match self.open() {
+                Ok(file) => state.file = Some(file),
+                Err(error) => {
+                    state.error.get_or_insert(error);
+                    return;
+                }
+            }
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let row = match state.file.as_mut() {
+            // Flushed right away, for a single write per row
+            Some(file) => writeln!(
+                file,
+                "{},{},{}",
+                Field(label),
+                elapsed.as_micros(),
+                timestamp.as_millis()
+            )
+            .and_then(|()| file.flush()),
+            None => Ok(()),
+        };
+        if let Err(error) = row {
+            state.error.get_or_insert(error);
+        }
+    }
+}
+
+/// A field, quoted if it has to be
+struct Field<'a>(&'a str);
+
+impl fmt::Display for Field<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.contains([',', '"', '\n', '\r']) {
+            write!(f, "\"{}\"", self.0.replace('"', "\"\""))
+        } else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_reporter() {
+        let path = std::env::temp_dir().join(format!("timeit-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let reporter = CsvReporter::new(&path);
+        assert!(!path.exists());
+        reporter.report("load, \"fast\" path", Duration::from_micros(1_250));
+        drop(reporter);
+        // Appended to, without a second header
+        let reporter: &'static CsvReporter = Box::leak(Box::new(CsvReporter::new(&path)));
+        crate::timeit!(|| 42; reporter = reporter; quiet = true);
+
+        // Written without a flush, as a reporter that's set never is
+        let csv = std::fs::read_to_string(&path).unwrap();
+        reporter.flush().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rows: Vec<Vec<&str>> = csv.lines().map(|l| l.rsplitn(3, ',').collect()).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], ["timestamp", "elapsed_us", "label"]);
+        assert_eq!(rows[1][1..], ["1250", "\"load, \"\"fast\"\" path\""]);
+        assert_eq!(rows[2][2], "");
+        assert!(rows[1..].iter().all(|row| row[0].parse::<u64>().is_ok()));
+
+        let unwritable = CsvReporter::new(path.join("not a directory.csv"));
+        unwritable.report("lost", Duration::from_micros(1));
+        assert!(unwritable.flush().is_err());
+        assert!(unwritable.flush().is_ok());
+    }
+}
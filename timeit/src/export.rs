@@ -0,0 +1,184 @@
+//! Dump the registry's raw samples for offline analysis
+//!
+//! Each sample becomes a row of `label` (utf8), `outcome` (utf8, null unless the
+//! expression returned a `Result`) and `nanos` (u64), ready to be loaded into
+//! DataFusion, Polars, pandas, etc.
+//! ```ignore
+//! timeit::export::write_ipc("timings.arrow")?;
+//! ```
+//! ```python
+//! polars.read_ipc("timings.arrow").group_by("label").agg(polars.col("nanos").median())
+//! ```
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{StringBuilder, UInt64Builder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+
+use crate::registry;
+
+/// Failure to write an export file
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Arrow(ArrowError),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "writing export file: {}", e),
+            ExportError::Arrow(e) => write!(f, "encoding samples: {}", e),
+            #[cfg(feature = "parquet")]
+            ExportError::Parquet(e) => write!(f, "encoding samples: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(e) => Some(e),
+            ExportError::Arrow(e) => Some(e),
+            #[cfg(feature = "parquet")]
+            ExportError::Parquet(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<ArrowError> for ExportError {
+    fn from(e: ArrowError) -> Self {
+        ExportError::Arrow(e)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for ExportError {
+    fn from(e: parquet::errors::ParquetError) -> Self {
+        ExportError::Parquet(e)
+    }
+}
+
+/// Schema of the exported samples
+pub fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("label", DataType::Utf8, false),
+        Field::new("outcome", DataType::Utf8, true),
+        Field::new("nanos", DataType::UInt64, false),
+    ]))
+}
+
+/// Every sample recorded so far, as a single batch
+pub fn record_batch() -> Result<RecordBatch, ArrowError> {
+    let mut labels = StringBuilder::new();
+    let mut outcomes = StringBuilder::new();
+    let mut nanos = UInt64Builder::new();
+    registry::for_each_sample(|label, outcome, elapsed| {
+        labels.append_value(label);
+        outcomes.append_option(outcome.map(|o| o.to_string()));
+        nanos.append_value(elapsed.as_nanos() as u64);
+    });
+    RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(labels.finish()),
+            Arc::new(outcomes.finish()),
+            Arc::new(nanos.finish()),
+        ],
+    )
+}
+
+/// Write every sample recorded so far to an Arrow IPC (Feather v2) file
+pub fn write_ipc(path: impl AsRef<Path>) -> Result<(), ExportError> {
+    let batch = record_batch()?;
+    let mut writer = FileWriter::try_new(File::create(path)?, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Write every sample recorded so far to a Parquet file
+#[cfg(feature = "parquet")]
+pub fn write_parquet(path: impl AsRef<Path>) -> Result<(), ExportError> {
+    let batch = record_batch()?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use super::*;
+    use crate::timeit;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::UInt64Type;
+
+    fn exported() -> Result<u32, ()> {
+        Ok(1)
+    }
+
+    /// Rows of the `exported` label, other tests record into the registry concurrently
+    fn rows(batch: &RecordBatch) -> Vec<(Option<String>, u64)> {
+        let labels = batch.column(0).as_string::<i32>();
+        let outcomes = batch.column(1).as_string::<i32>();
+        let nanos = batch.column(2).as_primitive::<UInt64Type>();
+        (0..batch.num_rows())
+            .filter(|&i| labels.value(i) == "exported")
+            .map(|i| {
+                let outcome = Some(outcomes.value(i).to_owned()).filter(|_| outcomes.is_valid(i));
+                (outcome, nanos.value(i))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_export_ipc() {
+        let _ = timeit!(exported());
+        let _ = timeit!(exported());
+
+        let path = std::env::temp_dir().join(format!("timeit-{}.arrow", std::process::id()));
+        write_ipc(&path).unwrap();
+        let reader = arrow::ipc::reader::FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches[0].schema(), schema());
+        let rows: Vec<_> = batches.iter().flat_map(rows).collect();
+        assert!(rows.len() >= 2);
+        assert!(rows.iter().all(|(outcome, _)| outcome.as_deref() == Some("Ok")));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_export_parquet() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let _ = timeit!(exported());
+
+        let path = std::env::temp_dir().join(format!("timeit-{}.parquet", std::process::id()));
+        write_parquet(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(|b| b.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!batches.iter().flat_map(rows).collect::<Vec<_>>().is_empty());
+    }
+}
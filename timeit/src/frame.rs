@@ -0,0 +1,292 @@
+//! Per-frame profiling for game and other fixed-tick loops
+//!
+//! Instead of timing single expressions, a [`FrameTimer`] splits every frame
+//! into named sections, keeps a rolling average of each one across frames, and
+//! reports the frames (and sections) that blew their time budget:
+//! ```
+//! use timeit::frame::FrameTimer;
+//!
+//! let mut frames = FrameTimer::new().budget_share("physics", 0.25);
+//! for _ in 0..3 {
+//!     frames.begin_frame();
+//!     {
+//!         let _physics = frames.section("physics");
+//!         // step the simulation
+//!     }
+//!     {
+//!         let _render = frames.section("render");
+//!         // draw
+//!     }
+//!     let frame = frames.end_frame();
+//!     assert!(!frame.over_budget());
+//! }
+//! ```
+//! > frame 212 took 21.4 ms, over its 16.7 ms budget
+//! >   'physics' 12.1 ms (avg 4.0 ms), over its 4.2 ms share
+//! >   'render' 9.3 ms (avg 9.1 ms)
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::bench;
+use crate::clock;
+use crate::options;
+
+/// A frame at 60 fps
+pub const BUDGET_60FPS: Duration = Duration::from_nanos(16_666_667);
+
+/// Frames averaged over by default
+const WINDOW: usize = 60;
+
+/// Splits frames into timed sections, see the [module docs](self)
+#[derive(Debug)]
+pub struct FrameTimer {
+    budget: Duration,
+    window: usize,
+    frame: u64,
+    start: Instant,
+    sections: BTreeMap<&'static str, Section>,
+}
+
+#[derive(Debug)]
+struct Section {
+    /// Fraction of the frame budget this section may use
+    share: f64,
+    /// Time spent in the current frame, if the section ran
+    current: Option<Duration>,
+    /// The last `window` frames the section ran in
+    history: VecDeque<Duration>,
+}
+
+impl Section {
+    fn new(share: f64) -> Self {
+        Self {
+            share,
+            current: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn average(&self) -> Option<Duration> {
+        if self.history.is_empty() {
+            return None;
+        }
+        let total = self.history.iter().sum();
+        Some(bench::mean(total, self.history.len() as u64))
+    }
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameTimer {
+    /// Frames of [`BUDGET_60FPS`], averaged over the last 60 frames
+    pub fn new() -> Self {
+        Self::with_budget(BUDGET_60FPS)
+    }
+
+    pub fn with_budget(budget: Duration) -> Self {
+        Self {
+            budget,
+            window: WINDOW,
+            frame: 0,
+            start: clock::now(),
+            sections: BTreeMap::new(),
+        }
+    }
+
+    /// Average sections over this many of their most recent frames
+    pub fn window(mut self, frames: usize) -> Self {
+        self.window = frames.max(1);
+        self
+    }
+
+    /// Report `name` whenever it takes more than `share` (0.0 - 1.0) of the
+    /// frame budget. Sections without a share only count against the whole frame.
+    pub fn budget_share(mut self, name: &'static str, share: f64) -> Self {
+        self.sections
+            .entry(name)
+            .or_insert_with(|| Section::new(1.0))
+            .share = share;
+        self
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+        for section in self.sections.values_mut() {
+            section.current = None;
+        }
+        self.start = clock::now();
+    }
+
+    /// Time a section of the current frame until the guard is dropped. Running
+    /// the same section more than once in a frame adds up.
+    pub fn section(&mut self, name: &'static str) -> SectionTimer<'_> {
+        SectionTimer {
+            section: self.sections.entry(name).or_insert_with(|| Section::new(1.0)),
+            start: clock::now(),
+        }
+    }
+
+    /// Finish the frame, printing a report of it if it (or any of its
+    /// sections) went over budget
+    pub fn end_frame(&mut self) -> FrameReport {
+        let elapsed = clock::elapsed(self.start);
+        let budget = self.budget;
+        let window = self.window;
+        let sections = self
+            .sections
+            .iter_mut()
+            .filter_map(|(name, section)| {
+                let elapsed = section.current?;
+                if section.history.len() == window {
+                    section.history.pop_front();
+                }
+                section.history.push_back(elapsed);
+                Some(SectionReport {
+                    name,
+                    elapsed,
+                    average: section.average()?,
+                    budget: budget.mul_f64(section.share),
+                })
+            })
+            .collect();
+        let report = FrameReport {
+            frame: self.frame,
+            elapsed,
+            budget,
+            sections,
+        };
+        if report.over_budget() && !options::is_quiet() {
+            eprint!("{}", report);
+        }
+        report
+    }
+
+    /// Rolling average of a section, over the frames it ran in
+    pub fn average(&self, name: &str) -> Option<Duration> {
+        self.sections.get(name)?.average()
+    }
+}
+
+/// Times a section of a frame, see [`FrameTimer::section`]
+#[must_use = "the section ends as soon as the timer is dropped"]
+pub struct SectionTimer<'a> {
+    section: &'a mut Section,
+    start: Instant,
+}
+
+impl Drop for SectionTimer<'_> {
+    fn drop(&mut self) {
+        let elapsed = clock::elapsed(self.start);
+        *self.section.current.get_or_insert(Duration::from_secs(0)) += elapsed;
+    }
+}
+
+/// A finished frame, returned by [`FrameTimer::end_frame`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameReport {
+    /// Frame number, starting at 1
+    pub frame: u64,
+    pub elapsed: Duration,
+    pub budget: Duration,
+    /// The sections that ran this frame, by name
+    pub sections: Vec<SectionReport>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionReport {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    /// Rolling average, including this frame
+    pub average: Duration,
+    /// This section's share of the frame budget
+    pub budget: Duration,
+}
+
+impl SectionReport {
+    pub fn over_budget(&self) -> bool {
+        self.elapsed > self.budget
+    }
+}
+
+impl FrameReport {
+    /// The frame, or any of its sections, took longer than it should have
+    pub fn over_budget(&self) -> bool {
+        self.elapsed > self.budget || self.sections.iter().any(SectionReport::over_budget)
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for FrameReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frame {} took {:.1} ms", self.frame, ms(self.elapsed))?;
+        if self.elapsed > self.budget {
+            write!(f, ", over its {:.1} ms budget", ms(self.budget))?;
+        }
+        writeln!(f)?;
+        for section in &self.sections {
+            write!(
+                f,
+                "  '{}' {:.1} ms (avg {:.1} ms)",
+                section.name,
+                ms(section.elapsed),
+                ms(section.average)
+            )?;
+            if section.over_budget() {
+                write!(f, ", over its {:.1} ms share", ms(section.budget))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+
+    #[test]
+    fn test_frames() {
+        let clock = FakeClock::new();
+        let _guard = clock::set_thread_clock(clock.clone());
+        let mut frames = FrameTimer::with_budget(Duration::from_millis(20))
+            .window(2)
+            .budget_share("physics", 0.25);
+        for physics_ms in &[1, 2, 9] {
+            frames.begin_frame();
+            {
+                let _physics = frames.section("physics");
+                clock.advance(Duration::from_millis(*physics_ms));
+            }
+            for _ in 0..2 {
+                let _render = frames.section("render");
+                clock.advance(Duration::from_millis(1));
+            }
+            let frame = frames.end_frame();
+            assert_eq!(frame.sections.len(), 2);
+            assert_eq!(frame.sections[1].name, "render");
+            assert_eq!(frame.sections[1].elapsed, Duration::from_millis(2));
+            assert_eq!(frame.over_budget(), *physics_ms == 9);
+        }
+
+        // Only the last two frames are averaged
+        let physics = frames.average("physics").unwrap();
+        assert_eq!(physics, Duration::from_micros(5500));
+
+        // Sections that didn't run aren't reported (or averaged)
+        frames.begin_frame();
+        let frame = frames.end_frame();
+        assert!(frame.sections.is_empty());
+        assert_eq!(frame.frame, 4);
+        assert_eq!(frames.average("physics"), Some(physics));
+        assert_eq!(frames.average("audio"), None);
+    }
+}
@@ -0,0 +1,55 @@
+//! Thread-local group labels, see [`timeit_group!`](crate::timeit_group)
+use std::cell::RefCell;
+
+thread_local! {
+    static GROUPS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Nest the following measurements on this thread under `name`, until the
+/// returned guard is dropped
+pub fn enter(name: impl Into<String>) -> GroupGuard {
+    GROUPS.with(|groups| groups.borrow_mut().push(name.into()));
+    GroupGuard { _private: () }
+}
+
+/// The current group path, like `request 1234 / parse`
+pub fn current() -> Option<String> {
+    GROUPS.with(|groups| {
+        let groups = groups.borrow();
+        if groups.is_empty() {
+            None
+        } else {
+            Some(groups.join(" / "))
+        }
+    })
+}
+
+/// Leaves the group when dropped
+#[must_use = "the group is left as soon as the guard is dropped"]
+pub struct GroupGuard {
+    _private: (),
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        GROUPS.with(|groups| groups.borrow_mut().pop());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting() {
+        assert_eq!(current(), None);
+        let outer = enter("request 1234");
+        {
+            let _inner = enter("auth");
+            assert_eq!(current().as_deref(), Some("request 1234 / auth"));
+        }
+        assert_eq!(current().as_deref(), Some("request 1234"));
+        drop(outer);
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! Registry stats kept between runs, see [`save`](crate::registry::save)
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::registry::{self, Stats};
+use crate::Outcome;
+
+const HEADER: &str =
+    "# timeit history v1: saved_at\tlabel\toutcome\tcount\ttotal_ns\tmin_ns\tmax_ns";
+
+/// Append the stats recorded by this process to the history file at `path`,
+/// stamped with the current time
+///
+/// Meant for CLI tools and build steps run over and over, to [`load`] the
+/// stats of their previous runs and see how the current one compares:
+/// ```ignore
+/// let history = timeit::registry::load("timings.tsv")?;
+/// let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+/// if let Some(change) = history.change_since("compile", week_ago) {
+///     eprintln!("compile took {:+.0}% vs. the 7-day average", change * 100.0);
+/// }
+/// timeit::registry::save("timings.tsv")?;
+/// ```
+/// > compile took +20% vs. the 7-day average
+///
+/// Save once per run (typically at the end of `main`), as each save appends
+/// everything recorded so far. The file is tab-separated text, one line per
+/// label and outcome of each run.
+pub fn save(path: impl AsRef<Path>) -> io::Result<()> {
+    let saved_at = SystemTime::now();
+    let mut runs = Vec::new();
+    registry::for_each_stats(|label, outcome, stats| {
+        runs.push(Saved {
+            saved_at,
+            label: label.to_string(),
+            outcome,
+            stats,
+        })
+    });
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let is_new = file.metadata()?.len() == 0;
+    let mut out = BufWriter::new(file);
+    if is_new {
+        writeln!(out, "{}", HEADER)?;
+    }
+    for run in &runs {
+        writeln!(out, "{}", run)?;
+    }
+    out.flush()
+}
+
+/// Read back the stats [`save`]d to `path` by earlier runs
+///
+/// A missing file is an empty history, as it is on the first run.
+pub fn load(path: impl AsRef<Path>) -> io::Result<History> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(History::default()),
+        Err(e) => return Err(e),
+    };
+    let mut runs = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let run = Saved::parse(&line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {} of the timing history is malformed", n + 1),
+            )
+        })?;
+        runs.push(run);
+    }
+    Ok(History { runs })
+}
+
+/// The stats of earlier runs, see [`load`]
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    runs: Vec<Saved>,
+}
+
+impl History {
+    /// Whether nothing was saved yet
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Aggregated timings of a label over every saved run, across outcomes
+    pub fn stats(&self, label: &str) -> Option<Stats> {
+        self.stats_since(label, UNIX_EPOCH)
+    }
+
+    /// Aggregated timings of a label over the runs saved since `since`
+    pub fn stats_since(&self, label: &str, since: SystemTime) -> Option<Stats> {
+        self.runs
+            .iter()
+            .filter(|run| run.label == label && run.saved_at >= since)
+            .fold(None, |acc: Option<Stats>, run| match acc {
+                Some(mut acc) => {
+                    acc.merge(&run.stats);
+                    Some(acc)
+                }
+                None => Some(run.stats),
+            })
+    }
+
+    /// How the mean of a label in this process compares to its mean over the
+    /// runs saved since `since`: `0.2` when it's 20% slower, `-0.1` when it's
+    /// 10% faster
+    ///
+    /// `None` unless both have measurements of it.
+    pub fn change_since(&self, label: &str, since: SystemTime) -> Option<f64> {
+        let current = registry::stats(label)?.mean().as_secs_f64();
+        let saved = self.stats_since(label, since)?.mean().as_secs_f64();
+        if saved == 0.0 {
+            return None;
+        }
+        Some(current / saved - 1.0)
+    }
+}
+
+/// The stats of one label and outcome, as saved by one run
+#[derive(Clone, Debug, PartialEq)]
+struct Saved {
+    saved_at: SystemTime,
+    label: String,
+    outcome: Option<Outcome>,
+    stats: Stats,
+}
+
+impl Saved {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let mut next = || fields.next();
+        let saved_at = UNIX_EPOCH + Duration::from_secs(next()?.parse().ok()?);
+        let label = unescape(next()?)?;
+        let outcome = match next()? {
+            "-" => None,
+            "Ok" => Some(Outcome::Ok),
+            "Err" => Some(Outcome::Err),
+            _ => return None,
+        };
+        let mut nanos = || next()?.parse::<u64>().ok();
+        let count = nanos()?;
+        let (total, min, max) = (nanos()?, nanos()?, nanos()?);
+        if count == 0 || next().is_some() {
+            return None;
+        }
+        Some(Self {
+            saved_at,
+            label,
+            outcome,
+            stats: Stats {
+                count,
+                total: Duration::from_nanos(total),
+                min: Duration::from_nanos(min),
+                max: Duration::from_nanos(max),
+            },
+        })
+    }
+}
+
+impl fmt::Display for Saved {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let saved_at = self.saved_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(f, "{}\t", saved_at.as_secs())?;
+        for c in self.label.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '\t' => f.write_str("\\t")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        match self.outcome {
+            Some(outcome) => write!(f, "\t{}", outcome)?,
+            None => f.write_str("\t-")?,
+        }
+        write!(
+            f,
+            "\t{}\t{}\t{}\t{}",
+            self.stats.count,
+            self.stats.total.as_nanos(),
+            self.stats.min.as_nanos(),
+            self.stats.max.as_nanos()
+        )
+    }
+}
+
+fn unescape(field: &str) -> Option<String> {
+    let mut label = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        label.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("timeit-history-{}.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert!(load(&path).unwrap().is_empty());
+
+        // Two earlier runs, a week and a day ago
+        let day = Duration::from_secs(24 * 3600);
+        let ago = |days| SystemTime::now() - day * days;
+        let earlier = |days, ms| {
+            let stats = Stats {
+                count: 2,
+                total: Duration::from_millis(ms * 2),
+                min: Duration::from_millis(ms),
+                max: Duration::from_millis(ms),
+            };
+            Saved {
+                saved_at: ago(days),
+                label: "history::step\tone".to_string(),
+                outcome: None,
+                stats,
+            }
+        };
+        let lines = format!("{}\n{}\n{}\n", HEADER, earlier(7, 50), earlier(1, 100));
+        std::fs::write(&path, lines).unwrap();
+        registry::record("history::step\tone", None, Duration::from_millis(120));
+        registry::record("history::step\tone", None, Duration::from_millis(120));
+        save(&path).unwrap();
+
+        let history = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let all = history.stats("history::step\tone").unwrap();
+        assert_eq!(all.count, 6);
+        assert_eq!(all.total, Duration::from_millis(540));
+        assert_eq!(all.min, Duration::from_millis(50));
+        let recent = history.stats_since("history::step\tone", ago(2)).unwrap();
+        assert_eq!(recent.count, 4);
+        assert_eq!(recent.mean(), Duration::from_millis(110));
+
+        // This process against the day-old run only
+        let last_run = history.runs[1].saved_at;
+        let change = history
+            .change_since("history::step\tone", last_run)
+            .unwrap();
+        assert!((change - 0.0909).abs() < 0.001, "{}", change);
+        assert_eq!(history.change_since("history::missing", ago(8)), None);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let path =
+            std::env::temp_dir().join(format!("timeit-malformed-{}.tsv", std::process::id()));
+        std::fs::write(&path, format!("{}\n12\tlabel\tMaybe\t1\t1\t1\t1\n", HEADER)).unwrap();
+        let err = load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("line 2"));
+        assert_eq!(Saved::parse("12\ta\\x\t-\t1\t1\t1\t1"), None);
+    }
+}
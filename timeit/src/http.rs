@@ -0,0 +1,365 @@
+//! Request timing middleware for `tower` services (axum, tonic, hyper through
+//! `hyper-util`'s tower adapter, ...)
+//!
+//! ```ignore
+//! let app = Router::new()
+//!     .route("/users/{id}", get(get_user))
+//!     .layer(timeit::http::TimeitLayer::new().threshold_ms(100));
+//! ```
+//! > GET /users/{id} took 142 ms (Ok)
+//!
+//! Each request is labeled with its method and route, and reported like a
+//! `timeit!` measurement: to the [`Reporter`], and into the registry and the
+//! shared `observability` state (along with its sinks and exporters) when
+//! those features are on. Responses with a 5xx status, and errors of the
+//! service itself, are reported as `Err`. The time is taken until the response
+//! is ready to be sent, not until its body has been streamed out.
+//!
+//! With the `axum` feature, routes are the ones requests were matched against
+//! (`/users/{id}`, see `axum::extract::MatchedPath`). Otherwise, and for
+//! requests no route matched, they're labeled by their method alone: labels
+//! are kept for good, with measurements (and Prometheus series) per label, so
+//! raw paths with ids in them would grow without bounds. Name requests after
+//! something bounded with [`TimeitLayer::label_with`] to tell them apart.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::{Extensions, Method, Request, Response, Uri};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::clock;
+use crate::options::ReporterRef;
+use crate::report::{self, Label};
+use crate::{Level, Options, Outcome, Reporter};
+
+/// Names a request, see [`TimeitLayer::label_with`]
+pub type LabelFn = fn(&Method, &Uri, &Extensions) -> String;
+
+/// Times every request (or a sample of them) going through the services it wraps
+#[derive(Clone, Debug)]
+pub struct TimeitLayer {
+    label: LabelFn,
+    threshold: Duration,
+    level: Option<Level>,
+    reporter: Option<ReporterRef>,
+    sample_rate: f64,
+    /// Requests seen so far, shared by every service the layer wraps
+    requests: Arc<AtomicU64>,
+}
+
+impl Default for TimeitLayer {
+    fn default() -> Self {
+        Self {
+            label: method_and_route,
+            threshold: Duration::from_secs(0),
+            level: None,
+            reporter: None,
+            sample_rate: 1.0,
+            requests: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl TimeitLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only report requests taking at least this long, see [`Options::threshold`]
+    pub fn threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// [`threshold`](Self::threshold) in milliseconds
+    pub fn threshold_ms(self, threshold: u64) -> Self {
+        self.threshold(Duration::from_millis(threshold))
+    }
+
+    /// Tag each line with a severity, see [`Options::level`]
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Hand the measurements to `reporter` instead of the process-wide one
+    pub fn reporter(mut self, reporter: &'static dyn Reporter) -> Self {
+        self.reporter = Some(ReporterRef(reporter));
+        self
+    }
+
+    /// Only time this fraction of the requests (between 0 and 1), spread evenly:
+    /// at `0.1`, every tenth one
+    ///
+    /// Keeps the overhead and the output down on busy services, the aggregates
+    /// of the registry being just as representative.
+    pub fn sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Name requests with `label` rather than after their method and route
+    /// ```ignore
+    /// TimeitLayer::new().label_with(|method, uri, _| {
+    ///     let tenant = uri.path().split('/').nth(1).unwrap_or_default();
+    ///     format!("{} /{}/...", method, tenant)
+    /// })
+    /// ```
+    pub fn label_with(mut self, label: LabelFn) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Whether the next request is one of the sample
+    fn sampled(&self) -> bool {
+        let n = self.requests.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.sample_rate).floor() > (n * self.sample_rate).floor()
+    }
+
+    fn options(&self) -> Options<'static> {
+        let mut opts = Options::default();
+        opts.threshold(self.threshold);
+        if let Some(level) = self.level {
+            opts.level(level);
+        }
+        if let Some(reporter) = self.reporter {
+            opts.reporter(reporter.0);
+        }
+        opts
+    }
+}
+
+impl<S> Layer<S> for TimeitLayer {
+    type Service = Timeit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeit {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// `"GET /users/{id}"`, or `"GET"` without a matched route
+fn method_and_route(method: &Method, _uri: &Uri, extensions: &Extensions) -> String {
+    #[cfg(feature = "axum")]
+    if let Some(route) = extensions.get::<axum::extract::MatchedPath>() {
+        return format!("{} {}", method, route.as_str());
+    }
+    let _ = extensions;
+    method.to_string()
+}
+
+/// A service timed by [`TimeitLayer`]
+#[derive(Clone, Debug)]
+pub struct Timeit<S> {
+    inner: S,
+    layer: TimeitLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Timeit<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let timing = if crate::is_enabled() && self.layer.sampled() {
+            let label = (self.layer.label)(req.method(), req.uri(), req.extensions());
+            Some(Timing::start(label, &self.layer))
+        } else {
+            None
+        };
+        ResponseFuture {
+            inner: self.inner.call(req),
+            timing,
+        }
+    }
+}
+
+pin_project! {
+    /// The response of a [`Timeit`] service, reported once it's ready
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        timing: Option<Timing>,
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = match this.inner.poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some(timing) = this.timing.take() {
+            let outcome = match &res {
+                Ok(response) if !response.status().is_server_error() => Outcome::Ok,
+                _ => Outcome::Err,
+            };
+            timing.finish(outcome);
+        }
+        Poll::Ready(res)
+    }
+}
+
+/// A request being timed
+///
+/// Unlike the `Timer` of `timeit!`, it holds nothing tied to a thread, as
+/// requests can move between threads while they're awaited.
+struct Timing {
+    label: String,
+    layer: TimeitLayer,
+    start: Instant,
+    #[cfg(feature = "observability")]
+    in_flight: observability::InFlight,
+}
+
+impl Timing {
+    fn start(label: String, layer: &TimeitLayer) -> Self {
+        Self {
+            #[cfg(feature = "observability")]
+            in_flight: observability::begin(observability::Kind::Timing, &label),
+            label,
+            layer: layer.clone(),
+            start: clock::now(),
+        }
+    }
+
+    fn finish(self, outcome: Outcome) {
+        let elapsed = clock::elapsed(self.start);
+        report::report(
+            Label::Described(&self.label),
+            &self.layer.options(),
+            &[elapsed],
+            Some(outcome),
+            0,
+        );
+        #[cfg(feature = "observability")]
+        drop(self.in_flight);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::Collect;
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+    use std::task::Waker;
+
+    /// Answers `/fail` with a 500, and everything else with a 200
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<()>;
+        type Error = Infallible;
+        type Future = Ready<Result<Response<()>, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<()>) -> Self::Future {
+            let status = if req.uri().path() == "/fail" {
+                500
+            } else {
+                200
+            };
+            ready(Ok(Response::builder().status(status).body(()).unwrap()))
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn get(path: &str) -> Request<()> {
+        Request::get(path).body(()).unwrap()
+    }
+
+    #[test]
+    fn test_layer() {
+        static LINES: Collect = Collect::new();
+        let mut service = TimeitLayer::new().reporter(&LINES).layer(Echo);
+        block_on(service.call(get("/orders/7"))).unwrap();
+        block_on(service.call(get("/fail"))).unwrap();
+        let mut post = get("/orders");
+        *post.method_mut() = Method::POST;
+        block_on(service.call(post)).unwrap();
+
+        // Without a route, the paths (and the ids in them) are left out
+        let lines = LINES.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("GET took ") && lines[0].ends_with(" (Ok)"));
+        assert!(lines[1].starts_with("GET took ") && lines[1].ends_with(" (Err)"));
+        assert!(lines[2].starts_with("POST took "));
+    }
+
+    #[test]
+    fn test_sampling_and_threshold() {
+        static SAMPLED: Collect = Collect::new();
+        let layer = TimeitLayer::new()
+            .reporter(&SAMPLED)
+            .sample_rate(0.25)
+            .label_with(|method, _, _| format!("{} sampled", method));
+        // Clones of the service share the count
+        let (mut first, mut second) = (layer.layer(Echo), layer.layer(Echo));
+        for _ in 0..4 {
+            block_on(first.call(get("/a"))).unwrap();
+            block_on(second.call(get("/b"))).unwrap();
+        }
+        let mut slow = TimeitLayer::new()
+            .reporter(&SAMPLED)
+            .threshold_ms(60_000)
+            .layer(Echo);
+        block_on(slow.call(get("/c"))).unwrap();
+
+        let lines = SAMPLED.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.starts_with("GET sampled took ")));
+    }
+
+    #[cfg(feature = "axum")]
+    #[test]
+    fn test_axum_route() {
+        use axum::body::Body;
+        use axum::routing::get as route_get;
+        use axum::Router;
+
+        static ROUTES: Collect = Collect::new();
+        let mut router = Router::new()
+            .route("/users/{id}", route_get(|| async { "ok" }))
+            .layer(TimeitLayer::new().reporter(&ROUTES));
+        let req = Request::get("/users/42").body(Body::empty()).unwrap();
+        block_on(router.call(req)).unwrap();
+
+        let lines = ROUTES.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("GET /users/{id} took "));
+    }
+}
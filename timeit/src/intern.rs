@@ -0,0 +1,55 @@
+//! Labels that live for the rest of the process
+//!
+//! Each distinct dynamic label (a route name, a tenant id) is copied once and
+//! then shared, so measuring under it again doesn't allocate. The copies are
+//! never freed: intern bounded sets of labels, not request ids.
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+
+static LABELS: RwLock<BTreeSet<&'static str>> = RwLock::new(BTreeSet::new());
+
+/// The interned copy of `label`, made on first use
+pub(crate) fn intern(label: &str) -> &'static str {
+    if let Some(interned) = lookup(label) {
+        return interned;
+    }
+    let mut labels = LABELS.write().unwrap_or_else(|e| e.into_inner());
+    // Another thread may have got there between the locks
+    if let Some(interned) = labels.get(label) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(label.into());
+    labels.insert(interned);
+    interned
+}
+
+/// The interned copy of `label`, if there is one already
+pub(crate) fn lookup(label: &str) -> Option<&'static str> {
+    let labels = LABELS.read().unwrap_or_else(|e| e.into_inner());
+    labels.get(label).copied()
+}
+
+/// Borrowed labels as they are, owned ones [interned](intern)
+pub(crate) fn label<'a>(label: impl Into<Cow<'a, str>>) -> &'a str {
+    match label.into() {
+        Cow::Borrowed(label) => label,
+        Cow::Owned(label) => intern(&label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let route = String::from("GET /users/:id");
+        assert_eq!(lookup(&route), None);
+        let first = label(route.clone());
+        assert_eq!(first, "GET /users/:id");
+        assert!(std::ptr::eq(first, intern(&route)));
+        assert_eq!(lookup(&route), Some(first));
+        assert!(std::ptr::eq(label("static"), "static"));
+    }
+}
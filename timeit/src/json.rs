@@ -0,0 +1,203 @@
+//! Measurements as JSON objects, one per line, see [`Options::json`](crate::Options::json)
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "allocations")]
+use crate::allocations::Allocations;
+use crate::bench::Summary;
+use crate::cpu::CpuTime;
+use crate::metadata::Metadata;
+use crate::{Level, Outcome};
+
+/// What was measured, or that a correlated measurement started
+pub(crate) enum Elapsed {
+    Started,
+    Single(Duration),
+    Summary(Summary),
+}
+
+/// `{"name":"wait_for_it","elapsed_us":2002123,"ts":1760606400123}`, with the
+/// fields that don't apply left out
+pub(crate) struct Record<'a> {
+    pub(crate) name: Option<&'a str>,
+    pub(crate) group: Option<&'a str>,
+    pub(crate) id: Option<u64>,
+    pub(crate) level: Option<Level>,
+    pub(crate) outcome: Option<Outcome>,
+    pub(crate) elapsed: Elapsed,
+    /// The mean CPU time of a run, with `clock = "cpu"`
+    pub(crate) cpu: Option<CpuTime>,
+    /// The mean allocations of a run, with a counting allocator installed
+    #[cfg(feature = "allocations")]
+    pub(crate) allocs: Option<Allocations>,
+    pub(crate) metadata: Option<&'a Metadata>,
+}
+
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{{\"name\":{}", Str(name))?,
+            None => write!(f, "{{\"name\":null")?,
+        }
+        match &self.elapsed {
+            Elapsed::Started => write!(f, ",\"event\":\"started\"")?,
+            Elapsed::Single(elapsed) => write!(f, ",\"elapsed_us\":{}", elapsed.as_micros())?,
+            Elapsed::Summary(summary) => write!(
+                f,
+                ",\"elapsed_us\":{},\"runs\":{},\"warmup\":{},\"min_us\":{},\"max_us\":{},\"stddev_us\":{},\"p50_us\":{},\"p90_us\":{},\"p99_us\":{},\"outliers\":{}",
+                summary.mean.as_micros(),
+                summary.runs,
+                summary.warmup,
+                summary.min.as_micros(),
+                summary.max.as_micros(),
+                summary.stddev.as_micros(),
+                summary.p50.as_micros(),
+                summary.p90.as_micros(),
+                summary.p99.as_micros(),
+                summary.outliers
+            )?,
+        }
+        if let Some(cpu) = self.cpu {
+            write!(
+                f,
+                ",\"cpu_user_us\":{},\"cpu_sys_us\":{}",
+                cpu.user.as_micros(),
+                cpu.system.as_micros()
+            )?;
+        }
+        #[cfg(feature = "allocations")]
+        if let Some(allocs) = self.allocs {
+            write!(
+                f,
+                ",\"allocs\":{},\"alloc_bytes\":{}",
+                allocs.count, allocs.bytes
+            )?;
+        }
+        if let Some(outcome) = self.outcome {
+            write!(f, ",\"outcome\":\"{}\"", outcome)?;
+        }
+        if let Some(group) = self.group {
+            write!(f, ",\"group\":{}", Str(group))?;
+        }
+        if let Some(id) = self.id {
+            write!(f, ",\"id\":{}", id)?;
+        }
+        if let Some(level) = self.level {
+            write!(f, ",\"level\":\"{}\"", level)?;
+        }
+        if let Some(metadata) = self.metadata {
+            write!(f, ",\"pid\":{},\"tid\":{}", metadata.pid(), metadata.tid())?;
+            if let Some(thread) = metadata.thread_name() {
+                write!(f, ",\"thread\":{}", Str(thread))?;
+            }
+        }
+        // Milliseconds since the Unix epoch, when the line was written
+        let ts = match self.metadata {
+            Some(metadata) => metadata.epoch_ms(),
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+        };
+        write!(f, ",\"ts\":{}}}", ts)
+    }
+}
+
+/// A quoted JSON string
+struct Str<'a>(&'a str);
+
+impl fmt::Display for Str<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line without its `ts`, which changes from run to run
+    fn without_ts(record: Record) -> String {
+        let line = record.to_string();
+        let ts = line.rfind(",\"ts\":").unwrap();
+        assert!(line[ts + 6..line.len() - 1].parse::<u64>().is_ok());
+        format!("{}}}", &line[..ts])
+    }
+
+    #[test]
+    fn test_record() {
+        let record = |name, elapsed| Record {
+            name,
+            group: None,
+            id: None,
+            level: None,
+            outcome: None,
+            elapsed,
+            cpu: None,
+            #[cfg(feature = "allocations")]
+            allocs: None,
+            metadata: None,
+        };
+        assert_eq!(
+            without_ts(record(
+                Some("wait_for_it"),
+                Elapsed::Single(Duration::from_micros(2_002_123))
+            )),
+            r#"{"name":"wait_for_it","elapsed_us":2002123}"#
+        );
+        assert_eq!(
+            without_ts(Record {
+                group: Some("request \"7\"\n"),
+                id: Some(3),
+                level: Some(Level::Warn),
+                outcome: Some(Outcome::Err),
+                ..record(None, Elapsed::Single(Duration::from_nanos(1_500)))
+            }),
+            r#"{"name":null,"elapsed_us":1,"outcome":"Err","group":"request \"7\"\n","id":3,"level":"WARN"}"#
+        );
+        assert_eq!(
+            without_ts(Record {
+                id: Some(4),
+                ..record(Some("migrate"), Elapsed::Started)
+            }),
+            r#"{"name":"migrate","event":"started","id":4}"#
+        );
+        assert_eq!(
+            without_ts(Record {
+                cpu: Some(CpuTime {
+                    user: Duration::from_micros(250),
+                    system: Duration::from_micros(60),
+                }),
+                outcome: Some(Outcome::Ok),
+                ..record(Some("fetch"), Elapsed::Single(Duration::from_millis(12)))
+            }),
+            r#"{"name":"fetch","elapsed_us":12000,"cpu_user_us":250,"cpu_sys_us":60,"outcome":"Ok"}"#
+        );
+        let samples: Vec<_> = [4, 1, 7]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        let summary = Summary::from_samples(&samples).unwrap();
+        assert_eq!(
+            without_ts(record(Some("sort"), Elapsed::Summary(summary))),
+            r#"{"name":"sort","elapsed_us":4000,"runs":3,"warmup":0,"min_us":1000,"max_us":7000,"stddev_us":3000,"p50_us":4000,"p90_us":7000,"p99_us":7000,"outliers":0}"#
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(Str("a\\b\t\u{1}é").to_string(), r#""a\\b\t\u0001é""#);
+    }
+}
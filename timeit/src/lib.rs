@@ -18,91 +18,1881 @@
 //! Outputs:
 //! ```ignore
 //! This is going to be...
-//! 'wait_for_it' took 2002 ms
+//! 'wait_for_it' took 2.00 s
 //! ...Legendary!
 //! ```
 
+use std::fmt;
+
+// Lets the attribute macros use `::timeit` paths inside this crate too
+extern crate self as timeit;
+
+/// Attribute macros, kept apart from the `timeit!` macro sharing a name
+pub mod attr {
+    pub use timeit_macros::timeit;
+}
+
+#[cfg(feature = "allocations")]
+pub mod allocations;
+mod bench;
+pub mod clock;
+mod cpu;
+mod csv;
+#[cfg(feature = "arrow")]
+pub mod export;
+pub mod frame;
+pub mod group;
+#[cfg(feature = "registry")]
+mod history;
+#[cfg(feature = "http")]
+pub mod http;
+mod intern;
+mod iter;
+mod json;
+mod limit;
+mod metadata;
+mod options;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "registry")]
+pub mod registry;
+mod report;
+mod reporter;
+mod scope;
+pub mod slo;
+mod statsd;
+mod template;
+
+pub use csv::CsvReporter;
+pub use iter::{IterTimeExt, TimedIter};
+pub use limit::OverBudget;
+pub use options::{
+    is_enabled, is_json, is_metadata, is_quiet, set_enabled, set_json, set_metadata, set_quiet,
+    ClockKind, Level, OnStart, Options, Unit,
+};
+pub use reporter::{set_reporter, Reporter, Stderr};
+pub use scope::TimeitGuard;
+pub use statsd::StatsdReporter;
+
+/// Print the [registry](registry::dump) of every labeled measurement so far to
+/// stderr, typically at the end of `main` or a batch job
+///
+/// With the `hdrhistogram` feature, the [latency distribution](registry::distribution)
+/// of each label follows. Nothing is printed if no labeled measurement was made.
+#[cfg(feature = "registry")]
+pub fn summary() {
+    if !registry::all().is_empty() {
+        eprint!("timing summary:\n{}", registry::dump());
+        #[cfg(feature = "hdrhistogram")]
+        eprint!("latency distribution:\n{}", registry::distribution());
+    }
+}
+
+/// Which path a timed `Result` took
+///
+/// Error paths often have very different latency than the happy path,
+/// so they are reported (and aggregated) separately
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    Ok,
+    Err,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Ok => write!(f, "Ok"),
+            Outcome::Err => write!(f, "Err"),
+        }
+    }
+}
+
+/// Used by the macros, not part of the public API
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::limit::deadline;
+    pub use crate::options::on_complete_fn;
+    pub use crate::report::{path_len, path_name, AnyOutcome, Label, Probe, ResultOutcome, Timer};
+    pub use crate::template::is_valid as is_valid_template;
+
+    /// Whether `clock = "cpu"` can measure anything
+    pub const CPU_TIME: bool = cfg!(feature = "cpu-time");
+}
+
 /// Macro for timing functions
 ///
-/// This example uses `eprintln!()` to print to stderr,
-/// but in your own version, using the `log` crate would have
-/// the advantage of controlling when (levels) and where (stdout/stderr/etc.)
-/// the timing info is signaled
+/// Timings are printed to stderr. With the `log` feature they go through the
+/// `log` crate instead (target `timeit`, at `Info` unless a `level` is given),
+/// so the application's logger config decides which ones are kept:
+/// ```ignore
+/// timeit!(fetch_user(42); level = "debug");
+/// ```
+/// With the `tracing` feature, each measurement is a `timeit` span instead, with
+/// the label in its `label` field and the time taken in `elapsed_ms`.
+///
+/// When the expression returns a `Result`, the output notes which path was taken:
+/// ```ignore
+/// timeit!(fetch_user(42));
+/// ```
+/// > 'fetch_user' took 12.0 ms (Err)
+///
+/// Method calls are named after the method, whatever the receiver
+/// (`timeit!(self.client.fetch_user(42))` prints the same line), and
+/// path-qualified calls after their path:
+/// ```ignore
+/// timeit!(str::parse::<u64>(input));
+/// ```
+/// > 'str::parse::<u64>' took 120 ns
+///
+/// Measurements made while another one is in progress on the same thread are
+/// indented by how deeply they're nested, so the call structure shows (inner
+/// calls finish, and print, first):
+/// ```text
+///     'parse_row' took 1.02 ms
+///   'load_csv' took 3.40 ms
+/// 'import' took 3.52 ms
+/// ```
+///
+/// Options are passed as `key = value` pairs after the expression, each one
+/// calls the [`Options`] method of the same name:
+/// ```ignore
+/// timeit!(migrate_users(); correlate = true);
+/// ```
+/// > 'migrate_users' started (#1)
+/// > 'migrate_users' finished (#1) took 5.12 s
+///
+/// Or to benchmark steady-state performance over repeated runs:
+/// ```ignore
+/// timeit!(sort_all(); iterations = 100; warmup = 10);
+/// ```
+/// > 'sort_all' took mean 1.2ms over 100 runs after 10 warmup (min 1.1ms, max 3.0ms, stddev 240.00µs), p50 1.2ms, p90 1.4ms, p99 2.9ms
+///
+/// `iterations = auto` keeps sampling until the mean is known to within 5%
+/// (see [`Options::auto_iterations`]).
+///
+/// `threshold_ms = 50` only reports calls taking at least 50 ms, keeping hot loops
+/// readable. The result is returned either way.
+///
+/// `fmt = "[perf] {name}: {elapsed_ms}ms"` lays out the line to match existing
+/// log conventions (see [`Options::fmt`]), and `json = true` writes a JSON object
+/// per line instead (see [`Options::json`]).
+///
+/// `quiet = true` (or [`set_quiet`] for every call) still measures, but prints nothing.
+/// [`set_enabled`]`(false)` (or `TIMEIT_DISABLED=1`) doesn't even measure.
+/// `on_complete = |name, dur| metrics.record(name, dur)` hands the measurement to
+/// a closure instead of printing it (see [`Options::on_complete`]).
+/// `reporter = &MY_REPORTER` (or [`set_reporter`] for every call) hands the
+/// measurements to a [`Reporter`] instead of stderr.
+///
+/// # Overhead
+///
+/// Timing a single call costs two `Instant::now()` reads, and the output line is
+/// formatted straight into the stderr handle. Nothing is allocated, so it can stay
+/// in allocation-sensitive paths (audio callbacks, order handling, ...). Repeated
+/// runs keep their samples in a `Vec`, and [`timeit_group!`] labels, the `registry`
+/// and the `observability` features allocate as well.
+///
+/// With the `disabled` feature there's no overhead at all: `timeit!` (along
+/// with [`timeit_scope!`], [`timeit_block!`], [`timed_trait!`] and the
+/// [`#[timeit]`](attr::timeit) attribute) expands to just the expression, so
+/// instrumentation can stay in place for release builds:
+/// ```toml
+/// [features]
+/// release = ["timeit/disabled"]
+/// ```
+/// Labels and options are still type-checked then, but never evaluated.
 #[macro_export]
 macro_rules! timeit {
     // Attempt to match function name & args
     // ```ignore
     // timeit!(something_slow());
     // ```
-    // > 'wait_for_it' took 2000 ms
-    ($n:ident ( $($args:expr),*)) => {{
-        let _start = std::time::Instant::now();
-        let _res = $n($($args,)*);
+    // > 'wait_for_it' took 2.00 s
+    ($n:ident ( $($args:expr),*) $(; $($opts:tt)*)?) => {{
         // Use the function name (ident) in the log
-        eprintln!("'{}' took {:.3} ms", stringify!($n), _start.elapsed().as_millis());
-        _res
+        $crate::timeit!(
+            @run $crate::__private::Label::Function(stringify!($n)),
+            [$($($opts)*)?],
+            $n($($args,)*)
+        )
+    }};
+    // Or a method call, on a variable or a chain of fields/calls
+    // ```ignore
+    // timeit!(client.fetch(42));
+    // ```
+    // > 'fetch' took 12.0 ms
+    ($recv:ident . $($rest:tt)+) => {{
+        $crate::timeit!(@method [$recv] . $($rest)+)
+    }};
+    // Or a path-qualified function, with or without a turbofish
+    // ```ignore
+    // timeit!(parse::<u64>(input));
+    // ```
+    // > 'parse::<u64>' took 120 ns
+    ($first:ident :: $($rest:tt)+) => {{
+        $crate::timeit!(@path [$first ::] $($rest)+)
     }};
     // Otherwise take a function by name:
     // ```ignore
     // timeit!(my_func);
     // ```
-    // > Took 2000 ms
-    ($e:expr) => {{
-        let _start = std::time::Instant::now();
-        let _res = $e();
-        eprintln!("Took {:.3} ms", _start.elapsed().as_millis());
-        _res
+    // > Took 2.00 s
+    ($e:expr $(; $($opts:tt)*)?) => {{
+        $crate::timeit!(@run $crate::__private::Label::Anonymous, [$($($opts)*)?], $e())
     }};
     // Otherwise take a function by name, and a log prefix
     // ```ignore
     // timeit!(my_func, "My Func");
     // ```
-    // > My Func took 2000 ms
+    // > My Func took 2.00 s
+    ($e:expr, $desc:literal $(; $($opts:tt)*)?) => {{
+        $crate::timeit!(@run $crate::__private::Label::Described($desc), [$($($opts)*)?], $e())
+    }};
+    // Munch the receiver until only `.method(args)` (and the options) are left.
+    // Anything else ending in a field, like a closure held by a struct, is
+    // handed back to the rules above.
+    (@method [$($recv:tt)*] . $m:ident ( $($args:expr),* ) $(; $($opts:tt)*)?) => {
+        $crate::timeit!(
+            @run $crate::__private::Label::Function(stringify!($m)),
+            [$($($opts)*)?],
+            $($recv)*.$m($($args,)*)
+        )
+    };
+    (@method [$($recv:tt)*] $(; $($opts:tt)*)?) => {
+        $crate::timeit!(($($recv)*) $(; $($opts)*)?)
+    };
+    (@method [$($recv:tt)*] , $($rest:tt)*) => {
+        $crate::timeit!(($($recv)*), $($rest)*)
+    };
+    (@method [$($recv:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::timeit!(@method [$($recv)* $next] $($rest)*)
+    };
+    // Munch the path up to the arguments, then parse it back as a `path`, which
+    // can't be followed by `(` directly. A path followed by a method call goes to
+    // `@method`, and anything that doesn't end in a call back to the rules above.
+    (@path [$($p:tt)*] ( $($args:tt)* ) $(; $($opts:tt)*)?) => {
+        $crate::timeit!(@path_call ($($p)*) ($($args)*) [$($($opts)*)?])
+    };
+    (@path [$($p:tt)*] ( $($args:tt)* ) $($rest:tt)+) => {
+        $crate::timeit!(@method [$($p)* ($($args)*)] $($rest)+)
+    };
+    (@path [$($p:tt)*] . $($rest:tt)+) => {
+        $crate::timeit!(@method [$($p)*] . $($rest)+)
+    };
+    (@path [$($p:tt)*] $(; $($opts:tt)*)?) => {
+        $crate::timeit!(($($p)*) $(; $($opts)*)?)
+    };
+    (@path [$($p:tt)*] , $($rest:tt)*) => {
+        $crate::timeit!(($($p)*), $($rest)*)
+    };
+    (@path [$($p:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::timeit!(@path [$($p)* $next] $($rest)*)
+    };
+    (@path_call ($n:path) ($($args:expr),*) [$($opts:tt)*]) => {
+        $crate::timeit!(
+            @run $crate::__private::Label::Function({
+                const RAW: &str = stringify!($n);
+                const NAME: [u8; $crate::__private::path_len(RAW)] =
+                    $crate::__private::path_name(RAW);
+                const NAME_STR: &str = match std::str::from_utf8(&NAME) {
+                    Ok(name) => name,
+                    Err(_) => RAW,
+                };
+                NAME_STR
+            }),
+            [$($opts)*],
+            $n($($args,)*)
+        )
+    };
+    // Shared by the rules above: apply the options, time the call, report the
+    // elapsed time (split by `Ok`/`Err` when the result is a `Result`) and hand back
+    // the result. The call is evaluated in place so `?` and `return` keep working.
+    (@run $label:expr, [$($opts:tt)*], $call:expr) => {
+        $crate::__instrument!(@run $label, [$($opts)*], $call)
+    };
+    // Only expand the call in a loop when it will be repeated, so a single
+    // evaluation can still move its arguments
+    (@mode [], $label:expr, [$($opts:tt)*], $call:expr) => {{
+        #[allow(unused_mut)]
+        let mut _opts = $crate::Options::default();
+        $crate::timeit!(@opts _opts; $($opts)*);
+        let mut _timer = $crate::__private::Timer::start($label, &_opts);
+        _timer.begin();
+        let _res = $call;
+        _timer.end();
+        $crate::timeit!(@finish _timer, _res)
+    }};
+    (@mode [iterations = $($rest:tt)*], $label:expr, [$($opts:tt)*], $call:expr) => {
+        $crate::timeit!(@repeat $label, [$($opts)*], $call)
+    };
+    (@mode [warmup = $($rest:tt)*], $label:expr, [$($opts:tt)*], $call:expr) => {
+        $crate::timeit!(@repeat $label, [$($opts)*], $call)
+    };
+    (@mode [$key:ident = $val:expr $(; $($rest:tt)*)?], $label:expr, [$($opts:tt)*], $call:expr) => {
+        $crate::timeit!(@mode [$($($rest)*)?], $label, [$($opts)*], $call)
+    };
+    (@repeat $label:expr, [$($opts:tt)*], $call:expr) => {{
+        let mut _opts = $crate::Options::default();
+        $crate::timeit!(@opts _opts; $($opts)*);
+        let mut _timer = $crate::__private::Timer::start($label, &_opts);
+        for _ in 0.._timer.warmup() {
+            let _ = $call;
+        }
+        let _res = loop {
+            _timer.begin();
+            let _res = $call;
+            if _timer.end() {
+                break _res;
+            }
+        };
+        $crate::timeit!(@finish _timer, _res)
+    }};
+    (@finish $timer:ident, $res:ident) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{AnyOutcome, ResultOutcome};
+        $timer.finish((&$crate::__private::Probe(&$res)).outcome());
+        $res
+    }};
+    // Each `key = value` option calls the matching `Options` method
+    (@opts $o:ident;) => {};
+    (@opts $o:ident; iterations = auto $(; $($rest:tt)*)?) => {
+        $o.auto_iterations();
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+    // Unit, level and clock names are checked at compile time
+    (@opts $o:ident; unit = $unit:literal $(; $($rest:tt)*)?) => {
+        $o.unit({
+            const UNIT: $crate::Unit = match $crate::Unit::from_name($unit) {
+                Some(unit) => unit,
+                None => panic!("unknown unit, expected one of: ns, us, µs, ms, s, min, auto"),
+            };
+            UNIT
+        });
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+    (@opts $o:ident; level = $level:literal $(; $($rest:tt)*)?) => {
+        $o.level({
+            const LEVEL: $crate::Level = match $crate::Level::from_name($level) {
+                Some(level) => level,
+                None => panic!("unknown level, expected one of: error, warn, info, debug, trace"),
+            };
+            LEVEL
+        });
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+    (@opts $o:ident; clock = $clock:literal $(; $($rest:tt)*)?) => {
+        $o.clock({
+            const CLOCK: $crate::ClockKind = match $crate::ClockKind::from_name($clock) {
+                Some($crate::ClockKind::Cpu) if !$crate::__private::CPU_TIME => {
+                    panic!("clock = \"cpu\" requires the cpu-time feature")
+                }
+                Some(clock) => clock,
+                None => panic!("unknown clock, expected one of: wall, cpu"),
+            };
+            CLOCK
+        });
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+    (@opts $o:ident; fmt = $fmt:literal $(; $($rest:tt)*)?) => {
+        $o.fmt({
+            const FMT: &str = $fmt;
+            const _: () = assert!(
+                $crate::__private::is_valid_template(FMT),
+                "{}",
+                "unknown placeholder or unmatched brace in fmt, placeholders are: \
+                 {name}, {group}, {outcome}, {elapsed}, {elapsed_ms}, {elapsed_us}, {elapsed_ns}"
+            );
+            FMT
+        });
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+    // Kept in a local, so the options can borrow it until the measurement is over
+    (@opts $o:ident; on_complete = $f:expr $(; $($rest:tt)*)?) => {
+        let _on_complete = $crate::__private::on_complete_fn($f);
+        $o.on_complete(&_on_complete);
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+    (@opts $o:ident; $key:ident = $val:expr $(; $($rest:tt)*)?) => {
+        $o.$key($val);
+        $crate::timeit!(@opts $o; $($($rest)*)?);
+    };
+}
+
+/// Like [`timeit!`], but hand back the elapsed time along with the result,
+/// instead of printing it
+///
+/// Takes the same forms of expression, for feeding your own metrics:
+/// ```
+/// use timeit::timed;
+///
+/// fn slow_sum(a: u32, b: u32) -> u32 {
+///     std::thread::sleep(std::time::Duration::from_millis(5));
+///     a + b
+/// }
+///
+/// let (sum, took) = timed!(slow_sum(1, 2));
+/// assert_eq!(sum, 3);
+/// assert!(took >= std::time::Duration::from_millis(5));
+/// ```
+/// Nothing is printed or recorded, so options don't apply.
+#[macro_export]
+macro_rules! timed {
+    ($n:ident ( $($args:expr),*)) => {{
+        $crate::timed!(@run $n($($args,)*))
+    }};
+    ($recv:ident . $($rest:tt)+) => {{
+        $crate::timed!(@method [$recv] . $($rest)+)
+    }};
+    ($first:ident :: $($rest:tt)+) => {{
+        $crate::timed!(@path [$first ::] $($rest)+)
+    }};
+    ($e:expr) => {{
+        $crate::timed!(@run $e())
+    }};
+    // The description only labels output, which there isn't any of
     ($e:expr, $desc:literal) => {{
-        let _start = std::time::Instant::now();
-        let _res = $e();
-        eprintln!("{} took {:.3} ms", $desc, _start.elapsed().as_millis());
+        $crate::timed!(@run $e())
+    }};
+    (@method [$($recv:tt)*] . $m:ident ( $($args:expr),* )) => {
+        $crate::timed!(@run $($recv)*.$m($($args,)*))
+    };
+    (@method [$($recv:tt)*]) => {
+        $crate::timed!(($($recv)*))
+    };
+    (@method [$($recv:tt)*] , $($rest:tt)*) => {
+        $crate::timed!(($($recv)*), $($rest)*)
+    };
+    (@method [$($recv:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::timed!(@method [$($recv)* $next] $($rest)*)
+    };
+    // No name to keep, so a path call can be evaluated as it is
+    (@path [$($p:tt)*] ( $($args:tt)* )) => {
+        $crate::timed!(@run $($p)*($($args)*))
+    };
+    (@path [$($p:tt)*] ( $($args:tt)* ) $($rest:tt)+) => {
+        $crate::timed!(@method [$($p)* ($($args)*)] $($rest)+)
+    };
+    (@path [$($p:tt)*] . $($rest:tt)+) => {
+        $crate::timed!(@method [$($p)*] . $($rest)+)
+    };
+    (@path [$($p:tt)*]) => {
+        $crate::timed!(($($p)*))
+    };
+    (@path [$($p:tt)*] , $($rest:tt)*) => {
+        $crate::timed!(($($p)*), $($rest)*)
+    };
+    (@path [$($p:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::timed!(@path [$($p)* $next] $($rest)*)
+    };
+    (@run $call:expr) => {{
+        let _start = $crate::clock::now();
+        let _res = $call;
+        (_res, $crate::clock::elapsed(_start))
+    }};
+}
+
+/// Label every measurement made (on this thread) while running a block
+///
+/// Nested `timeit!` output is prefixed with the group, so the timings of
+/// concurrent requests can be told apart after the fact:
+/// ```
+/// use timeit::{timeit, timeit_group};
+///
+/// fn parse() -> u32 {
+///     42
+/// }
+///
+/// let value = timeit_group!("request 1234", {
+///     timeit!(parse())
+/// });
+/// assert_eq!(value, 42);
+/// ```
+/// > request 1234 / 'parse' took 120 ns
+///
+/// Groups nest (`request 1234 / auth / 'check' took ...`). The block runs in
+/// place, so `?` and `return` work as usual.
+#[macro_export]
+macro_rules! timeit_group {
+    ($name:expr, $body:block) => {{
+        let _group = $crate::group::enter($name);
+        $body
+    }};
+}
+
+/// Time the rest of the enclosing scope
+///
+/// Creates a [`TimeitGuard`] that reports when the scope is left, including
+/// early returns and `?` exits, so a whole function body can be timed without
+/// wrapping it:
+/// ```
+/// use timeit::timeit_scope;
+///
+/// fn handle(input: &str) -> Option<u32> {
+///     timeit_scope!("handle request");
+///     let id = input.strip_prefix("id=")?;
+///     id.parse().ok()
+/// }
+/// assert_eq!(handle("id=7"), Some(7));
+/// assert_eq!(handle("name=x"), None);
+/// ```
+/// > handle request took 2.31 µs
+///
+/// Options are passed like for [`timeit!`]:
+/// ```ignore
+/// timeit_scope!("sync"; threshold = Duration::from_millis(100));
+/// ```
+#[macro_export]
+macro_rules! timeit_scope {
+    ($label:expr $(; $($opts:tt)*)?) => {
+        $crate::__instrument!(@scope $label, [$($($opts)*)?]);
+    };
+}
+
+/// Time a block of code in place and hand back its value
+///
+/// Unlike wrapping the block in a closure for [`timeit!`], `?`, `return`,
+/// `break` and `continue` inside it act on the enclosing function or loop.
+/// Leaving early is still reported, just without the `Ok`/`Err` outcome:
+/// ```
+/// use timeit::timeit_block;
+///
+/// fn total(lines: &[&str]) -> Result<u32, std::num::ParseIntError> {
+///     let total = timeit_block!("parse", {
+///         let mut total = 0;
+///         for line in lines {
+///             total += line.parse::<u32>()?;
+///         }
+///         total
+///     });
+///     Ok(total)
+/// }
+/// assert_eq!(total(&["1", "2"]), Ok(3));
+/// assert!(total(&["1", "two"]).is_err());
+/// ```
+/// > parse took 1.06 µs
+///
+/// Options are passed like for [`timeit!`], after the block:
+/// ```ignore
+/// timeit_block!("parse", { ... }; level = Level::Debug);
+/// ```
+#[macro_export]
+macro_rules! timeit_block {
+    ($label:expr, $body:block $(; $($opts:tt)*)?) => {
+        $crate::__instrument!(@block $label, $body, [$($($opts)*)?])
+    };
+}
+
+/// What the timing macros expand to, or just the code they wrap with the
+/// `disabled` feature
+#[cfg(not(feature = "disabled"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __instrument {
+    (@run $label:expr, [$($opts:tt)*], $call:expr) => {
+        $crate::timeit!(@mode [$($opts)*], $label, [$($opts)*], $call)
+    };
+    (@scope $label:expr, [$($opts:tt)*]) => {
+        // Locals of the options (an `on_complete` closure) have to outlive the guard
+        #[allow(unused_mut)]
+        let mut _timeit_opts = $crate::Options::default();
+        $crate::timeit!(@opts _timeit_opts; $($opts)*);
+        let _timeit_scope = $crate::TimeitGuard::with_options($label, &_timeit_opts);
+    };
+    (@block $label:expr, $body:block, [$($opts:tt)*]) => {{
+        #[allow(unused_mut)]
+        let mut _opts = $crate::Options::default();
+        $crate::timeit!(@opts _opts; $($opts)*);
+        let _guard = $crate::TimeitGuard::with_options($label, &_opts);
+        let _res = $body;
+        #[allow(unused_imports)]
+        use $crate::__private::{AnyOutcome, ResultOutcome};
+        _guard.finish((&$crate::__private::Probe(&_res)).outcome());
         _res
     }};
 }
 
+#[cfg(feature = "disabled")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __instrument {
+    (@run $label:expr, [$($opts:tt)*], $call:expr) => {{
+        $crate::__instrument!(@unused $label, [$($opts)*]);
+        $call
+    }};
+    (@scope $label:expr, [$($opts:tt)*]) => {
+        $crate::__instrument!(@unused $label, [$($opts)*]);
+    };
+    (@block $label:expr, $body:block, [$($opts:tt)*]) => {{
+        $crate::__instrument!(@unused $label, [$($opts)*]);
+        $body
+    }};
+    // Still type-checked, so they don't go stale (or leave what they use
+    // unused), but never run
+    (@unused $label:expr, [$($opts:tt)*]) => {
+        let _ = || {
+            let _label = &$label;
+            #[allow(unused_mut)]
+            let mut _opts = $crate::Options::default();
+            $crate::timeit!(@opts _opts; $($opts)*);
+        };
+    };
+}
+
+/// Macro for enforcing a soft deadline on an expression
+///
+/// The expression always runs to completion, but if it took longer than the
+/// budget the value is returned as `Err(OverBudget { took, budget, value })`
+/// so callers can discard results that arrived too late:
+/// ```
+/// use std::time::Duration;
+/// use timeit::time_limit;
+///
+/// let quote = time_limit!(42; budget = Duration::from_millis(50));
+/// assert_eq!(quote, Ok(42));
+///
+/// let stale = time_limit!({
+///     std::thread::sleep(Duration::from_millis(20));
+///     42
+/// }; budget = Duration::from_millis(10));
+/// assert_eq!(stale.unwrap_err().value, 42);
+/// ```
+///
+/// With the `retryable` feature, `Retryable` loops inside the expression stop
+/// retrying once their next attempt would start past the budget.
+#[macro_export]
+macro_rules! time_limit {
+    ($e:expr; budget=$b:expr) => {{
+        let _budget: std::time::Duration = $b;
+        let _start = $crate::clock::now();
+        let _deadline = $crate::__private::deadline(_budget);
+        let _value = $e;
+        let _took = $crate::clock::elapsed(_start);
+        if _took > _budget {
+            Err($crate::OverBudget {
+                took: _took,
+                budget: _budget,
+                value: _value,
+            })
+        } else {
+            Ok(_value)
+        }
+    }};
+}
+
+/// Macro for enforcing a latency SLO on a block
+///
+/// Like [`time_limit!`] the block always runs to completion and its value is
+/// returned, but a block that took longer than the budget is reported to the
+/// handler registered with [`slo::on_violation`], which turns it into a metric,
+/// an event or a decision to shed load:
+/// ```
+/// use std::time::Duration;
+/// use timeit::ensure_within;
+///
+/// let total = ensure_within!("checkout", Duration::from_millis(200), {
+///     1 + 1
+/// });
+/// assert_eq!(total, 2);
+/// assert_eq!(timeit::slo::stats("checkout").unwrap().violations, 0);
+/// ```
+/// Checks and violations are counted per SLO name, see [`slo::stats`].
+#[macro_export]
+macro_rules! ensure_within {
+    ($slo:expr, $budget:expr, $body:block) => {{
+        let _budget: std::time::Duration = $budget;
+        let _start = $crate::clock::now();
+        let _value = $body;
+        $crate::slo::check($slo, _budget, $crate::clock::elapsed(_start));
+        _value
+    }};
+}
+
+/// Wrapper that times every call made through a trait declared with [`timed_trait!`]
+///
+/// `Timed` itself doesn't know anything about the trait, the macro generates
+/// the `impl Trait for Timed<T>` which forwards each method to the wrapped value.
+pub struct Timed<T: ?Sized> {
+    inner: T,
+}
+
+impl<T> Timed<T> {
+    /// Wrap a value to time its trait methods
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Unwrap, returning the original value
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ?Sized> Timed<T> {
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// Time the methods of a trait through [`Timed`], declaring the trait or
+/// instrumenting an existing one
+///
+/// Each method call made through `Timed<T>` is logged as `Trait::method`:
+/// ```
+/// use timeit::{timed_trait, Timed};
+///
+/// timed_trait! {
+///     pub trait Storage {
+///         fn get(&self, key: &str) -> Option<String>;
+///         fn put(&mut self, key: &str, value: String);
+///     }
+/// }
+///
+/// struct Memory(std::collections::HashMap<String, String>);
+///
+/// impl Storage for Memory {
+///     fn get(&self, key: &str) -> Option<String> {
+///         self.0.get(key).cloned()
+///     }
+///     fn put(&mut self, key: &str, value: String) {
+///         self.0.insert(key.to_owned(), value);
+///     }
+/// }
+///
+/// let mut storage: Box<Timed<dyn Storage>> = Box::new(Timed::new(Memory(Default::default())));
+/// storage.put("answer", "42".to_owned());
+/// assert_eq!(storage.get("answer"), Some("42".to_owned()));
+/// ```
+/// > 'Storage::put' took 3.41 µs
+/// > 'Storage::get' took 980 ns
+///
+/// For a trait declared elsewhere, give its path after `impl` and repeat the
+/// signatures of its methods. Orphan rules still apply: the trait has to be
+/// from the crate using the macro.
+/// ```
+/// # mod storage {
+/// #     pub trait Storage {
+/// #         fn get(&self, key: &str) -> Option<String>;
+/// #     }
+/// # }
+/// timeit::timed_trait! {
+///     impl storage::Storage {
+///         fn get(&self, key: &str) -> Option<String>;
+///     }
+/// }
+/// ```
+///
+/// Methods must take `&self` or `&mut self` and can't have generics or default bodies.
+/// A trait object is timed by wrapping the value before it's boxed, as a
+/// `Box<Timed<dyn Trait>>`.
+#[macro_export]
+macro_rules! timed_trait {
+    (
+        impl $name:path {
+            $(
+                $(#[$mattr:meta])*
+                fn $method:ident ( $($params:tt)* ) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        impl<T: $name + ?Sized> $name for $crate::Timed<T> {
+            $(
+                fn $method($($params)*) $(-> $ret)? {
+                    $crate::timeit!(
+                        @run $crate::__private::Label::Function(concat!(stringify!($name), "::", stringify!($method))),
+                        [],
+                        $crate::timed_trait!(@forward $method; $($params)*)
+                    )
+                }
+            )*
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $name:ident {
+            $(
+                $(#[$mattr:meta])*
+                fn $method:ident ( $($params:tt)* ) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis trait $name {
+            $(
+                $(#[$mattr])*
+                fn $method($($params)*) $(-> $ret)?;
+            )*
+        }
+
+        $crate::timed_trait! {
+            impl $name {
+                $(fn $method($($params)*) $(-> $ret)?;)*
+            }
+        }
+    };
+    // `self` has to come from the caller's tokens to be usable in the generated body
+    (@forward $method:ident; &mut $s:ident $(, $arg:ident : $ty:ty)* $(,)?) => {
+        $s.get_mut().$method($($arg),*)
+    };
+    (@forward $method:ident; & $s:ident $(, $arg:ident : $ty:ty)* $(,)?) => {
+        $s.get_ref().$method($($arg),*)
+    };
+}
+
 /// Run `cargo test -- --nocapture` to see stderr output
-#[cfg(test)]
+// The timing macros don't measure anything with the `disabled` feature
+#[cfg(all(test, not(feature = "disabled")))]
 mod tests {
     use super::*;
+    use crate::clock::FakeClock;
+    use crate::reporter::Collect;
+    use std::time::Duration;
 
     #[test]
     fn test_simple() {
-        timeit!(|| { std::thread::sleep(std::time::Duration::from_secs(1)) });
+        let clock = FakeClock::new();
+        let _guard = clock::set_thread_clock(clock.clone());
+        timeit!(|| { clock.advance(Duration::from_secs(1)) });
     }
 
     /// Pass a prefix
     #[test]
     fn test_with_name() {
-        timeit!(
-            || { std::thread::sleep(std::time::Duration::from_secs(1)) },
-            "Sleeping"
-        );
+        let clock = FakeClock::new();
+        let _guard = clock::set_thread_clock(clock.clone());
+        timeit!(|| { clock.advance(Duration::from_secs(1)) }, "Sleeping");
     }
 
     #[test]
     fn test_ext() {
-        fn wait_for_it() -> String {
-            std::thread::sleep(std::time::Duration::from_secs(2));
+        // As in the README
+        #[allow(clippy::needless_return)]
+        fn wait_for_it(clock: &FakeClock) -> String {
+            clock.advance(Duration::from_secs(2));
             return String::from("...Legendary!");
         }
+        let clock = FakeClock::new();
+        let _guard = clock::set_thread_clock(clock.clone());
         eprintln!("This is going to be...");
-        let res = timeit!(wait_for_it());
+        let res = timeit!(wait_for_it(&clock));
         eprintln!("{}", res);
     }
 
     #[test]
     fn test_ext_multiple_args() {
-        fn slow_sum(a: u32, b: u32) -> u32 {
-            std::thread::sleep(std::time::Duration::from_secs(2));
+        fn slow_sum(clock: &FakeClock, a: u32, b: u32) -> u32 {
+            clock.advance(Duration::from_secs(2));
             a + b
         }
-        let res = timeit!(slow_sum(5, 9));
+        let clock = FakeClock::new();
+        let _guard = clock::set_thread_clock(clock.clone());
+        let (res, took) = timed!(slow_sum(&clock, 5, 9));
+        assert_eq!((res, took), (14, Duration::from_secs(2)));
+        let res = timeit!(slow_sum(&clock, 5, 9));
         eprintln!("Slow sum result: {}", res);
     }
+
+    timed_trait! {
+        trait Counter {
+            fn get(&self) -> u32;
+            fn add(&mut self, amount: u32);
+        }
+    }
+
+    struct Simple(u32);
+
+    impl Counter for Simple {
+        fn get(&self) -> u32 {
+            self.0
+        }
+        fn add(&mut self, amount: u32) {
+            self.0 += amount;
+        }
+    }
+
+    #[test]
+    fn test_timed_trait() {
+        let mut counter = Timed::new(Simple(1));
+        counter.add(2);
+        assert_eq!(counter.get(), 3);
+        assert_eq!(counter.into_inner().0, 3);
+    }
+
+    #[test]
+    fn test_timed_trait_object() {
+        let mut counter: Box<Timed<dyn Counter>> = Box::new(Timed::new(Simple(0)));
+        counter.add(5);
+        assert_eq!(counter.get(), 5);
+    }
+
+    mod shapes {
+        pub trait Area {
+            fn area(&self) -> u32;
+        }
+    }
+
+    timed_trait! {
+        impl shapes::Area {
+            fn area(&self) -> u32;
+        }
+    }
+
+    struct Square(u32);
+
+    impl shapes::Area for Square {
+        fn area(&self) -> u32 {
+            self.0 * self.0
+        }
+    }
+
+    #[test]
+    fn test_timed_existing_trait() {
+        use shapes::Area;
+
+        assert_eq!(Timed::new(Square(3)).area(), 9);
+    }
+
+    #[test]
+    fn test_result_outcome() {
+        fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+            s.parse()
+        }
+        assert!(timeit!(parse("12")).is_ok());
+        assert!(timeit!(parse("twelve")).is_err());
+        assert_eq!(timeit!(|| Ok::<_, ()>(5), "Ok path"), Ok(5));
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn test_registry_dump() {
+        fn slow() -> Result<(), ()> {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            Err(())
+        }
+        for _ in 0..2 {
+            let _ = timeit!(slow, "tests::dump slow"; quiet = true);
+        }
+        timeit!(|| 1, "tests::dump fast"; quiet = true);
+
+        let labels = registry::all();
+        let position = |name| labels.iter().position(|(label, _)| *label == name).unwrap();
+        assert!(position("tests::dump slow") < position("tests::dump fast"));
+        assert_eq!(labels[position("tests::dump slow")].1.count, 2);
+
+        let table = registry::dump();
+        assert!(table.starts_with("label "));
+        let row = table.lines().find(|l| l.starts_with("tests::dump slow")).unwrap();
+        assert_eq!(row.split_whitespace().nth(2), Some("2"));
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn test_registry_outcomes() {
+        fn flaky(fail: bool) -> Result<(), ()> {
+            if fail {
+                Err(())
+            } else {
+                Ok(())
+            }
+        }
+        let _ = timeit!(flaky(false));
+        let _ = timeit!(flaky(true));
+        let _ = timeit!(flaky(true));
+        assert_eq!(registry::stats_for("flaky", Outcome::Ok).unwrap().count, 1);
+        assert_eq!(registry::stats_for("flaky", Outcome::Err).unwrap().count, 2);
+        assert_eq!(registry::stats("flaky").unwrap().count, 3);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        use std::time::Duration;
+
+        let res = time_limit!(1 + 1; budget = Duration::from_secs(1));
+        assert_eq!(res, Ok(2));
+
+        let res = time_limit!({
+            std::thread::sleep(Duration::from_millis(20));
+            "late"
+        }; budget = Duration::from_millis(5));
+        let over = res.unwrap_err();
+        assert!(over.took > over.budget);
+        assert_eq!(over.budget, Duration::from_millis(5));
+        assert_eq!(over.into_value(), "late");
+    }
+
+    #[cfg(feature = "retryable")]
+    #[test]
+    fn test_time_limit_caps_retries() {
+        use retryable::{RetryDelay, RetryStrategy, Retryable};
+        use std::time::Duration;
+
+        let strategy = RetryStrategy::new(10, RetryDelay::Fixed(Duration::from_millis(10)));
+        let mut r = Retryable::new(|| Err::<(), _>("unavailable"), strategy);
+        let res = time_limit!(r.try_call(); budget = Duration::from_millis(35));
+        assert!(res.unwrap().is_err());
+        assert_eq!(r.attempts_made(), 4);
+    }
+
+    #[cfg(feature = "hdrhistogram")]
+    #[test]
+    fn test_registry_histogram() {
+        fn nap(ms: u64) {
+            std::thread::sleep(std::time::Duration::from_millis(ms));
+        }
+        for ms in &[1, 1, 1, 10] {
+            timeit!(nap(*ms));
+        }
+        let hist = registry::histogram("nap").unwrap();
+        assert_eq!(hist.len(), 4);
+        assert!(hist.value_at_quantile(0.5) < 10_000_000);
+        assert!(hist.max() >= 10_000_000);
+        assert!(registry::histogram_for("nap", Outcome::Ok).is_none());
+    }
+
+    #[test]
+    fn test_correlate() {
+        fn migrate(rows: u32) -> u32 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            rows
+        }
+        assert_eq!(timeit!(migrate(5); correlate = true), 5);
+        assert_eq!(timeit!(|| migrate(6), "Migrating"; correlate = true), 6);
+    }
+
+    #[test]
+    fn test_question_mark() {
+        fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+            s.parse()
+        }
+        fn double(s: &str) -> Result<u32, std::num::ParseIntError> {
+            let n = timeit!(parse(s))?;
+            Ok(n * 2)
+        }
+        assert_eq!(double("4"), Ok(8));
+        assert!(double("four").is_err());
+    }
+
+    #[test]
+    fn test_iterations_warmup() {
+        let mut calls = 0;
+        let mut count = || {
+            calls += 1;
+            calls
+        };
+        assert_eq!(timeit!(count, "Counting"; iterations = 5; warmup = 3), 8);
+
+        let mut calls = 0;
+        let mut count = || {
+            calls += 1;
+            calls
+        };
+        assert_eq!(timeit!(count; warmup = 2), 3);
+    }
+
+    #[test]
+    fn test_iterations_auto() {
+        let mut calls = 0;
+        let mut nap = || {
+            calls += 1;
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        timeit!(nap, "Auto"; iterations = auto; target_error = 0.5);
+        assert!(calls >= 10);
+
+        let start = std::time::Instant::now();
+        let max_time = std::time::Duration::from_millis(50);
+        let mut flip = false;
+        let mut noisy = || {
+            flip = !flip;
+            std::thread::sleep(std::time::Duration::from_millis(if flip { 0 } else { 2 }));
+        };
+        timeit!(noisy; iterations = auto; target_error = 0.0; max_time = max_time);
+        assert!(start.elapsed() >= max_time);
+    }
+
+    #[crate::attr::timeit(threshold = "1ms", level = "warn", label = "tests::parse")]
+    fn attribute_parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+        let value: u32 = input.trim().parse()?;
+        Ok(value * 2)
+    }
+
+    #[crate::attr::timeit]
+    fn attribute_early_return(skip: bool) -> &'static str {
+        if skip {
+            return "skipped";
+        }
+        "done"
+    }
+
+    struct Store(Vec<u32>);
+
+    #[crate::attr::timeit(level = "debug")]
+    impl Store {
+        const fn new() -> Self {
+            Store(Vec::new())
+        }
+
+        fn push(&mut self, value: u32) -> usize {
+            self.0.push(value);
+            self.0.len()
+        }
+
+        fn first(&self) -> Option<u32> {
+            let first = self.0.first()?;
+            Some(*first)
+        }
+
+        #[crate::attr::timeit(label = "tests::Store::take")]
+        fn into_inner(self) -> Vec<u32> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_timeit_attribute_impl() {
+        const STORE: Store = Store::new();
+        let mut store = STORE;
+        assert_eq!(store.first(), None);
+        assert_eq!(store.push(3), 1);
+        assert_eq!(store.first(), Some(3));
+        assert_eq!(store.into_inner(), vec![3]);
+    }
+
+    #[test]
+    fn test_timeit_attribute() {
+        assert_eq!(attribute_parse(" 21 "), Ok(42));
+        assert!(attribute_parse("x").is_err());
+        assert_eq!(attribute_early_return(true), "skipped");
+        assert_eq!(attribute_early_return(false), "done");
+    }
+
+    #[test]
+    fn test_threshold_level() {
+        let value = timeit!(|| 42; threshold = std::time::Duration::from_secs(60); level = Level::Debug);
+        assert_eq!(value, 42);
+        let value = timeit!(|| 42; threshold = std::time::Duration::from_secs(60); level = "debug");
+        assert_eq!(value, 42);
+        assert_eq!(Level::from_name("trace"), Some(Level::Trace));
+        assert_eq!(Level::from_name("loud"), None);
+    }
+
+    #[test]
+    fn test_threshold_ms() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static REPORTS: AtomicUsize = AtomicUsize::new(0);
+        static COUNT: fn(&str, std::time::Duration) = |_, _| {
+            REPORTS.fetch_add(1, Ordering::Relaxed);
+        };
+        for i in 0..100 {
+            assert_eq!(timeit!(|| i; threshold_ms = 50; reporter = &COUNT), i);
+        }
+        let slow = || std::thread::sleep(std::time::Duration::from_millis(60));
+        timeit!(slow; threshold_ms = 50; reporter = &COUNT);
+        assert_eq!(REPORTS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_fmt() {
+        static LINES: Collect = Collect::new();
+        fn fetch() -> Result<u32, ()> {
+            Ok(1)
+        }
+        let res = timeit!(fetch(); fmt = "[perf] {name}: {elapsed_us}us {outcome}"; reporter = &LINES);
+        assert_eq!(res, Ok(1));
+        timeit_group!("batch", {
+            timeit!(fetch(); iterations = 5; fmt = "{group}/{name} {{mean}} {elapsed_ns}"; reporter = &LINES)
+        })
+        .unwrap();
+        // Set at runtime, an unknown placeholder is kept as written
+        drop(TimeitGuard::with_options("scope", Options::default().fmt("{name} {nope}").reporter(&LINES)));
+
+        let lines = LINES.lines();
+        assert!(lines[0].starts_with("[perf] fetch: ") && lines[0].ends_with("us Ok"));
+        assert!(lines[1].starts_with("batch/fetch {mean} "));
+        assert!(lines[1][19..].parse::<u64>().is_ok());
+        assert_eq!(lines[2], "scope {nope}");
+    }
+
+    #[test]
+    fn test_json() {
+        static LINES: Collect = Collect::new();
+        fn load_rows() -> Result<u32, ()> {
+            Ok(2)
+        }
+        let res = timeit!(load_rows(); json = true; level = "warn"; reporter = &LINES);
+        assert_eq!(res, Ok(2));
+        timeit!(load_rows(); json = true; correlate = true; reporter = &LINES).unwrap();
+        timeit!(load_rows(); json = true; iterations = 3; reporter = &LINES).unwrap();
+
+        let lines = LINES.lines();
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|l| l.starts_with('{') && l.ends_with('}')));
+        assert!(lines[0].starts_with(r#"{"name":"load_rows","elapsed_us":"#));
+        assert!(lines[0].contains(r#","outcome":"Ok","level":"WARN","ts":"#));
+        assert!(lines[1].starts_with(r#"{"name":"load_rows","event":"started","id":"#));
+        let id = &lines[1][lines[1].find(r#""id":"#).unwrap()..lines[1].find(",\"ts").unwrap()];
+        assert!(lines[2].contains(id));
+        assert!(lines[3].contains(r#","runs":3,"warmup":0,"min_us":"#));
+    }
+
+    #[test]
+    fn test_metadata() {
+        static LINES: Collect = Collect::new();
+        fn tag_rows() -> u32 {
+            3
+        }
+        timeit!(tag_rows(); metadata = true; level = "warn"; reporter = &LINES);
+        timeit!(tag_rows(); metadata = true; json = true; reporter = &LINES);
+        timeit!(tag_rows(); reporter = &LINES);
+
+        let lines = LINES.lines();
+        let pid = format!(" pid={} tid=", std::process::id());
+        assert!(lines[0].starts_with("ts=") && lines[0].contains(&pid));
+        // Before the level, so every line starts with the timestamp
+        assert!(lines[0].contains(" [WARN] 'tag_rows' took "));
+        let pid = format!(r#","pid":{},"tid":"#, std::process::id());
+        assert!(lines[1].starts_with('{') && lines[1].contains(&pid));
+        assert!(lines[2].starts_with("'tag_rows' took "));
+    }
+
+    #[cfg(all(
+        feature = "cpu-time",
+        any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")
+    ))]
+    #[test]
+    fn test_cpu_clock() {
+        use std::time::Duration;
+
+        static LINES: Collect = Collect::new();
+        fn doze() -> Result<(), ()> {
+            std::thread::sleep(Duration::from_millis(20));
+            Ok(())
+        }
+        timeit!(doze(); clock = "cpu"; unit = "ms"; reporter = &LINES).unwrap();
+        timeit!(doze(); clock = "cpu"; iterations = 2; json = true; reporter = &LINES).unwrap();
+        timeit!(doze(); clock = ClockKind::Wall; reporter = &LINES).unwrap();
+
+        let lines = LINES.lines();
+        // Asleep, so the CPU time is a small fraction of the wall time
+        let (wall, rest) = lines[0]["'doze' took ".len()..]
+            .split_once(" ms (user ")
+            .unwrap();
+        let (user, sys) = rest
+            .strip_suffix(" ms) (Ok)")
+            .unwrap()
+            .split_once(" ms, sys ")
+            .unwrap();
+        assert!(wall.parse::<f64>().unwrap() >= 20.0);
+        let cpu = user.parse::<f64>().unwrap() + sys.parse::<f64>().unwrap();
+        assert!(cpu < 10.0, "{}", lines[0]);
+        assert!(lines[1].contains(r#","runs":2,"#) && lines[1].contains(r#","cpu_sys_us":"#));
+        assert!(!lines[2].contains("cpu"));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing() {
+        use std::sync::Mutex;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Collects the string and float fields of every span
+        #[derive(Default)]
+        struct Spans(Mutex<Vec<(Id, String, String)>>);
+
+        struct Fields<'a>(&'a Spans, &'a Id);
+
+        impl Visit for Fields<'_> {
+            fn record_f64(&mut self, field: &Field, value: f64) {
+                let field = (self.1.clone(), field.name().to_string(), value.to_string());
+                (self.0).0.lock().unwrap().push(field);
+            }
+
+            fn record_str(&mut self, field: &Field, value: &str) {
+                let field = (self.1.clone(), field.name().to_string(), value.to_string());
+                (self.0).0.lock().unwrap().push(field);
+            }
+
+            fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+        }
+
+        impl Subscriber for &'static Spans {
+            fn enabled(&self, _metadata: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes) -> Id {
+                let id = Id::from_u64((self.0.lock().unwrap().len() + 1) as u64);
+                span.record(&mut Fields(self, &id));
+                id
+            }
+
+            fn record(&self, span: &Id, values: &Record) {
+                values.record(&mut Fields(self, span));
+            }
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+            fn event(&self, _event: &Event) {}
+            fn enter(&self, _span: &Id) {}
+            fn exit(&self, _span: &Id) {}
+        }
+
+        fn fetch() -> u32 {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            7
+        }
+        let spans: &'static Spans = Box::leak(Box::default());
+        let value = tracing::subscriber::with_default(spans, || timeit!(fetch()));
+        assert_eq!(value, 7);
+
+        let fields = spans.0.lock().unwrap();
+        let field = |name: &str| fields.iter().find(|(_, n, _)| n == name).unwrap();
+        assert_eq!(field("label").2, "fetch");
+        assert_eq!(field("otel.name").2, "fetch");
+        let (id, _, elapsed_ms) = field("elapsed_ms");
+        assert_eq!(*id, field("label").0);
+        assert!(elapsed_ms.parse::<f64>().unwrap() >= 2.0);
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn test_opentelemetry() {
+        use opentelemetry::trace::{
+            SpanBuilder, SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId,
+            TraceState, Tracer, TracerProvider,
+        };
+        use opentelemetry::{Context, InstrumentationScope, KeyValue};
+        use std::borrow::Cow;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+        use std::time::SystemTime;
+
+        /// Keeps the name, parent, attributes and status of every ended span
+        #[derive(Clone, Default)]
+        struct Spans(Arc<Mutex<Vec<Recorded>>>, Arc<AtomicU64>);
+
+        #[derive(Clone, Debug)]
+        struct Recorded {
+            name: String,
+            id: SpanId,
+            parent: Option<SpanId>,
+            attributes: Vec<KeyValue>,
+            status: Status,
+        }
+
+        struct Span(Spans, SpanContext, Recorded);
+
+        impl opentelemetry::trace::Span for Span {
+            fn add_event_with_timestamp<T>(&mut self, _: T, _: SystemTime, _: Vec<KeyValue>)
+            where
+                T: Into<Cow<'static, str>>,
+            {
+            }
+
+            fn span_context(&self) -> &SpanContext {
+                &self.1
+            }
+
+            fn is_recording(&self) -> bool {
+                true
+            }
+
+            fn set_attribute(&mut self, attribute: KeyValue) {
+                self.2.attributes.push(attribute);
+            }
+
+            fn set_status(&mut self, status: Status) {
+                self.2.status = status;
+            }
+
+            fn update_name<T>(&mut self, _: T)
+            where
+                T: Into<Cow<'static, str>>,
+            {
+            }
+
+            fn add_link(&mut self, _: SpanContext, _: Vec<KeyValue>) {}
+
+            fn end_with_timestamp(&mut self, _: SystemTime) {
+                (self.0).0.lock().unwrap().push(self.2.clone());
+            }
+        }
+
+        impl Tracer for Spans {
+            type Span = Span;
+
+            fn build_with_context(&self, builder: SpanBuilder, parent_cx: &Context) -> Span {
+                let id = SpanId::from(self.1.fetch_add(1, Ordering::Relaxed) + 1);
+                let context = SpanContext::new(
+                    TraceId::from(1),
+                    id,
+                    TraceFlags::SAMPLED,
+                    false,
+                    TraceState::NONE,
+                );
+                let recorded = Recorded {
+                    name: builder.name.to_string(),
+                    id,
+                    parent: parent_cx
+                        .has_active_span()
+                        .then(|| parent_cx.span().span_context().span_id()),
+                    attributes: builder.attributes.unwrap_or_default(),
+                    status: Status::Unset,
+                };
+                Span(self.clone(), context, recorded)
+            }
+        }
+
+        impl TracerProvider for Spans {
+            type Tracer = Spans;
+
+            fn tracer_with_scope(&self, _: InstrumentationScope) -> Spans {
+                self.clone()
+            }
+        }
+
+        fn otel_lookup() -> Result<u32, ()> {
+            Err(())
+        }
+        fn otel_fetch() -> u32 {
+            let _ = timeit!(otel_lookup());
+            7
+        }
+        let spans = Spans::default();
+        opentelemetry::global::set_tracer_provider(spans.clone());
+        let line = line!() + 1;
+        assert_eq!(timeit!(otel_fetch()), 7);
+
+        let recorded = spans.0.lock().unwrap();
+        let span = |name: &str| recorded.iter().find(|s| s.name == name).unwrap();
+        let attribute = |span: &Recorded, key: &str| {
+            let attribute = span.attributes.iter().find(|kv| kv.key.as_str() == key);
+            attribute.unwrap().value.to_string()
+        };
+        let (fetch, lookup) = (span("otel_fetch"), span("otel_lookup"));
+        assert_eq!(attribute(fetch, "code.file.path"), file!());
+        assert_eq!(attribute(fetch, "code.line.number"), line.to_string());
+        let elapsed_ms = attribute(fetch, "timeit.elapsed_ms");
+        assert!(elapsed_ms.parse::<f64>().unwrap() > 0.0);
+        assert_eq!(fetch.status, Status::Unset);
+        // Nested in the span of the measurement it ran in
+        assert_eq!(lookup.parent, Some(fetch.id));
+        assert_eq!(fetch.parent, None);
+        assert!(matches!(lookup.status, Status::Error { .. }));
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log() {
+        use std::sync::Mutex;
+
+        struct Collect(Mutex<Vec<(log::Level, String)>>);
+
+        impl log::Log for Collect {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.target() == "timeit"
+            }
+
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    let line = record.args().to_string();
+                    self.0.lock().unwrap().push((record.level(), line));
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: Collect = Collect(Mutex::new(Vec::new()));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        timeit!(|| 42, "tests::log"; level = "debug");
+        timeit!(|| 42, "tests::log default");
+        let lines = LOGGER.0.lock().unwrap();
+        let logged = |label: &str| {
+            lines
+                .iter()
+                .find(|(_, line)| line.starts_with(label) && line.contains(" took "))
+                .map(|(level, _)| *level)
+        };
+        assert_eq!(logged("tests::log took"), Some(log::Level::Debug));
+        assert_eq!(logged("tests::log default took"), Some(log::Level::Info));
+    }
+
+    #[cfg(feature = "observability")]
+    #[test]
+    fn test_observability() {
+        fn observed() -> bool {
+            observability::snapshot()
+                .in_flight
+                .iter()
+                .any(|op| op.label == "observed")
+        }
+        assert!(timeit!(observed()));
+        assert!(!observed());
+        let snapshot = observability::snapshot();
+        let (_, latency) = snapshot
+            .latency
+            .iter()
+            .find(|(label, _)| label == "observed")
+            .unwrap();
+        assert_eq!(latency.count, 1);
+    }
+
+    #[test]
+    fn test_quiet() {
+        fn hushed() -> u32 {
+            7
+        }
+        assert_eq!(timeit!(hushed(); quiet = true), 7);
+        assert_eq!(timeit!(hushed(); quiet = true; iterations = 3), 7);
+        #[cfg(feature = "registry")]
+        assert_eq!(registry::stats("hushed").unwrap().count, 4);
+    }
+
+    #[test]
+    fn test_unit() {
+        use std::time::Duration;
+
+        fn hash_block() -> u32 {
+            std::thread::sleep(Duration::from_millis(2));
+            7
+        }
+        assert_eq!(timeit!(hash_block(); unit = Unit::Micros), 7);
+        assert_eq!(timeit!(hash_block(); unit = "us"), 7);
+        assert_eq!(Unit::from_name("µs"), Some(Unit::Micros));
+        assert_eq!(Unit::from_name("hours"), None);
+        let shown = |unit: Unit, elapsed| unit.display(elapsed).to_string();
+        assert_eq!(shown(Unit::Micros, Duration::from_millis(3)), "3000.000 µs");
+        assert_eq!(shown(Unit::Secs, Duration::from_millis(2999)), "2.999 s");
+        // Sub-millisecond measurements aren't truncated to 0
+        assert_eq!(shown(Unit::Millis, Duration::from_micros(150)), "0.150 ms");
+        assert_eq!(shown(Unit::Millis, Duration::from_nanos(42_317)), "0.042 ms");
+        assert_eq!(shown(Unit::Nanos, Duration::from_nanos(42_317)), "42317 ns");
+        assert_eq!(shown(Unit::Auto, Duration::from_nanos(850)), "850 ns");
+        assert_eq!(shown(Unit::Auto, Duration::from_nanos(12_400)), "12.4 µs");
+        assert_eq!(shown(Unit::Auto, Duration::from_micros(123_456)), "123 ms");
+        assert_eq!(shown(Unit::Auto, Duration::from_millis(3_200)), "3.20 s");
+        assert_eq!(shown(Unit::Auto, Duration::from_secs(90)), "1.50 min");
+        let expected = if cfg!(feature = "unit-us") {
+            Unit::Micros
+        } else if cfg!(feature = "unit-ms") {
+            Unit::Millis
+        } else {
+            Unit::Auto
+        };
+        assert_eq!(Unit::default(), expected);
+    }
+
+    #[test]
+    fn test_block() {
+        fn first_even(values: &[u32]) -> Option<u32> {
+            let found: Option<u32> = timeit_block!("tests::block", {
+                for value in values {
+                    if value % 2 == 0 {
+                        return Some(*value);
+                    }
+                }
+                None
+            }; correlate = true);
+            found.map(|v| v * 100)
+        }
+        assert_eq!(first_even(&[1, 4, 5]), Some(4));
+        assert_eq!(first_even(&[1, 3]), None);
+        let res: Result<u32, ()> = timeit_block!("tests::block", { Err(()) });
+        assert_eq!(res, Err(()));
+        #[cfg(feature = "registry")]
+        assert_eq!(registry::stats("tests::block").unwrap().count, 3);
+    }
+
+    #[test]
+    fn test_method_call() {
+        struct Client {
+            base: u32,
+            refresh: fn() -> u32,
+        }
+
+        impl Client {
+            fn fetch_order(&self, id: u32) -> Result<u32, ()> {
+                Ok(self.base + id)
+            }
+
+            fn inner(&self) -> &Self {
+                self
+            }
+        }
+
+        struct App {
+            client: Client,
+        }
+
+        let app = App {
+            client: Client {
+                base: 100,
+                refresh: || 7,
+            },
+        };
+        let client = &app.client;
+        assert_eq!(timeit!(client.fetch_order(1)), Ok(101));
+        assert_eq!(timeit!(app.client.fetch_order(2); quiet = true), Ok(102));
+        assert_eq!(timeit!(app.client.inner().fetch_order(3 + 1)), Ok(104));
+        let (res, _) = timed!(app.client.fetch_order(5));
+        assert_eq!(res, Ok(105));
+        // Fields holding a function are still called like before
+        assert_eq!(timeit!(app.client.refresh), 7);
+        assert_eq!(timeit!(app.client.refresh, "Refresh"; quiet = true), 7);
+        #[cfg(feature = "registry")]
+        assert_eq!(registry::stats("fetch_order").unwrap().count, 3);
+    }
+
+    #[test]
+    fn test_path_call() {
+        mod paths {
+            pub struct Client;
+
+            impl Client {
+                pub fn get(&self) -> u32 {
+                    3
+                }
+            }
+
+            pub static CLIENT: Client = Client;
+
+            pub fn double(x: u32) -> u32 {
+                x * 2
+            }
+        }
+
+        assert_eq!(timeit!(paths::double(2)), 4);
+        assert_eq!(timeit!(paths::double(3); quiet = true), 6);
+        assert_eq!(timeit!(str::parse::<u64>("5")), Ok(5));
+        assert_eq!(timeit!(Vec::<u8>::with_capacity(3)).capacity(), 3);
+        assert_eq!(timeit!(paths::CLIENT.get()), 3);
+        let (value, _) = timed!(paths::double(4));
+        assert_eq!(value, 8);
+        // Without a call, the function is still called with no arguments
+        assert_eq!(timeit!(Vec::<u8>::new).len(), 0);
+        #[cfg(feature = "registry")]
+        assert_eq!(registry::stats("paths::double").unwrap().count, 2);
+        #[cfg(feature = "registry")]
+        assert_eq!(registry::stats("str::parse::<u64>").unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_timed() {
+        use std::time::Duration;
+
+        fn nap(ms: u64) -> Result<u64, ()> {
+            std::thread::sleep(Duration::from_millis(ms));
+            Ok(ms)
+        }
+        fn run() -> Result<Duration, ()> {
+            let (_, took) = timed!(nap(3));
+            let (ms, _) = timed!(|| nap(1), "Nap");
+            assert_eq!(ms?, 1);
+            Ok(took)
+        }
+        assert!(run().unwrap() >= Duration::from_millis(3));
+        let (value, _) = timed!(|| 7);
+        assert_eq!(value, 7);
+    }
+
+    #[test]
+    fn test_on_start() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Instant;
+
+        static STARTS: AtomicUsize = AtomicUsize::new(0);
+        fn render() -> u32 {
+            3
+        }
+        let res = timeit!(render(); iterations = 3; on_start = |label, start| {
+            assert_eq!(label, Some("render"));
+            assert!(start <= Instant::now());
+            STARTS.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(res, 3);
+        assert_eq!(STARTS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_on_complete() {
+        use std::cell::RefCell;
+
+        static LINES: Collect = Collect::new();
+        fn checkout() -> Result<u32, ()> {
+            Ok(7)
+        }
+        let metrics = RefCell::new(Vec::new());
+        let record = |name: &str, _dur| metrics.borrow_mut().push(name.to_string());
+        let res =
+            timeit!(checkout(); on_complete = |name, dur| record(name, dur); reporter = &LINES);
+        assert_eq!(res, Ok(7));
+        timeit!(checkout(); iterations = 3; on_complete = record; reporter = &LINES).unwrap();
+        timeit!(|| 1; on_complete = record; reporter = &LINES);
+        {
+            timeit_scope!("checkout scope"; on_complete = record; reporter = &LINES);
+        }
+        timeit!(checkout(); threshold_ms = 60_000; on_complete = record).unwrap();
+
+        assert_eq!(
+            *metrics.borrow(),
+            ["checkout", "checkout", "", "checkout scope"]
+        );
+        assert!(LINES.lines().is_empty());
+    }
+
+    #[test]
+    fn test_reporter() {
+        use std::time::Duration;
+
+        static COLLECT: Collect = Collect::new();
+        fn fetch() -> Result<u32, ()> {
+            std::thread::sleep(Duration::from_millis(2));
+            Ok(1)
+        }
+        assert_eq!(timeit!(fetch(); reporter = &COLLECT; correlate = true), Ok(1));
+        // Quiet only silences the lines
+        assert_eq!(timeit!(fetch(); reporter = &COLLECT; quiet = true), Ok(1));
+        let reports = COLLECT.reports();
+        assert_eq!(reports.len(), 2);
+        for (label, elapsed) in reports.iter() {
+            assert_eq!(label, "fetch");
+            assert!(*elapsed >= Duration::from_millis(2));
+        }
+        let lines = COLLECT.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("'fetch' started (#"));
+        assert!(lines[1].starts_with("'fetch' finished (#") && lines[1].ends_with("(Ok)"));
+    }
+
+    #[test]
+    fn test_nested_indent() {
+        static LINES: Collect = Collect::new();
+        fn parse_row() -> u32 {
+            1
+        }
+        fn load_csv() -> u32 {
+            timeit!(parse_row(); reporter = &LINES) + timeit!(parse_row(); reporter = &LINES)
+        }
+        fn import() -> u32 {
+            let _guard = TimeitGuard::with_options("import", Options::default().reporter(&LINES));
+            timeit!(load_csv(); reporter = &LINES)
+        }
+        assert_eq!(import(), 2);
+        assert_eq!(timeit!(parse_row(); reporter = &LINES), 1);
+
+        let lines = LINES.lines();
+        let labels: Vec<_> = lines.iter().map(|l| l.split(" took").next().unwrap()).collect();
+        assert_eq!(
+            labels,
+            [
+                "    'parse_row'",
+                "    'parse_row'",
+                "  'load_csv'",
+                "import",
+                "'parse_row'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scope() {
+        fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+            timeit_scope!("tests::scope"; correlate = true);
+            let value = input.parse::<u32>()?;
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            Ok(value)
+        }
+        assert_eq!(parse("12"), Ok(12));
+        assert!(parse("twelve").is_err());
+        #[cfg(feature = "registry")]
+        assert_eq!(registry::stats("tests::scope").unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_scope_owned_label() {
+        for tenant in &["acme", "initech", "acme"] {
+            timeit_scope!(format!("tests::tenant::{}", tenant); quiet = true);
+        }
+        let borrowed = String::from("tests::tenant::borrowed");
+        drop(TimeitGuard::new(borrowed.as_str()));
+        #[cfg(feature = "registry")]
+        {
+            assert_eq!(registry::stats("tests::tenant::acme").unwrap().count, 2);
+            assert_eq!(registry::stats("tests::tenant::initech").unwrap().count, 1);
+            assert_eq!(registry::stats("tests::tenant::borrowed").unwrap().count, 1);
+        }
+    }
+
+    #[test]
+    fn test_ensure_within() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::time::Duration;
+
+        static SHED_AFTER: AtomicU64 = AtomicU64::new(0);
+        fn handler(violation: &slo::Violation) {
+            if violation.slo == "tests::slo" && violation.consecutive == 2 {
+                SHED_AFTER.store(violation.took.as_millis() as u64, Ordering::Relaxed);
+                // Handlers can replace themselves
+                slo::on_violation(handler);
+            }
+        }
+        slo::on_violation(handler);
+
+        let slow = || {
+            ensure_within!("tests::slo", Duration::from_millis(1), {
+                std::thread::sleep(Duration::from_millis(5));
+                "slow"
+            })
+        };
+        assert_eq!(slow(), "slow");
+        assert_eq!(SHED_AFTER.load(Ordering::Relaxed), 0);
+        slow();
+        assert!(SHED_AFTER.load(Ordering::Relaxed) >= 5);
+
+        let fast = ensure_within!("tests::slo", Duration::from_secs(60), { 7 });
+        assert_eq!(fast, 7);
+        let stats = slo::stats("tests::slo").unwrap();
+        assert_eq!((stats.checks, stats.violations, stats.consecutive), (3, 2, 0));
+    }
+}
+
+#[cfg(all(test, feature = "disabled"))]
+mod disabled_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static REPORTS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Count;
+
+    impl Reporter for Count {
+        fn report(&self, _label: &str, _elapsed: Duration) {
+            REPORTS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    static COUNT: Count = Count;
+
+    fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+        timeit_scope!("parse"; reporter = &COUNT);
+        let value = timeit_block!("block", { input.parse::<u32>()? }; reporter = &COUNT);
+        Ok(timeit!(u32::max(value, 1); reporter = &COUNT; iterations = 10))
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert_eq!(parse("7"), Ok(7));
+        assert!(parse("seven").is_err());
+        assert_eq!(timeit!(|| 42; reporter = &COUNT), 42);
+        assert_eq!(REPORTS.load(Ordering::Relaxed), 0);
+    }
 }
@@ -0,0 +1,48 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// The expression given to [`time_limit!`](crate::time_limit) finished, but too late
+///
+/// The value is kept so callers can still decide to use it
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverBudget<T> {
+    pub took: Duration,
+    pub budget: Duration,
+    pub value: T,
+}
+
+impl<T> OverBudget<T> {
+    /// Use the late value anyway
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Display for OverBudget<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "took {} ms, over the budget of {} ms",
+            self.took.as_millis(),
+            self.budget.as_millis()
+        )
+    }
+}
+
+impl<T: fmt::Debug> Error for OverBudget<T> {}
+
+/// The budget of a [`time_limit!`](crate::time_limit), in place as a
+/// `retryable::deadline` with the `retryable` feature
+pub struct Deadline {
+    #[cfg(feature = "retryable")]
+    _guard: retryable::deadline::DeadlineGuard,
+}
+
+#[cfg_attr(not(feature = "retryable"), allow(unused_variables))]
+pub fn deadline(budget: Duration) -> Deadline {
+    Deadline {
+        #[cfg(feature = "retryable")]
+        _guard: retryable::deadline::enter(budget),
+    }
+}
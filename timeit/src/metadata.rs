@@ -0,0 +1,155 @@
+//! Where and when a line was written, see [`Options::metadata`](crate::Options::metadata)
+use std::fmt;
+use std::thread::{self, Thread};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `ts=2026-10-16T09:20:00.123456Z pid=4242 tid=3 thread=worker-1`, without
+/// `thread` for unnamed threads
+pub(crate) struct Metadata {
+    at: SystemTime,
+    pid: u32,
+    thread: Thread,
+}
+
+impl Metadata {
+    pub(crate) fn now() -> Self {
+        Self {
+            at: SystemTime::now(),
+            pid: std::process::id(),
+            thread: thread::current(),
+        }
+    }
+
+    /// Milliseconds since the Unix epoch
+    pub(crate) fn epoch_ms(&self) -> u128 {
+        self.at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    }
+
+    pub(crate) fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// The number in `ThreadId(3)`, which is all its `Debug` gives away on stable
+    pub(crate) fn tid(&self) -> ThreadNumber<'_> {
+        ThreadNumber(&self.thread)
+    }
+
+    pub(crate) fn thread_name(&self) -> Option<&str> {
+        self.thread.name()
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ts={} pid={} tid={}", Utc(self.at), self.pid, self.tid())?;
+        if let Some(name) = self.thread_name() {
+            write!(f, " thread={}", name)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct ThreadNumber<'a>(&'a Thread);
+
+impl fmt::Display for ThreadNumber<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Formatted into a buffer on the stack, to keep reporting free of allocations
+        let mut buf = Buf::default();
+        fmt::write(&mut buf, format_args!("{:?}", self.0.id()))?;
+        let debug = buf.as_str();
+        let digits = debug.trim_start_matches(|c: char| !c.is_ascii_digit());
+        f.write_str(digits.trim_end_matches(')'))
+    }
+}
+
+#[derive(Default)]
+struct Buf {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Buf {
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
+impl fmt::Write for Buf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.bytes.len() {
+            return Err(fmt::Error);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// RFC 3339, to the microsecond
+struct Utc(SystemTime);
+
+impl fmt::Display for Utc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since = self.0.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since.as_secs();
+        let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+        let time = secs % 86_400;
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            year,
+            month,
+            day,
+            time / 3600,
+            time % 3600 / 60,
+            time % 60,
+            since.subsec_micros()
+        )
+    }
+}
+
+/// Year, month and day of a count of days since 1970-01-01, in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_metadata() {
+        let utc = Utc(UNIX_EPOCH + Duration::new(951_782_400, 42_000)).to_string();
+        assert_eq!(utc, "2000-02-29T00:00:00.000042Z");
+
+        let line = thread::Builder::new()
+            .name("worker-1".to_string())
+            .spawn(|| Metadata::now().to_string())
+            .unwrap()
+            .join()
+            .unwrap();
+        let fields: Vec<&str> = line.split(' ').collect();
+        assert_eq!(fields.len(), 4);
+        assert!(fields[0].starts_with("ts=") && fields[0].ends_with('Z'));
+        assert_eq!(fields[1], format!("pid={}", std::process::id()));
+        assert!(fields[2]["tid=".len()..].parse::<u64>().is_ok());
+        assert_eq!(fields[3], "thread=worker-1");
+    }
+}
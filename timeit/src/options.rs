@@ -0,0 +1,639 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::Reporter;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+static METADATA: AtomicBool = AtomicBool::new(false);
+/// [`UNSET`] until the environment is read or [`set_enabled`] is called
+static ENABLED: AtomicU8 = AtomicU8::new(UNSET);
+
+const UNSET: u8 = 0;
+const ON: u8 = 1;
+const OFF: u8 = 2;
+
+/// Turn every measurement in the process on or off, from now on
+///
+/// Off, `timeit!` (along with [`timeit_scope!`](crate::timeit_scope), [`TimeitGuard`](crate::TimeitGuard)
+/// and the `http` layer) just runs the code it wraps, once: unlike
+/// [`set_quiet`], nothing is measured, printed or recorded. All that's left is
+/// an atomic load per call, so operators can silence a binary without
+/// rebuilding it, starting it with `TIMEIT_DISABLED=1` in its environment.
+/// For no overhead at all, see the `disabled` feature instead.
+/// ```
+/// use timeit::timeit;
+///
+/// timeit::set_enabled(false);
+/// let mut runs = 0;
+/// timeit!(|| runs += 1; iterations = 100; on_complete = |_, _| panic!("measured"));
+/// assert_eq!(runs, 1);
+/// ```
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(if enabled { ON } else { OFF }, Ordering::Relaxed);
+}
+
+/// Whether measurements are on, see [`set_enabled`]
+pub fn is_enabled() -> bool {
+    match ENABLED.load(Ordering::Relaxed) {
+        UNSET => {
+            let from_env = if disables(std::env::var("TIMEIT_DISABLED").ok().as_deref()) {
+                OFF
+            } else {
+                ON
+            };
+            // Unless `set_enabled` was called in the meantime
+            match ENABLED.compare_exchange(UNSET, from_env, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => from_env == ON,
+                Err(set) => set == ON,
+            }
+        }
+        enabled => enabled == ON,
+    }
+}
+
+/// Whether a value of `TIMEIT_DISABLED` turns measurements off
+fn disables(value: Option<&str>) -> bool {
+    match value {
+        None => false,
+        Some(value) => !matches!(value.trim(), "" | "0" | "false"),
+    }
+}
+
+/// Silence the output of every `timeit!` in the process, as if each had
+/// `quiet = true`
+///
+/// Measurements are still taken and recorded (in the registry, etc.), which
+/// lets a library stay instrumented without printing to its users' terminal.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether output has been silenced with [`set_quiet`]
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Write the measurements of every `timeit!` in the process as JSON lines, as
+/// if each had `json = true`
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Whether JSON output has been turned on with [`set_json`]
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Tag the output of every `timeit!` in the process with the time, process and
+/// thread it was written from, as if each had `metadata = true`
+pub fn set_metadata(metadata: bool) {
+    METADATA.store(metadata, Ordering::Relaxed);
+}
+
+/// Whether output is tagged with [`set_metadata`]
+pub fn is_metadata() -> bool {
+    METADATA.load(Ordering::Relaxed)
+}
+
+/// Per-call options for `timeit!`
+///
+/// Given after the expression as `key = value` pairs separated by `;`,
+/// each pair calls the method with the same name:
+/// ```ignore
+/// timeit!(sync_all(); correlate = true);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Options<'a> {
+    pub(crate) correlate: bool,
+    pub(crate) iterations: Iterations,
+    pub(crate) warmup: usize,
+    pub(crate) target_error: f64,
+    pub(crate) max_time: Duration,
+    pub(crate) threshold: Duration,
+    pub(crate) level: Option<Level>,
+    pub(crate) quiet: bool,
+    pub(crate) unit: Unit,
+    pub(crate) on_start: Option<OnStart>,
+    pub(crate) on_complete: Option<OnCompleteRef<'a>>,
+    pub(crate) reporter: Option<ReporterRef>,
+    pub(crate) fmt: Option<&'static str>,
+    pub(crate) json: bool,
+    pub(crate) metadata: bool,
+    pub(crate) clock: ClockKind,
+}
+
+/// A reporter given with [`Options::reporter`], which has no `Debug` of its own
+#[derive(Clone, Copy)]
+pub(crate) struct ReporterRef(pub(crate) &'static dyn Reporter);
+
+impl fmt::Debug for ReporterRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Reporter")
+    }
+}
+
+/// Called with the label (if any) and start time of a measurement, see [`Options::on_start`]
+pub type OnStart = fn(Option<&str>, Instant);
+
+/// A callback given with [`Options::on_complete`], which has no `Debug` of its own
+#[derive(Clone, Copy)]
+pub(crate) struct OnCompleteRef<'a>(pub(crate) &'a dyn Fn(&str, Duration));
+
+impl fmt::Debug for OnCompleteRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OnComplete")
+    }
+}
+
+/// Gives a closure passed as `on_complete = ...` its signature, as `timeit!`
+/// binds it to a local before borrowing it
+pub fn on_complete_fn<F: Fn(&str, Duration)>(on_complete: F) -> F {
+    on_complete
+}
+
+/// Unit the elapsed time of a single measurement is printed in, see [`Options::unit`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    /// Whichever of the units below keeps the number readable:
+    /// `850 ns`, `12.4 µs`, `3.20 s`, `1.50 min`
+    Auto,
+    Nanos,
+    Micros,
+    Millis,
+    Secs,
+    Minutes,
+}
+
+impl Unit {
+    /// [`Auto`](Unit::Auto), unless a fixed unit is picked at compile time with the
+    /// `unit-us` or `unit-ms` features (the finer one winning when both end up enabled)
+    pub const DEFAULT: Unit = if cfg!(feature = "unit-us") {
+        Unit::Micros
+    } else if cfg!(feature = "unit-ms") {
+        Unit::Millis
+    } else {
+        Unit::Auto
+    };
+
+    /// The unit called `name` in `unit = "..."` options: `ns`, `us` (or `µs`),
+    /// `ms`, `s`, `min` or `auto`
+    pub const fn from_name(name: &str) -> Option<Unit> {
+        const NAMES: [(&str, Unit); 7] = [
+            ("auto", Unit::Auto),
+            ("ns", Unit::Nanos),
+            ("us", Unit::Micros),
+            ("µs", Unit::Micros),
+            ("ms", Unit::Millis),
+            ("s", Unit::Secs),
+            ("min", Unit::Minutes),
+        ];
+        let mut i = 0;
+        while i < NAMES.len() {
+            if str_eq(NAMES[i].0, name) {
+                return Some(NAMES[i].1);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// `elapsed` in this unit, like `0.150 ms` (to the thousandth of a unit) or
+    /// `150 µs` when picked automatically
+    pub fn display(self, elapsed: Duration) -> impl fmt::Display {
+        InUnit(elapsed, self)
+    }
+
+    fn nanos(self) -> u128 {
+        match self {
+            Unit::Auto | Unit::Nanos => 1,
+            Unit::Micros => 1_000,
+            Unit::Millis => 1_000_000,
+            Unit::Secs => 1_000_000_000,
+            Unit::Minutes => 60_000_000_000,
+        }
+    }
+
+    /// The largest unit `elapsed` is at least one of
+    fn fitting(elapsed: Duration) -> Unit {
+        let nanos = elapsed.as_nanos();
+        [Unit::Minutes, Unit::Secs, Unit::Millis, Unit::Micros]
+            .iter()
+            .copied()
+            .find(|unit| nanos >= unit.nanos())
+            .unwrap_or(Unit::Nanos)
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+impl Default for Unit {
+    fn default() -> Self {
+        Unit::DEFAULT
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Auto => write!(f, "auto"),
+            Unit::Nanos => write!(f, "ns"),
+            Unit::Micros => write!(f, "µs"),
+            Unit::Millis => write!(f, "ms"),
+            Unit::Secs => write!(f, "s"),
+            Unit::Minutes => write!(f, "min"),
+        }
+    }
+}
+
+struct InUnit(Duration, Unit);
+
+impl fmt::Display for InUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let InUnit(elapsed, unit) = *self;
+        match unit {
+            Unit::Auto => match Unit::fitting(elapsed) {
+                Unit::Nanos => write!(f, "{} ns", elapsed.as_nanos()),
+                unit => {
+                    // Three significant digits
+                    let value = elapsed.as_nanos() as f64 / unit.nanos() as f64;
+                    let decimals = if value < 10.0 {
+                        2
+                    } else if value < 100.0 {
+                        1
+                    } else {
+                        0
+                    };
+                    write!(f, "{:.*} {}", decimals, value, unit)
+                }
+            },
+            Unit::Nanos => write!(f, "{} ns", elapsed.as_nanos()),
+            // Fractions of the unit, so sub-millisecond timings don't show as `0 ms`
+            unit => {
+                let value = elapsed.as_nanos() as f64 / unit.nanos() as f64;
+                write!(f, "{:.3} {}", value, unit)
+            }
+        }
+    }
+}
+
+/// Severity tag for the output lines of a measurement, see [`Options::level`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// The level called `name` in `level = "..."` options: `error`, `warn`,
+    /// `info`, `debug` or `trace`
+    pub const fn from_name(name: &str) -> Option<Level> {
+        const NAMES: [(&str, Level); 5] = [
+            ("error", Level::Error),
+            ("warn", Level::Warn),
+            ("info", Level::Info),
+            ("debug", Level::Debug),
+            ("trace", Level::Trace),
+        ];
+        let mut i = 0;
+        while i < NAMES.len() {
+            if str_eq(NAMES[i].0, name) {
+                return Some(NAMES[i].1);
+            }
+            i += 1;
+        }
+        None
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<Level> for log::Level {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => log::Level::Error,
+            Level::Warn => log::Level::Warn,
+            Level::Info => log::Level::Info,
+            Level::Debug => log::Level::Debug,
+            Level::Trace => log::Level::Trace,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "ERROR"),
+            Level::Warn => write!(f, "WARN"),
+            Level::Info => write!(f, "INFO"),
+            Level::Debug => write!(f, "DEBUG"),
+            Level::Trace => write!(f, "TRACE"),
+        }
+    }
+}
+
+/// What a measurement is timed by, see [`Options::clock`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockKind {
+    /// Time passing on the wall clock
+    Wall,
+    /// The wall clock, and the CPU time the thread spent running the expression
+    /// in user and in system mode (with the `cpu-time` feature)
+    Cpu,
+}
+
+impl ClockKind {
+    /// The clock called `name` in `clock = "..."` options: `wall` or `cpu`
+    pub const fn from_name(name: &str) -> Option<ClockKind> {
+        if str_eq(name, "wall") {
+            Some(ClockKind::Wall)
+        } else if str_eq(name, "cpu") {
+            Some(ClockKind::Cpu)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Iterations {
+    Fixed(usize),
+    /// Keep sampling until the mean is known to within `target_error`
+    Auto,
+}
+
+impl Default for Options<'_> {
+    fn default() -> Self {
+        Self {
+            correlate: false,
+            iterations: Iterations::Fixed(1),
+            warmup: 0,
+            target_error: 0.05,
+            max_time: Duration::from_secs(5),
+            threshold: Duration::from_secs(0),
+            level: None,
+            quiet: false,
+            unit: Unit::DEFAULT,
+            on_start: None,
+            on_complete: None,
+            reporter: None,
+            fmt: None,
+            json: false,
+            metadata: false,
+            clock: ClockKind::Wall,
+        }
+    }
+}
+
+impl<'a> Options<'a> {
+    /// Emit a "started" line with a unique id when the expression begins, and
+    /// tag the finishing line with the same id
+    ///
+    /// Useful for long-running operations: a hung call shows up in the logs
+    /// as a "started" line without a matching "finished".
+    pub fn correlate(&mut self, correlate: bool) -> &mut Self {
+        self.correlate = correlate;
+        self
+    }
+
+    /// Evaluate the expression this many times and report aggregate statistics
+    /// instead of a single measurement. The result of the last run is returned.
+    /// ```ignore
+    /// timeit!(sort_all(); iterations = 100);
+    /// ```
+    /// > 'sort_all' took mean 1.2ms over 100 runs (min 1.1ms, max 3.0ms, stddev 240.00µs), p50 1.2ms, p90 1.4ms, p99 2.9ms
+    ///
+    /// Outlying runs (a page fault, a context switch) are left out of the mean
+    /// and its spread, but not out of the percentiles, which show the tail
+    /// latency they add.
+    pub fn iterations(&mut self, iterations: usize) -> &mut Self {
+        self.iterations = Iterations::Fixed(iterations.max(1));
+        self
+    }
+
+    /// Keep evaluating the expression until the 95% confidence interval of the
+    /// mean is within [`target_error`](Self::target_error) of it, or until
+    /// [`max_time`](Self::max_time) has been spent measuring.
+    ///
+    /// This is what `iterations = auto` calls:
+    /// ```ignore
+    /// timeit!(sort_all(); iterations = auto; target_error = 0.01);
+    /// ```
+    pub fn auto_iterations(&mut self) -> &mut Self {
+        self.iterations = Iterations::Auto;
+        self
+    }
+
+    /// Relative error of the mean to stop at with `iterations = auto` (default 5%)
+    pub fn target_error(&mut self, target_error: f64) -> &mut Self {
+        self.target_error = target_error;
+        self
+    }
+
+    /// Upper bound on measuring time with `iterations = auto` (default 5s)
+    pub fn max_time(&mut self, max_time: Duration) -> &mut Self {
+        self.max_time = max_time;
+        self
+    }
+
+    /// Evaluate the expression this many times, discarding the measurements,
+    /// before the measured runs start (warming caches, faulting in pages, etc.)
+    pub fn warmup(&mut self, warmup: usize) -> &mut Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Only report measurements taking at least this long (the mean, over
+    /// several iterations), keeping fast calls of a noisy function quiet
+    /// ```ignore
+    /// timeit!(fetch_user(42); threshold = Duration::from_millis(10));
+    /// ```
+    pub fn threshold(&mut self, threshold: Duration) -> &mut Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// [`threshold`](Options::threshold) in milliseconds, for hot loops where
+    /// only the slow calls are worth a line
+    /// ```ignore
+    /// timeit!(fetch_user(42); threshold_ms = 50);
+    /// ```
+    pub fn threshold_ms(&mut self, threshold: u64) -> &mut Self {
+        self.threshold(Duration::from_millis(threshold))
+    }
+
+    /// Tag each output line with a severity (or with the `log` feature, log it
+    /// at that level). `timeit!` also takes the level's name
+    /// ```ignore
+    /// timeit!(fetch_user(42); level = Level::Warn);
+    /// timeit!(fetch_user(42); level = "warn");
+    /// ```
+    /// > [WARN] 'fetch_user' took 12.0 ms
+    pub fn level(&mut self, level: Level) -> &mut Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Measure (and record) as usual, but print nothing
+    /// ```ignore
+    /// timeit!(fetch_user(42); quiet = true);
+    /// ```
+    pub fn quiet(&mut self, quiet: bool) -> &mut Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Print a single measurement in this unit, instead of the crate-wide default
+    /// ([`Unit::DEFAULT`]), either by name or as a [`Unit`]
+    /// ```ignore
+    /// timeit!(hash_block(&block); unit = "us");
+    /// timeit!(hash_block(&block); unit = Unit::Micros);
+    /// ```
+    /// > 'hash_block' took 182.310 µs
+    ///
+    /// Unknown names are rejected at compile time.
+    ///
+    /// Summaries of repeated runs pick a unit per value.
+    pub fn unit(&mut self, unit: Unit) -> &mut Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Call `on_start` right before the clock starts, e.g. to open a zone in an
+    /// external profiler that needs explicit begin and end events
+    /// ```ignore
+    /// timeit!(render(); on_start = |label, start| nvtx::range_push(label.unwrap_or("?")));
+    /// ```
+    /// It runs once, before the first measured run, and its own run time isn't measured.
+    /// Being a plain `fn`, closures passed here can't capture anything.
+    pub fn on_start(&mut self, on_start: OnStart) -> &mut Self {
+        self.on_start = Some(on_start);
+        self
+    }
+
+    /// Call `on_complete` with the label and elapsed time (the mean, over repeated
+    /// runs) of the measurement, instead of printing it
+    /// ```ignore
+    /// timeit!(fetch_user(42); on_complete = |name, dur| metrics.record(name, dur));
+    /// ```
+    /// Unlike a [`reporter`](Self::reporter), the closure can borrow from the
+    /// caller. The label is empty for anonymous expressions, and measurements
+    /// under the [`threshold`](Self::threshold) are skipped as usual.
+    pub fn on_complete(&mut self, on_complete: &'a dyn Fn(&str, Duration)) -> &mut Self {
+        self.on_complete = Some(OnCompleteRef(on_complete));
+        self
+    }
+
+    /// Hand the measurement to `reporter` instead of the process-wide one
+    /// (see [`set_reporter`](crate::set_reporter))
+    /// ```ignore
+    /// timeit!(fetch_user(42); reporter = &METRICS);
+    /// ```
+    pub fn reporter(&mut self, reporter: &'static dyn Reporter) -> &mut Self {
+        self.reporter = Some(ReporterRef(reporter));
+        self
+    }
+
+    /// Lay out the finishing line with a template, to match existing log
+    /// conventions
+    /// ```ignore
+    /// timeit!(fetch_user(42); fmt = "[perf] {name}: {elapsed_ms}ms");
+    /// ```
+    /// > [perf] fetch_user: 12.042ms
+    ///
+    /// Placeholders are `{name}`, `{group}`, `{outcome}` (`Ok`/`Err`, or empty),
+    /// `{elapsed}` (in the [`unit`](Self::unit)), `{elapsed_ms}` (with 3 decimals),
+    /// `{elapsed_us}` and `{elapsed_ns}`, with `{{` and `}}` for literal braces.
+    /// Over repeated runs, the elapsed time is the mean. Literal templates given
+    /// to `timeit!` are checked at compile time.
+    pub fn fmt(&mut self, template: &'static str) -> &mut Self {
+        self.fmt = Some(template);
+        self
+    }
+
+    /// Write the measurement as a JSON object on a line of its own, for log
+    /// pipelines to ingest, rather than as text (see [`set_json`] for every call)
+    /// ```ignore
+    /// timeit!(wait_for_it(); json = true);
+    /// ```
+    /// > {"name":"wait_for_it","elapsed_us":2002123,"ts":1760606400123}
+    ///
+    /// `ts` is the time the line was written, in milliseconds since the Unix
+    /// epoch. `outcome`, `group`, `id` (with [`correlate`](Self::correlate))
+    /// and `level` are there when they apply, and repeated runs add `runs`,
+    /// `warmup`, `min_us`, `max_us`, `stddev_us`, `p50_us`, `p90_us`, `p99_us`
+    /// and `outliers`, `elapsed_us` being the mean. `cpu_user_us` and
+    /// `cpu_sys_us` (with [`clock`](Self::clock)) and `allocs` and
+    /// `alloc_bytes` (with the
+    /// `allocations` feature) are per run too. A [`fmt`](Self::fmt) template
+    /// takes precedence.
+    pub fn json(&mut self, json: bool) -> &mut Self {
+        self.json = json;
+        self
+    }
+
+    /// Start the line with when it was written, and from which process and
+    /// thread, so the interleaved output of a multi-threaded program can be
+    /// told apart (see [`set_metadata`] for every call)
+    /// ```ignore
+    /// timeit!(fetch_user(42); metadata = true);
+    /// ```
+    /// > ts=2026-10-16T09:20:00.123456Z pid=4242 tid=3 thread=worker-1 'fetch_user' took 12.0 ms
+    ///
+    /// The timestamp is UTC, and `thread` is left out for unnamed threads. JSON
+    /// lines get `pid`, `tid` and `thread` fields instead.
+    pub fn metadata(&mut self, metadata: bool) -> &mut Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Also measure the CPU time the thread spent on the expression, to tell
+    /// whether it was busy computing or just waiting (on I/O, a lock, a sleep)
+    /// ```ignore
+    /// timeit!(fetch_user(42); clock = "cpu");
+    /// ```
+    /// > 'fetch_user' took 12.0 ms (user 250 µs, sys 60 µs)
+    ///
+    /// `user` is the thread's time running the expression's own code, `sys`
+    /// the kernel's time working for it (syscalls, page faults). Work the
+    /// expression hands off to other threads doesn't count. Repeated runs
+    /// report the mean CPU time per run. Needs the `cpu-time` feature, without
+    /// which `clock = "cpu"` doesn't compile. On platforms without per-thread
+    /// CPU usage (anything but Linux, FreeBSD and OpenBSD, for now), and with
+    /// a `ClockKind::Cpu` chosen at runtime but no `cpu-time`, only the wall
+    /// clock is reported.
+    pub fn clock(&mut self, clock: ClockKind) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_by_env() {
+        assert!(!disables(None));
+        assert!(!disables(Some("0")));
+        assert!(!disables(Some("false")));
+        assert!(disables(Some("1")));
+        assert!(disables(Some("yes")));
+    }
+}
@@ -0,0 +1,105 @@
+//! Labeled measurements as a Prometheus histogram, see [`register`]
+//!
+//! Once registered, every labeled `timeit!` (and [`TimeitGuard`](crate::TimeitGuard),
+//! and request timed by the `http` layer) is observed in the
+//! `timeit_duration_seconds` histogram, under its `label` and `outcome` (`ok`,
+//! `err`, or empty when the expression isn't a `Result`):
+//! ```text
+//! timeit_duration_seconds_bucket{label="fetch_user",outcome="ok",le="0.005"} 1187
+//! timeit_duration_seconds_sum{label="fetch_user",outcome="ok"} 3.204
+//! timeit_duration_seconds_count{label="fetch_user",outcome="ok"} 1200
+//! ```
+//! Its `_count` is the number of calls, so no separate counter is needed.
+//! Each distinct label is a series of its own: keep them to a bounded set
+//! like function or route names.
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use ::prometheus::{HistogramOpts, HistogramVec, Registry, Result, DEFAULT_BUCKETS};
+
+use crate::Outcome;
+
+static DURATIONS: OnceLock<HistogramVec> = OnceLock::new();
+
+/// Observe labeled measurements in `timeit_duration_seconds` from now on,
+/// registered with `registry` (like the one served on `/metrics`), with
+/// Prometheus' default buckets of 5 ms to 10 s
+/// ```
+/// timeit::prometheus::register(prometheus::default_registry())?;
+/// # Ok::<(), prometheus::Error>(())
+/// ```
+pub fn register(registry: &Registry) -> Result<()> {
+    register_with_buckets(registry, DEFAULT_BUCKETS.to_vec())
+}
+
+/// [`register`] with buckets of your own, upper bounds in seconds
+///
+/// The histogram is created on the first call, so the buckets of later calls
+/// (registering it with other registries) are ignored.
+pub fn register_with_buckets(registry: &Registry, buckets: Vec<f64>) -> Result<()> {
+    let durations = match DURATIONS.get() {
+        Some(durations) => durations.clone(),
+        None => {
+            let opts = HistogramOpts::new(
+                "timeit_duration_seconds",
+                "Time taken by the expressions measured with timeit",
+            )
+            .buckets(buckets);
+            let durations = HistogramVec::new(opts, &["label", "outcome"])?;
+            // Another thread may have won the race, in which case its histogram is used
+            DURATIONS.get_or_init(|| durations).clone()
+        }
+    };
+    registry.register(Box::new(durations))
+}
+
+/// Observe a measurement, if a histogram was registered
+pub(crate) fn observe(label: &str, outcome: Option<Outcome>, elapsed: Duration) {
+    if let Some(durations) = DURATIONS.get() {
+        let outcome = match outcome {
+            Some(Outcome::Ok) => "ok",
+            Some(Outcome::Err) => "err",
+            None => "",
+        };
+        durations
+            .with_label_values(&[label, outcome])
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::prometheus::{Encoder, TextEncoder};
+
+    #[test]
+    fn test_register() {
+        let registry = Registry::new();
+        register_with_buckets(&registry, vec![0.01, 0.1]).unwrap();
+        // The same histogram, with another registry's own metrics
+        let other = Registry::new();
+        register(&other).unwrap();
+        assert!(register(&other).is_err());
+
+        let label = "prometheus::fetch";
+        observe(label, Some(Outcome::Ok), Duration::from_millis(5));
+        observe(label, Some(Outcome::Ok), Duration::from_millis(50));
+        observe(label, None, Duration::from_secs(1));
+
+        let mut text = Vec::new();
+        TextEncoder::new()
+            .encode(&registry.gather(), &mut text)
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines = [
+            r#"_bucket{label="prometheus::fetch",outcome="ok",le="0.01"} 1"#,
+            r#"_bucket{label="prometheus::fetch",outcome="ok",le="0.1"} 2"#,
+            r#"_count{label="prometheus::fetch",outcome="ok"} 2"#,
+            r#"_count{label="prometheus::fetch",outcome=""} 1"#,
+        ];
+        for line in &lines {
+            let line = format!("timeit_duration_seconds{}", line);
+            assert!(text.lines().any(|l| l == line), "{} not in\n{}", line, text);
+        }
+    }
+}
@@ -0,0 +1,594 @@
+//! Process-wide aggregation of timings, keyed by label
+//!
+//! Every labeled `timeit!` call records into this registry when the
+//! `registry` feature is enabled. `Result`-returning expressions are
+//! aggregated separately for the `Ok` and `Err` paths.
+//!
+//! With the `hdrhistogram` feature each label is also backed by an
+//! [HDR histogram](https://docs.rs/hdrhistogram) of nanosecond samples,
+//! available via [`histogram()`] for percentile queries and comparisons between runs.
+//! [`distribution()`] renders the percentiles of every label as a table (which
+//! [`summary()`](crate::summary) prints too), and [`write_hgrm`] exports a
+//! label's full distribution for the
+//! [HdrHistogram plotter](https://hdrhistogram.github.io/HdrHistogram/plotFiles.html).
+//!
+//! With the `arrow` feature every raw sample is kept as well, for
+//! [exporting](crate::export) to Arrow IPC or Parquet files. Long-running
+//! processes can cap how many are kept per label with [`set_sample_cap`]: past
+//! the cap, a uniform random subset of everything recorded is kept (reservoir
+//! sampling), so the export stays representative of the whole run.
+//!
+//! Millions of trivial measurements can be kept out of the aggregates with
+//! [`set_floor`], either dropped or only counted per label (see [`fast()`]).
+//!
+//! CLI tools and build steps run over and over can [`save`] what each run
+//! measured to a file, and [`load`] it back to see how the current run compares
+//! with earlier ones ("20% slower than the 7-day average").
+//!
+//! [`dump()`] renders every label as a table, slowest in total first (and
+//! [`summary()`](crate::summary) prints it):
+//! ```text
+//! label              calls       total        mean         max
+//! fetch_user          1200        3.2s       2.7ms      41.0ms
+//! parse_config           1      12.0ms      12.0ms      12.0ms
+//! ```
+use std::cmp::Reverse;
+#[cfg(feature = "arrow")]
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+#[cfg(feature = "arrow")]
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "hdrhistogram")]
+use std::io;
+#[cfg(feature = "arrow")]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(feature = "hdrhistogram")]
+use hdrhistogram::Histogram;
+
+pub use crate::history::{load, save, History};
+use crate::{bench, intern, Outcome};
+
+type Key = (&'static str, Option<Outcome>);
+
+static REGISTRY: Mutex<BTreeMap<Key, Entry>> = Mutex::new(BTreeMap::new());
+/// Measurements under the floor, by label, when they're counted
+static FAST: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+#[cfg(feature = "arrow")]
+static SAMPLE_CAP: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Keep at most `cap` raw samples per label (and outcome), from now on
+///
+/// Once a label has recorded more than `cap` samples, each new one replaces a
+/// random kept sample with probability `cap / count`, so the kept samples are
+/// always a uniform random subset of all the label's samples. Stats and
+/// histograms still cover every sample. A cap of zero keeps no samples at all.
+/// Unlimited by default.
+#[cfg(feature = "arrow")]
+pub fn set_sample_cap(cap: usize) {
+    SAMPLE_CAP.store(cap, Ordering::Relaxed);
+}
+
+/// The [`Floor`], packed so a record never sees the floor of one `set_floor`
+/// call and the mode of another
+static FLOOR: AtomicU64 = AtomicU64::new(0);
+
+/// What happens to measurements shorter than the [floor](set_floor)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BelowFloor {
+    /// Not recorded at all
+    Drop,
+    /// Only counted, see [`fast()`]
+    Count,
+}
+
+/// Leave measurements shorter than `floor` out of the aggregates (stats,
+/// histograms and raw samples), from now on
+///
+/// ```ignore
+/// timeit::registry::set_floor(Duration::from_micros(1), BelowFloor::Count);
+/// ```
+/// A floor of zero (the default) records everything.
+pub fn set_floor(floor: Duration, below: BelowFloor) {
+    FLOOR.store(Floor { floor, below }.packed(), Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Floor {
+    floor: Duration,
+    below: BelowFloor,
+}
+
+impl Floor {
+    /// Set in a packed floor for [`BelowFloor::Count`], the other bits being nanoseconds
+    const COUNT_BELOW: u64 = 1 << 63;
+
+    fn current() -> Self {
+        Self::unpack(FLOOR.load(Ordering::Relaxed))
+    }
+
+    fn packed(self) -> u64 {
+        // Floors over 292 years are as good as infinite
+        let nanos = self.floor.as_nanos().min(u128::from(!Self::COUNT_BELOW)) as u64;
+        let below = match self.below {
+            BelowFloor::Count => Self::COUNT_BELOW,
+            BelowFloor::Drop => 0,
+        };
+        nanos | below
+    }
+
+    fn unpack(packed: u64) -> Self {
+        let below = match packed & Self::COUNT_BELOW != 0 {
+            true => BelowFloor::Count,
+            false => BelowFloor::Drop,
+        };
+        Self {
+            floor: Duration::from_nanos(packed & !Self::COUNT_BELOW),
+            below,
+        }
+    }
+}
+
+struct Entry {
+    stats: Stats,
+    #[cfg(feature = "hdrhistogram")]
+    histogram: Histogram<u64>,
+    #[cfg(feature = "arrow")]
+    samples: Vec<Duration>,
+    /// xorshift64 state for picking which samples to keep
+    #[cfg(feature = "arrow")]
+    rng: u64,
+}
+
+impl Entry {
+    fn new(elapsed: Duration) -> Self {
+        #[cfg(feature = "hdrhistogram")]
+        let histogram = {
+            // 3 significant figures, auto-resizing to fit whatever gets recorded
+            let mut histogram = Histogram::new(3).expect("3 sigfigs is valid");
+            record_nanos(&mut histogram, elapsed);
+            histogram
+        };
+        Self {
+            stats: Stats::new(elapsed),
+            #[cfg(feature = "hdrhistogram")]
+            histogram,
+            #[cfg(feature = "arrow")]
+            // The first sample of a reservoir, unless it keeps none
+            samples: match SAMPLE_CAP.load(Ordering::Relaxed) {
+                0 => Vec::new(),
+                _ => vec![elapsed],
+            },
+            #[cfg(feature = "arrow")]
+            rng: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.stats.merge(&Stats::new(elapsed));
+        #[cfg(feature = "hdrhistogram")]
+        record_nanos(&mut self.histogram, elapsed);
+        #[cfg(feature = "arrow")]
+        self.sample(elapsed, SAMPLE_CAP.load(Ordering::Relaxed));
+    }
+
+    /// Reservoir sampling (Vitter's Algorithm R), `stats` already counting `elapsed`
+    #[cfg(feature = "arrow")]
+    fn sample(&mut self, elapsed: Duration, cap: usize) {
+        if self.samples.len() < cap {
+            self.samples.push(elapsed);
+            return;
+        }
+        let slot = (self.next_u64() % self.stats.count) as usize;
+        if slot < cap {
+            self.samples[slot] = elapsed;
+        }
+    }
+
+    #[cfg(feature = "arrow")]
+    fn next_u64(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+#[cfg(feature = "hdrhistogram")]
+fn record_nanos(histogram: &mut Histogram<u64>, elapsed: Duration) {
+    let nanos = elapsed.as_nanos() as u64;
+    // Only fails if the value can't fit even after resizing
+    if histogram.record(nanos).is_err() {
+        histogram.saturating_record(nanos);
+    }
+}
+
+/// Aggregated timings for a single label
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    fn new(elapsed: Duration) -> Self {
+        Self {
+            count: 1,
+            total: elapsed,
+            min: elapsed,
+            max: elapsed,
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &Stats) {
+        self.count += other.count;
+        self.total += other.total;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn mean(&self) -> Duration {
+        bench::mean(self.total, self.count)
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<Key, Entry>> {
+    // A panic while holding the lock can't leave the map in a bad state
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record a single measurement
+pub fn record(label: &str, outcome: Option<Outcome>, elapsed: Duration) {
+    record_above(Floor::current(), label, outcome, elapsed);
+}
+
+fn record_above(floor: Floor, label: &str, outcome: Option<Outcome>, elapsed: Duration) {
+    if elapsed < floor.floor {
+        if floor.below == BelowFloor::Count {
+            let mut fast = FAST.lock().unwrap_or_else(|e| e.into_inner());
+            *fast.entry(intern::intern(label)).or_default() += 1;
+        }
+        return;
+    }
+    lock()
+        .entry((intern::intern(label), outcome))
+        .and_modify(|e| e.record(elapsed))
+        .or_insert_with(|| Entry::new(elapsed));
+}
+
+/// Aggregated timings for a label, across all outcomes
+pub fn stats(label: &str) -> Option<Stats> {
+    lock()
+        .iter()
+        .filter(|((l, _), _)| *l == label)
+        .map(|(_, e)| e.stats)
+        .fold(None, |acc: Option<Stats>, s| match acc {
+            Some(mut acc) => {
+                acc.merge(&s);
+                Some(acc)
+            }
+            None => Some(s),
+        })
+}
+
+/// Every label and outcome with its timings, in label order
+pub(crate) fn for_each_stats(mut f: impl FnMut(&str, Option<Outcome>, Stats)) {
+    for ((label, outcome), entry) in lock().iter() {
+        f(label, *outcome, entry.stats);
+    }
+}
+
+/// How many measurements of a label were under the floor, and only counted
+pub fn fast(label: &str) -> u64 {
+    let fast = FAST.lock().unwrap_or_else(|e| e.into_inner());
+    fast.get(label).copied().unwrap_or_default()
+}
+
+/// Aggregated timings for only the `Ok` or `Err` path of a label
+pub fn stats_for(label: &str, outcome: Outcome) -> Option<Stats> {
+    lock()
+        .get(&(intern::lookup(label)?, Some(outcome)))
+        .map(|e| e.stats)
+}
+
+/// Distribution of a label's timings (in nanoseconds), across all outcomes
+///
+/// ```ignore
+/// let hist = timeit::registry::histogram("fetch_user").unwrap();
+/// println!("p99: {} ns", hist.value_at_quantile(0.99));
+/// ```
+#[cfg(feature = "hdrhistogram")]
+pub fn histogram(label: &str) -> Option<Histogram<u64>> {
+    let registry = lock();
+    let mut matching = registry.iter().filter(|((l, _), _)| *l == label);
+    let mut histogram = matching.next()?.1.histogram.clone();
+    for (_, entry) in matching {
+        histogram
+            .add(&entry.histogram)
+            .expect("auto-resizing histograms can always be added");
+    }
+    Some(histogram)
+}
+
+/// Distribution of only the `Ok` or `Err` path of a label
+#[cfg(feature = "hdrhistogram")]
+pub fn histogram_for(label: &str, outcome: Outcome) -> Option<Histogram<u64>> {
+    lock()
+        .get(&(intern::lookup(label)?, Some(outcome)))
+        .map(|e| e.histogram.clone())
+}
+
+/// The percentiles of every label (across outcomes) as a table, in the order
+/// of [`dump()`]
+/// ```text
+/// label                p50         p90         p99       p99.9         max
+/// fetch_user         2.1ms       3.0ms      12.0ms      40.1ms      41.0ms
+/// parse_config      12.0ms      12.0ms      12.0ms      12.0ms      12.0ms
+/// ```
+#[cfg(feature = "hdrhistogram")]
+pub fn distribution() -> String {
+    const QUANTILES: [(&str, f64); 5] = [
+        ("p50", 0.5),
+        ("p90", 0.9),
+        ("p99", 0.99),
+        ("p99.9", 0.999),
+        ("max", 1.0),
+    ];
+    let labels = all();
+    let width = labels
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let mut out = format!("{:<width$}", "label", width = width);
+    for (name, _) in &QUANTILES {
+        let _ = write!(out, "  {:>10}", name);
+    }
+    out.push('\n');
+    for (label, _) in &labels {
+        let Some(histogram) = histogram(label) else {
+            continue;
+        };
+        let _ = write!(out, "{:<width$}", label, width = width);
+        for (_, quantile) in &QUANTILES {
+            let nanos = histogram.value_at_quantile(*quantile);
+            let _ = write!(out, "  {:>10.1?}", Duration::from_nanos(nanos));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Write the distribution of a label's timings (across outcomes) in the
+/// percentile format of HdrHistogram's `outputPercentileDistribution`, in
+/// milliseconds, as read by plotting tools for `.hgrm` files
+/// ```no_run
+/// let file = std::fs::File::create("fetch_user.hgrm")?;
+/// timeit::registry::write_hgrm("fetch_user", file)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+/// Fails with [`NotFound`](io::ErrorKind::NotFound) if nothing was recorded for the label.
+#[cfg(feature = "hdrhistogram")]
+pub fn write_hgrm(label: &str, mut out: impl io::Write) -> io::Result<()> {
+    let histogram = histogram(label).ok_or_else(|| {
+        let msg = format!("no timings recorded for {:?}", label);
+        io::Error::new(io::ErrorKind::NotFound, msg)
+    })?;
+    let ms = |nanos: f64| nanos / 1e6;
+    writeln!(
+        out,
+        "{:>12} {:>14} {:>10} {:>14}\n",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    )?;
+    let mut total = 0;
+    // 5 steps each time the distance to 100% halves, like HdrHistogram's own output
+    for step in histogram.iter_quantiles(5) {
+        total += step.count_since_last_iteration();
+        let value = ms(step.value_iterated_to() as f64);
+        let quantile = step.quantile_iterated_to();
+        write!(out, "{:12.3} {:2.12} {:10}", value, quantile, total)?;
+        if quantile < 1.0 {
+            write!(out, " {:14.2}", 1.0 / (1.0 - quantile))?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(
+        out,
+        "#[Mean    = {:12.3}, StdDeviation   = {:12.3}]",
+        ms(histogram.mean()),
+        ms(histogram.stdev())
+    )?;
+    writeln!(
+        out,
+        "#[Max     = {:12.3}, Total count    = {:12}]",
+        ms(histogram.max() as f64),
+        histogram.len()
+    )
+}
+
+/// Visit every raw sample, in label order
+#[cfg(feature = "arrow")]
+pub(crate) fn for_each_sample(mut f: impl FnMut(&str, Option<Outcome>, Duration)) {
+    for ((label, outcome), entry) in lock().iter() {
+        for elapsed in &entry.samples {
+            f(label, *outcome, *elapsed);
+        }
+    }
+}
+
+/// Aggregated timings of every label (across outcomes), slowest in total first
+pub fn all() -> Vec<(&'static str, Stats)> {
+    let mut labels: Vec<(&'static str, Stats)> = Vec::new();
+    // Keys are ordered by label first, so each label's outcomes are adjacent
+    for ((label, _), entry) in lock().iter() {
+        match labels.last_mut() {
+            Some((last, stats)) if last == label => stats.merge(&entry.stats),
+            _ => labels.push((label, entry.stats)),
+        }
+    }
+    labels.sort_by_key(|(_, stats)| Reverse(stats.total));
+    labels
+}
+
+/// Render [`all()`] as a table
+pub fn dump() -> String {
+    let labels = all();
+    let width = labels
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let mut out = format!(
+        "{:<width$}  {:>8}  {:>10}  {:>10}  {:>10}\n",
+        "label",
+        "calls",
+        "total",
+        "mean",
+        "max",
+        width = width
+    );
+    for (label, stats) in &labels {
+        let _ = writeln!(
+            out,
+            "{:<width$}  {:>8}  {:>10.1?}  {:>10.1?}  {:>10.1?}",
+            label,
+            stats.count,
+            stats.total,
+            stats.mean(),
+            stats.max,
+            width = width
+        );
+    }
+    out
+}
+
+/// Clear all recorded timings
+pub fn reset() {
+    lock().clear();
+    FAST.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean() {
+        // More calls than fit in a `u32`
+        let stats = Stats {
+            count: 5_000_000_000,
+            total: Duration::from_secs(10_000),
+            min: Duration::from_nanos(1),
+            max: Duration::from_millis(1),
+        };
+        assert_eq!(stats.mean(), Duration::from_micros(2));
+    }
+
+    #[test]
+    fn test_floor() {
+        for floor in [
+            Floor {
+                floor: Duration::from_micros(1),
+                below: BelowFloor::Count,
+            },
+            Floor {
+                floor: Duration::from_nanos(999),
+                below: BelowFloor::Drop,
+            },
+        ] {
+            assert_eq!(Floor::unpack(floor.packed()), floor);
+        }
+        let huge = Floor::unpack(
+            Floor {
+                floor: Duration::MAX,
+                below: BelowFloor::Drop,
+            }
+            .packed(),
+        );
+        assert_eq!(huge.below, BelowFloor::Drop);
+        assert!(huge.floor > Duration::from_secs(200 * 365 * 86_400));
+
+        let floor = |below| Floor {
+            floor: Duration::from_micros(1),
+            below,
+        };
+        let fast = Duration::from_nanos(200);
+        let slow = Duration::from_micros(5);
+
+        record_above(floor(BelowFloor::Drop), "registry::dropped", None, fast);
+        record_above(floor(BelowFloor::Drop), "registry::dropped", None, slow);
+        assert_eq!(stats("registry::dropped").unwrap().count, 1);
+        assert_eq!(super::fast("registry::dropped"), 0);
+
+        record_above(floor(BelowFloor::Count), "registry::counted", None, fast);
+        record_above(floor(BelowFloor::Count), "registry::counted", None, fast);
+        assert_eq!(stats("registry::counted"), None);
+        assert_eq!(super::fast("registry::counted"), 2);
+        record_above(floor(BelowFloor::Count), "registry::counted", None, slow);
+        assert_eq!(stats("registry::counted").unwrap().min, slow);
+    }
+
+    #[test]
+    #[cfg(feature = "hdrhistogram")]
+    fn test_distribution() {
+        for ms in 1..=100 {
+            let outcome = [Outcome::Ok, Outcome::Err][(ms % 2) as usize];
+            record("registry::hgrm", Some(outcome), Duration::from_millis(ms));
+        }
+        let table = distribution();
+        let row = table
+            .lines()
+            .find(|line| line.starts_with("registry::hgrm "))
+            .unwrap();
+        let columns: Vec<_> = row.split_whitespace().collect();
+        let percentiles = ["50.0ms", "90.0ms", "99.0ms", "100.0ms", "100.0ms"];
+        assert_eq!(columns[1..], percentiles);
+
+        let mut hgrm = Vec::new();
+        write_hgrm("registry::hgrm", &mut hgrm).unwrap();
+        let hgrm = String::from_utf8(hgrm).unwrap();
+        let lines: Vec<_> = hgrm.lines().collect();
+        assert!(lines[0].starts_with("       Value     Percentile TotalCount"));
+        assert!(lines[2].starts_with("       1.000 0.000000000000          1           1.00"));
+        let last = lines[lines.len() - 3];
+        assert!(last.starts_with("     100.008 1.000000000000        100"));
+        assert!(lines[lines.len() - 1].ends_with("Total count    =          100]"));
+
+        let missing = write_hgrm("registry::nothing", Vec::new()).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    #[cfg(feature = "arrow")]
+    fn test_reservoir() {
+        let cap = 100;
+        let mut entry = Entry::new(Duration::from_nanos(1));
+        for nanos in 2..=10_000 {
+            let elapsed = Duration::from_nanos(nanos);
+            entry.stats.merge(&Stats::new(elapsed));
+            entry.sample(elapsed, cap);
+        }
+        assert_eq!(entry.stats.count, 10_000);
+        assert_eq!(entry.samples.len(), cap);
+        // Kept from across the whole run, not just the first (or last) samples
+        let late = entry
+            .samples
+            .iter()
+            .filter(|elapsed| elapsed.as_nanos() > 5_000)
+            .count();
+        assert!((20..=80).contains(&late), "{} of {} late", late, cap);
+
+        entry.samples.clear();
+        entry.sample(Duration::from_nanos(1), 0);
+        assert!(entry.samples.is_empty());
+    }
+}
@@ -0,0 +1,1040 @@
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "allocations")]
+use crate::allocations::Allocations;
+use crate::bench::{self, Summary};
+use crate::clock;
+use crate::cpu::{self, CpuTime};
+use crate::group;
+use crate::json::{Elapsed, Record};
+use crate::metadata::Metadata;
+use crate::options::{
+    self, ClockKind, Iterations, Level, OnCompleteRef, OnStart, ReporterRef, Unit,
+};
+#[cfg(feature = "registry")]
+use crate::registry;
+use crate::reporter;
+use crate::template::Templated;
+use crate::{Options, Outcome};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// How many measurements are in progress on this thread
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// How deep a measurement is nested in others on its thread, for as long as
+/// it's in progress
+struct Depth(usize);
+
+impl Depth {
+    fn enter() -> Self {
+        Depth(DEPTH.with(|depth| depth.replace(depth.get() + 1)))
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        // Restore rather than decrement, in case an inner one was leaked
+        DEPTH.with(|depth| depth.set(self.0));
+    }
+}
+
+/// How the timed expression is named in the output
+#[derive(Clone, Copy, Debug)]
+pub enum Label<'a> {
+    /// > 'wait_for_it' took 2.00 s
+    Function(&'a str),
+    /// > My Func took 2.00 s
+    Described(&'a str),
+    /// > Took 2.00 s
+    Anonymous,
+}
+
+impl<'a> Label<'a> {
+    pub fn name(&self) -> Option<&'a str> {
+        match self {
+            Label::Function(name) | Label::Described(name) => Some(name),
+            Label::Anonymous => None,
+        }
+    }
+
+    /// `'name' verb` or `Verb` for anonymous expressions
+    fn with_verb(self, verb: &'static str) -> WithVerb<'a> {
+        WithVerb(self, verb)
+    }
+}
+
+/// Length of [`path_name`], for sizing its output
+pub const fn path_len(raw: &str) -> usize {
+    let bytes = raw.as_bytes();
+    let (mut i, mut len) = (0, 0);
+    while i < bytes.len() {
+        if keep_byte(bytes, i) {
+            len += 1;
+        }
+        i += 1;
+    }
+    len
+}
+
+/// A `stringify!`ed path without the spaces between its tokens, so
+/// `parse :: < u64 >` reads `parse::<u64>` (spaces between words are kept)
+pub const fn path_name<const N: usize>(raw: &str) -> [u8; N] {
+    let bytes = raw.as_bytes();
+    let mut name = [0; N];
+    let (mut i, mut len) = (0, 0);
+    while i < bytes.len() {
+        if keep_byte(bytes, i) {
+            name[len] = if bytes[i].is_ascii_whitespace() {
+                b' '
+            } else {
+                bytes[i]
+            };
+            len += 1;
+        }
+        i += 1;
+    }
+    name
+}
+
+const fn keep_byte(bytes: &[u8], i: usize) -> bool {
+    if !bytes[i].is_ascii_whitespace() {
+        return true;
+    }
+    if i == 0 || bytes[i - 1].is_ascii_whitespace() {
+        return false;
+    }
+    let mut next = i + 1;
+    while next < bytes.len() && bytes[next].is_ascii_whitespace() {
+        next += 1;
+    }
+    next < bytes.len() && is_word_byte(bytes[i - 1]) && is_word_byte(bytes[next])
+}
+
+const fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'\'' || !byte.is_ascii()
+}
+
+struct WithVerb<'a>(Label<'a>, &'static str);
+
+impl fmt::Display for WithVerb<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Label::Function(name) => write!(f, "'{}' {}", name, self.1),
+            Label::Described(desc) => write!(f, "{} {}", desc, self.1),
+            Label::Anonymous => {
+                let mut chars = self.1.chars();
+                if let Some(first) = chars.next() {
+                    write!(f, "{}{}", first.to_uppercase(), chars.as_str())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Autoref specialization: `(&Probe(&res)).outcome()` picks [`ResultOutcome`]
+/// when `res` is a `Result`, and falls back to [`AnyOutcome`] otherwise
+pub struct Probe<'a, T>(pub &'a T);
+
+pub trait ResultOutcome {
+    fn outcome(&self) -> Option<Outcome>;
+}
+
+impl<T, E> ResultOutcome for Probe<'_, Result<T, E>> {
+    fn outcome(&self) -> Option<Outcome> {
+        match self.0 {
+            Ok(_) => Some(Outcome::Ok),
+            Err(_) => Some(Outcome::Err),
+        }
+    }
+}
+
+pub trait AnyOutcome {
+    fn outcome(&self) -> Option<Outcome>;
+}
+
+impl<T> AnyOutcome for &Probe<'_, T> {
+    fn outcome(&self) -> Option<Outcome> {
+        None
+    }
+}
+
+/// An in-progress measurement (of one or more runs), started and finished by `timeit!`
+pub struct Timer<'a> {
+    /// Off, the timer only lets the expression run once, see [`crate::set_enabled`]
+    enabled: bool,
+    label: Label<'a>,
+    group: Option<String>,
+    depth: Depth,
+    id: Option<u64>,
+    iterations: Iterations,
+    target_error: f64,
+    max_time: Duration,
+    warmup: usize,
+    threshold: Duration,
+    level: Option<Level>,
+    quiet: bool,
+    unit: Unit,
+    on_start: Option<OnStart>,
+    on_complete: Option<OnCompleteRef<'a>>,
+    reporter: Option<ReporterRef>,
+    fmt: Option<&'static str>,
+    json: bool,
+    metadata: bool,
+    first_start: Option<Instant>,
+    start: Instant,
+    samples: Samples,
+    /// The thread's CPU time when the run began, with `clock = "cpu"`
+    cpu_start: Option<CpuTime>,
+    /// The CPU time of all runs so far, `None` unless it's measured
+    cpu: Option<CpuTime>,
+    /// The thread's allocations when the run began
+    #[cfg(feature = "allocations")]
+    allocs_start: Option<Allocations>,
+    /// The allocations of all runs so far, `None` without a counting allocator
+    #[cfg(feature = "allocations")]
+    allocs: Option<Allocations>,
+    #[cfg(feature = "observability")]
+    in_flight: Option<observability::InFlight>,
+    #[cfg(feature = "tracy")]
+    zone: Option<tracy_client::Span>,
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "opentelemetry")]
+    otel: Option<OtelSpan>,
+}
+
+impl<'a> Timer<'a> {
+    #[cfg_attr(any(feature = "tracy", feature = "opentelemetry"), track_caller)]
+    pub fn start(label: Label<'a>, opts: &Options<'a>) -> Self {
+        let enabled = options::is_enabled();
+        let group = if enabled { group::current() } else { None };
+        let depth = Depth::enter();
+        let quiet = opts.quiet || options::is_quiet();
+        let json = opts.json || options::is_json();
+        let metadata = opts.metadata || options::is_metadata();
+        let id = if enabled && opts.correlate {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let meta = (metadata && !quiet).then(Metadata::now);
+            if !quiet && json {
+                let record = Record {
+                    name: label.name(),
+                    group: group.as_deref(),
+                    id: Some(id),
+                    level: opts.level,
+                    outcome: None,
+                    elapsed: Elapsed::Started,
+                    cpu: None,
+                    #[cfg(feature = "allocations")]
+                    allocs: None,
+                    metadata: meta.as_ref(),
+                };
+                let reporter = reporter::current(opts.reporter.map(|r| r.0));
+                emit_untagged(&reporter, opts.level, format_args!("{}", record));
+            } else if !quiet {
+                let headline = Headline::new(depth.0, group.as_deref(), label, "started");
+                let reporter = reporter::current(opts.reporter.map(|r| r.0));
+                emit(
+                    &reporter,
+                    opts.level,
+                    meta.as_ref(),
+                    format_args!("{} (#{})", headline, id),
+                );
+            }
+            Some(id)
+        } else {
+            None
+        };
+        Self {
+            enabled,
+            label,
+            group,
+            depth,
+            id,
+            iterations: opts.iterations,
+            target_error: opts.target_error,
+            max_time: opts.max_time,
+            warmup: opts.warmup,
+            threshold: opts.threshold,
+            level: opts.level,
+            quiet,
+            unit: opts.unit,
+            on_start: opts.on_start,
+            on_complete: opts.on_complete,
+            reporter: opts.reporter,
+            fmt: opts.fmt,
+            json,
+            metadata,
+            first_start: None,
+            start: clock::now(),
+            samples: Samples::None,
+            cpu_start: None,
+            cpu: (opts.clock == ClockKind::Cpu).then(CpuTime::default),
+            #[cfg(feature = "allocations")]
+            allocs_start: None,
+            #[cfg(feature = "allocations")]
+            allocs: None,
+            #[cfg(feature = "observability")]
+            in_flight: enabled.then(|| {
+                observability::begin(
+                    observability::Kind::Timing,
+                    label.name().unwrap_or("<anonymous>"),
+                )
+            }),
+            #[cfg(feature = "tracy")]
+            zone: if enabled { tracy_zone(label) } else { None },
+            #[cfg(feature = "tracing")]
+            span: if enabled {
+                tracing_span(label, opts.level)
+            } else {
+                tracing::Span::none().entered()
+            },
+            #[cfg(feature = "opentelemetry")]
+            otel: if enabled {
+                Some(OtelSpan::start(label))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// How many unmeasured runs to do first
+    pub fn warmup(&self) -> usize {
+        if self.enabled {
+            self.warmup
+        } else {
+            0
+        }
+    }
+
+    /// Start timing a single run
+    pub fn begin(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        if let (None, Some(on_start)) = (self.first_start, self.on_start) {
+            on_start(self.label.name(), clock::now());
+        }
+        if self.cpu.is_some() {
+            self.cpu_start = cpu::thread_time();
+        }
+        #[cfg(feature = "allocations")]
+        {
+            self.allocs_start = Allocations::current();
+        }
+        self.start = clock::now();
+        self.first_start.get_or_insert(self.start);
+    }
+
+    /// Stop timing a single run, returns `true` once all iterations are done
+    pub fn end(&mut self) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        self.samples.push(clock::elapsed(self.start));
+        #[cfg(feature = "allocations")]
+        if let (Some(start), Some(now)) = (self.allocs_start, Allocations::current()) {
+            let total = self.allocs.unwrap_or_default();
+            self.allocs = Some(total + (now - start));
+        }
+        if let Some(total) = self.cpu {
+            // A platform without the clock leaves the CPU time out altogether
+            self.cpu = self
+                .cpu_start
+                .zip(cpu::thread_time())
+                .map(|(start, now)| total + now.since(start));
+        }
+        match self.iterations {
+            Iterations::Fixed(iterations) => self.samples.len() >= iterations,
+            Iterations::Auto => {
+                let measuring = self.first_start.map_or(Duration::from_secs(0), clock::elapsed);
+                measuring >= self.max_time
+                    || bench::is_confident(self.samples.as_slice(), self.target_error)
+            }
+        }
+    }
+
+    pub fn finish(self, outcome: Option<Outcome>) {
+        if !self.enabled {
+            return;
+        }
+        #[cfg(feature = "tracy")]
+        drop(self.zone);
+        #[cfg(feature = "tracing")]
+        {
+            let samples = self.samples.as_slice();
+            let mean = bench::mean(samples.iter().sum(), samples.len() as u64);
+            self.span.record("elapsed_ms", mean.as_secs_f64() * 1e3);
+            drop(self.span);
+        }
+        #[cfg(feature = "opentelemetry")]
+        if let Some(otel) = self.otel {
+            otel.end(self.samples.as_slice(), outcome);
+        }
+        let runs = self.samples.len() as u64;
+        let report = Report {
+            prefix: Prefix {
+                depth: self.depth.0,
+                group: self.group.as_deref(),
+                label: self.label,
+                id: self.id,
+            },
+            threshold: self.threshold,
+            warmup: self.warmup,
+            level: self.level,
+            quiet: self.quiet,
+            unit: self.unit,
+            fmt: self.fmt,
+            json: self.json,
+            metadata: self.metadata,
+            cpu: self.cpu.map(|total| total.per_run(runs)),
+            #[cfg(feature = "allocations")]
+            allocs: self.allocs.map(|total| total.per_run(runs)),
+            reporter: self.reporter,
+            on_complete: self.on_complete,
+            slowest: 0,
+        };
+        report.samples(self.samples.as_slice(), outcome);
+        #[cfg(feature = "registry")]
+        {
+            if let Some(name) = self.label.name() {
+                for elapsed in self.samples.as_slice() {
+                    registry::record(name, outcome, *elapsed);
+                }
+            }
+        }
+        #[cfg(feature = "prometheus")]
+        {
+            if let Some(name) = self.label.name() {
+                for elapsed in self.samples.as_slice() {
+                    crate::prometheus::observe(name, outcome, *elapsed);
+                }
+            }
+        }
+        #[cfg(feature = "observability")]
+        {
+            if let Some(name) = self.label.name() {
+                for elapsed in self.samples.as_slice() {
+                    observability::record(name, *elapsed);
+                }
+            }
+            drop(self.in_flight);
+        }
+    }
+}
+
+/// Report measurements timed outside of a [`Timer`] (across `.await`s, or between
+/// the `next()` calls of an iterator) like the runs of `timeit!`
+pub(crate) fn report(
+    label: Label,
+    opts: &Options,
+    samples: &[Duration],
+    outcome: Option<Outcome>,
+    slowest: usize,
+) {
+    let group = group::current();
+    let report = Report {
+        prefix: Prefix {
+            depth: 0,
+            group: group.as_deref(),
+            label,
+            id: None,
+        },
+        threshold: opts.threshold,
+        warmup: opts.warmup,
+        level: opts.level,
+        quiet: opts.quiet || options::is_quiet(),
+        unit: opts.unit,
+        fmt: opts.fmt,
+        json: opts.json || options::is_json(),
+        metadata: opts.metadata || options::is_metadata(),
+        // Spread over threads across `.await`s, or over the code in between, so
+        // not measured
+        cpu: None,
+        #[cfg(feature = "allocations")]
+        allocs: None,
+        reporter: opts.reporter,
+        on_complete: opts.on_complete,
+        slowest,
+    };
+    report.samples(samples, outcome);
+    #[cfg(feature = "registry")]
+    if let Some(name) = label.name() {
+        for elapsed in samples {
+            registry::record(name, outcome, *elapsed);
+        }
+    }
+    #[cfg(feature = "prometheus")]
+    if let Some(name) = label.name() {
+        for elapsed in samples {
+            crate::prometheus::observe(name, outcome, *elapsed);
+        }
+    }
+    #[cfg(feature = "observability")]
+    if let Some(name) = label.name() {
+        for elapsed in samples {
+            observability::record(name, *elapsed);
+        }
+    }
+}
+
+/// How a finished measurement is written out and handed over
+struct Report<'a> {
+    prefix: Prefix<'a>,
+    threshold: Duration,
+    warmup: usize,
+    level: Option<Level>,
+    quiet: bool,
+    unit: Unit,
+    fmt: Option<&'static str>,
+    json: bool,
+    metadata: bool,
+    /// The mean CPU time of a run, if it was measured
+    cpu: Option<CpuTime>,
+    /// The mean allocations of a run, if they were counted
+    #[cfg(feature = "allocations")]
+    allocs: Option<Allocations>,
+    reporter: Option<ReporterRef>,
+    on_complete: Option<OnCompleteRef<'a>>,
+    /// How many of the slowest samples to point out by their index, for the
+    /// items of a [`TimedIter`](crate::TimedIter)
+    slowest: usize,
+}
+
+impl Report<'_> {
+    fn record<'r>(
+        &'r self,
+        elapsed: Elapsed,
+        outcome: Option<Outcome>,
+        metadata: Option<&'r Metadata>,
+    ) -> Record<'r> {
+        Record {
+            name: self.prefix.label.name(),
+            group: self.prefix.group,
+            id: self.prefix.id,
+            level: self.level,
+            outcome,
+            elapsed,
+            cpu: self.cpu,
+            #[cfg(feature = "allocations")]
+            allocs: self.allocs,
+            metadata,
+        }
+    }
+
+    /// A single run, or the summary of several
+    fn samples(&self, samples: &[Duration], outcome: Option<Outcome>) {
+        let name = self.prefix.label.name().unwrap_or_default();
+        let reporter = reporter::current(self.reporter.map(|r| r.0));
+        let (group, unit) = (self.prefix.group, self.unit);
+        // The callback takes the place of the line
+        let quiet = self.quiet || self.on_complete.is_some();
+        let metadata = match quiet {
+            false if self.metadata => Some(Metadata::now()),
+            _ => None,
+        };
+        let meta = metadata.as_ref();
+        let templated = |template, elapsed| Templated {
+            template,
+            name,
+            group,
+            elapsed,
+            unit,
+            outcome,
+        };
+        let prefix = &self.prefix;
+        let extra = Extra {
+            unit,
+            cpu: self.cpu,
+            #[cfg(feature = "allocations")]
+            allocs: self.allocs,
+        };
+        match samples {
+            [elapsed] if *elapsed < self.threshold => {}
+            [elapsed] => {
+                let shown = self.unit.display(*elapsed);
+                match (outcome, self.fmt) {
+                    _ if quiet => {}
+                    (_, Some(template)) => emit(
+                        &reporter,
+                        self.level,
+                        meta,
+                        format_args!("{}", templated(template, *elapsed)),
+                    ),
+                    _ if self.json => emit_untagged(
+                        &reporter,
+                        self.level,
+                        format_args!("{}", self.record(Elapsed::Single(*elapsed), outcome, meta)),
+                    ),
+                    (Some(outcome), None) => emit(
+                        &reporter,
+                        self.level,
+                        meta,
+                        format_args!("{} {}{} ({})", prefix, shown, extra, outcome),
+                    ),
+                    (None, None) => emit(
+                        &reporter,
+                        self.level,
+                        meta,
+                        format_args!("{} {}{}", prefix, shown, extra),
+                    ),
+                }
+                reporter.report(name, *elapsed);
+                if let Some(on_complete) = self.on_complete {
+                    on_complete.0(name, *elapsed);
+                }
+            }
+            samples => match Summary::from_samples(samples) {
+                Some(mut summary) if summary.mean >= self.threshold => {
+                    summary.warmup = self.warmup;
+                    match self.fmt {
+                        _ if quiet => {}
+                        Some(template) => emit(
+                            &reporter,
+                            self.level,
+                            meta,
+                            format_args!("{}", templated(template, summary.mean)),
+                        ),
+                        None if self.json => emit_untagged(
+                            &reporter,
+                            self.level,
+                            format_args!(
+                                "{}",
+                                self.record(Elapsed::Summary(summary), outcome, meta)
+                            ),
+                        ),
+                        None => {
+                            let slowest = Slowest {
+                                samples,
+                                count: self.slowest,
+                            };
+                            emit(
+                                &reporter,
+                                self.level,
+                                meta,
+                                format_args!("{} {}{}{}", prefix, summary, extra, slowest),
+                            )
+                        }
+                    }
+                    reporter.report(name, summary.mean);
+                    if let Some(on_complete) = self.on_complete {
+                        on_complete.0(name, summary.mean);
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+/// A zone spanning every run of a measurement, at the `timeit!` call site
+///
+/// Like all Tracy zones it has to end on the thread it started on.
+#[cfg(feature = "tracy")]
+#[track_caller]
+fn tracy_zone(label: Label) -> Option<tracy_client::Span> {
+    let client = tracy_client::Client::running()?;
+    let location = std::panic::Location::caller();
+    let name = label.name().unwrap_or("timeit");
+    Some(client.span_alloc(Some(name), name, location.file(), location.line(), 0))
+}
+
+/// A span around every run of a measurement, entered until it's finished
+///
+/// Span names are fixed at compile time, so it's named `timeit` with the label
+/// in its `label` field, and in `otel.name` for collectors that rename spans by it
+/// (like `tracing-opentelemetry`).
+#[cfg(feature = "tracing")]
+fn tracing_span(label: Label, level: Option<Level>) -> tracing::span::EnteredSpan {
+    use tracing::field::Empty;
+
+    let name = label.name().unwrap_or("timeit");
+    // Levels are part of the span's static metadata too
+    macro_rules! span {
+        ($level:ident) => {
+            tracing::span!(
+                tracing::Level::$level,
+                "timeit",
+                otel.name = name,
+                label = name,
+                elapsed_ms = Empty
+            )
+        };
+    }
+    let span = match level.unwrap_or(Level::Info) {
+        Level::Error => span!(ERROR),
+        Level::Warn => span!(WARN),
+        Level::Info => span!(INFO),
+        Level::Debug => span!(DEBUG),
+        Level::Trace => span!(TRACE),
+    };
+    span.entered()
+}
+
+/// An OpenTelemetry span around every run of a measurement, at the `timeit!`
+/// call site
+///
+/// It's the current span until it ends, so spans started meanwhile (by nested
+/// measurements, or instrumented clients) become its children.
+#[cfg(feature = "opentelemetry")]
+struct OtelSpan {
+    cx: opentelemetry::Context,
+    _current: opentelemetry::ContextGuard,
+}
+
+#[cfg(feature = "opentelemetry")]
+impl OtelSpan {
+    #[track_caller]
+    fn start(label: Label) -> Self {
+        use opentelemetry::trace::{TraceContextExt, Tracer};
+        use opentelemetry::{global, Context, KeyValue};
+
+        let location = std::panic::Location::caller();
+        let tracer = global::tracer("timeit");
+        let span = tracer
+            .span_builder(label.name().unwrap_or("timeit").to_string())
+            .with_attributes([
+                KeyValue::new("code.file.path", location.file()),
+                KeyValue::new("code.line.number", i64::from(location.line())),
+            ])
+            .start(&tracer);
+        let cx = Context::current_with_span(span);
+        Self {
+            _current: cx.clone().attach(),
+            cx,
+        }
+    }
+
+    /// End the span with the mean of the runs in `timeit.elapsed_ms`, and an
+    /// error status for an `Err`
+    fn end(self, samples: &[Duration], outcome: Option<Outcome>) {
+        use opentelemetry::trace::{Status, TraceContextExt};
+        use opentelemetry::KeyValue;
+
+        let span = self.cx.span();
+        let mean = bench::mean(samples.iter().sum(), samples.len() as u64);
+        span.set_attribute(KeyValue::new("timeit.elapsed_ms", mean.as_secs_f64() * 1e3));
+        if samples.len() > 1 {
+            span.set_attribute(KeyValue::new("timeit.runs", samples.len() as i64));
+        }
+        if outcome == Some(Outcome::Err) {
+            span.set_status(Status::error("returned Err"));
+        }
+        span.end();
+    }
+}
+
+/// The samples of a measurement, kept inline for the common single run so
+/// timing one call never touches the heap
+enum Samples {
+    None,
+    One(Duration),
+    Many(Vec<Duration>),
+}
+
+impl Samples {
+    fn push(&mut self, elapsed: Duration) {
+        *self = match std::mem::replace(self, Samples::None) {
+            Samples::None => Samples::One(elapsed),
+            Samples::One(first) => Samples::Many(vec![first, elapsed]),
+            Samples::Many(mut samples) => {
+                samples.push(elapsed);
+                Samples::Many(samples)
+            }
+        };
+    }
+
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn as_slice(&self) -> &[Duration] {
+        match self {
+            Samples::None => &[],
+            Samples::One(elapsed) => std::slice::from_ref(elapsed),
+            Samples::Many(samples) => samples,
+        }
+    }
+}
+
+/// `group / 'name' verb`, without capitalizing the verb of anonymous labels
+/// when it follows a group, indented two spaces per level of nesting
+struct Headline<'a> {
+    depth: usize,
+    group: Option<&'a str>,
+    label: Label<'a>,
+    verb: &'static str,
+}
+
+impl<'a> Headline<'a> {
+    fn new(depth: usize, group: Option<&'a str>, label: Label<'a>, verb: &'static str) -> Self {
+        Self {
+            depth,
+            group,
+            label,
+            verb,
+        }
+    }
+}
+
+impl fmt::Display for Headline<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:1$}", "", self.depth * 2)?;
+        match (self.group, self.label) {
+            (Some(group), Label::Anonymous) => write!(f, "{} / {}", group, self.verb),
+            (Some(group), label) => write!(f, "{} / {}", group, label.with_verb(self.verb)),
+            (None, label) => write!(f, "{}", label.with_verb(self.verb)),
+        }
+    }
+}
+
+/// Everything before the elapsed time of a finishing line
+struct Prefix<'a> {
+    depth: usize,
+    group: Option<&'a str>,
+    label: Label<'a>,
+    id: Option<u64>,
+}
+
+impl fmt::Display for Prefix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.id {
+            Some(id) => write!(
+                f,
+                "{} (#{}) took",
+                Headline::new(self.depth, self.group, self.label, "finished"),
+                id
+            ),
+            None => write!(
+                f,
+                "{}",
+                Headline::new(self.depth, self.group, self.label, "took")
+            ),
+        }
+    }
+}
+
+/// Hand an output line to the reporter, tagged with its level if one was given
+///
+/// The arguments are passed along unformatted (the default reporter writes them
+/// straight to the locked stderr handle), no line is assembled on the heap first
+fn emit(
+    reporter: &reporter::Current,
+    level: Option<Level>,
+    metadata: Option<&Metadata>,
+    line: fmt::Arguments,
+) {
+    let metadata = MetadataTag(metadata);
+    if !to_logger(reporter, level, format_args!("{}{}", metadata, line)) {
+        match level {
+            Some(level) => reporter.line(format_args!("{}[{}] {}", metadata, level, line)),
+            None => reporter.line(format_args!("{}{}", metadata, line)),
+        }
+    }
+}
+
+/// The metadata a line starts with, if any
+struct MetadataTag<'a>(Option<&'a Metadata>);
+
+impl fmt::Display for MetadataTag<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(metadata) => write!(f, "{} ", metadata),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What was measured besides the elapsed time, after it:
+/// ` (user 250 µs, sys 60 µs) (12 allocs, 1.5 KiB)`
+struct Extra {
+    unit: Unit,
+    cpu: Option<CpuTime>,
+    #[cfg(feature = "allocations")]
+    allocs: Option<Allocations>,
+}
+
+impl fmt::Display for Extra {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(cpu) = self.cpu {
+            write!(
+                f,
+                " (user {}, sys {})",
+                self.unit.display(cpu.user),
+                self.unit.display(cpu.system)
+            )?;
+        }
+        #[cfg(feature = "allocations")]
+        if let Some(allocs) = self.allocs {
+            write!(f, " ({})", allocs)?;
+        }
+        Ok(())
+    }
+}
+
+/// The slowest samples by their index, after the aggregate of an iterator's
+/// items: `, slowest #412 35.1ms, #7 20.3ms`
+struct Slowest<'a> {
+    samples: &'a [Duration],
+    count: usize,
+}
+
+impl fmt::Display for Slowest<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.count == 0 {
+            return Ok(());
+        }
+        let mut indexed: Vec<_> = self.samples.iter().enumerate().collect();
+        // Stable, so the earliest of equally slow items comes first
+        indexed.sort_by(|a, b| b.1.cmp(a.1));
+        write!(f, ", slowest")?;
+        for (i, (index, elapsed)) in indexed.into_iter().take(self.count).enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{} #{} {:?}", separator, index, elapsed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Like [`emit`], for lines that carry their level themselves (JSON objects)
+fn emit_untagged(reporter: &reporter::Current, level: Option<Level>, line: fmt::Arguments) {
+    if !to_logger(reporter, level, line) {
+        reporter.line(line);
+    }
+}
+
+/// Whether the line went to the logger (or was left to the span), in place of
+/// the default reporter
+fn to_logger(reporter: &reporter::Current, level: Option<Level>, line: fmt::Arguments) -> bool {
+    // Unless another reporter was set, the logger takes the place of stderr
+    #[cfg(feature = "log")]
+    if reporter.is_default() {
+        let level = level.map_or(log::Level::Info, log::Level::from);
+        log::log!(target: "timeit", level, "{}", line);
+        return true;
+    }
+    // The span carries the measurement instead
+    #[cfg(all(feature = "tracing", not(feature = "log")))]
+    if reporter.is_default() {
+        return true;
+    }
+    let _ = (reporter, level, line);
+    false
+}
+
+#[cfg(all(test, not(feature = "disabled")))]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::fmt::Write;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts the allocations made by each thread
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    /// A fixed-size line buffer on the stack
+    struct StackLine {
+        buf: [u8; 128],
+        len: usize,
+    }
+
+    impl Write for StackLine {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.buf
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(not(any(
+        feature = "registry",
+        feature = "observability",
+        feature = "prometheus",
+        feature = "opentelemetry"
+    )))]
+    fn test_single_run_does_not_allocate() {
+        fn fetch() -> Result<u32, ()> {
+            Ok(42)
+        }
+        // Quiet, as the test harness captures stderr into a growing buffer
+        let timed = allocations(|| {
+            let _ = crate::timeit!(fetch(); quiet = true; correlate = true);
+        });
+        assert_eq!(timed, 0);
+    }
+
+    #[test]
+    fn test_path_name() {
+        fn compact(raw: &str) -> String {
+            // Sized generously, the unused tail is all zeros
+            let name: [u8; 64] = path_name(raw);
+            let name = &name[..path_len(raw)];
+            String::from_utf8(name.to_vec()).unwrap()
+        }
+        assert_eq!(compact("my_mod :: slow_fn"), "my_mod::slow_fn");
+        assert_eq!(compact("str :: parse :: < u64 >"), "str::parse::<u64>");
+        assert_eq!(
+            compact("Cow :: < 'static str > :: from"),
+            "Cow::<'static str>::from"
+        );
+        assert_eq!(compact("parse::<u64>"), "parse::<u64>");
+    }
+
+    #[test]
+    fn test_line_formats_in_place() {
+        let mut line = StackLine {
+            buf: [0; 128],
+            len: 0,
+        };
+        let prefix = Prefix {
+            depth: 0,
+            group: None,
+            label: Label::Function("fetch"),
+            id: Some(7),
+        };
+        let formatted = allocations(|| {
+            let elapsed = Unit::Auto.display(Duration::from_micros(12_040));
+            write!(line, "{} {} ({})", prefix, elapsed, Outcome::Ok).unwrap();
+        });
+        assert_eq!(formatted, 0);
+        assert_eq!(
+            std::str::from_utf8(&line.buf[..line.len]),
+            Ok("'fetch' finished (#7) took 12.0 ms (Ok)")
+        );
+    }
+}
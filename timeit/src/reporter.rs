@@ -0,0 +1,153 @@
+//! Where measurements go, see [`Reporter`]
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+static REPORTER: RwLock<Option<Arc<dyn Reporter>>> = RwLock::new(None);
+
+/// Receives the measurements of `timeit!`, in place of printing them to stderr
+///
+/// Set one for the whole process with [`set_reporter`], or per call:
+/// ```
+/// use std::time::Duration;
+/// use timeit::{timeit, Reporter};
+///
+/// struct Metrics;
+///
+/// impl Reporter for Metrics {
+///     fn report(&self, label: &str, elapsed: Duration) {
+///         // histogram!(label).record(elapsed) ...
+///     }
+/// }
+///
+/// static METRICS: Metrics = Metrics;
+///
+/// fn fetch() -> u32 {
+///     42
+/// }
+/// assert_eq!(timeit!(fetch(); reporter = &METRICS), 42);
+/// ```
+pub trait Reporter: Send + Sync {
+    /// A finished measurement (the mean, over repeated runs). The label is empty
+    /// for anonymous expressions.
+    fn report(&self, label: &str, elapsed: Duration);
+
+    /// Each line `timeit!` would print (finished measurements, `correlate` start
+    /// lines), unless it's quiet. Ignored by default.
+    fn line(&self, line: fmt::Arguments) {
+        let _ = line;
+    }
+}
+
+impl<F: Fn(&str, Duration) + Send + Sync> Reporter for F {
+    fn report(&self, label: &str, elapsed: Duration) {
+        self(label, elapsed)
+    }
+}
+
+/// Prints every line to stderr, the reporter used unless another one is set
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stderr;
+
+impl Reporter for Stderr {
+    fn report(&self, _label: &str, _elapsed: Duration) {}
+
+    fn line(&self, line: fmt::Arguments) {
+        eprintln!("{}", line);
+    }
+}
+
+/// Send the measurements of every `timeit!` without a `reporter` option to
+/// `reporter` from now on, replacing the previous one
+pub fn set_reporter(reporter: impl Reporter + 'static) {
+    *REPORTER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(reporter));
+}
+
+/// The per-call reporter, or else the process-wide one
+///
+/// The process-wide one is cloned out of the lock, so a reporter (or an
+/// `on_complete` callback) can time things itself, or set another reporter.
+pub(crate) fn current(per_call: Option<&'static dyn Reporter>) -> Current {
+    match per_call {
+        Some(reporter) => Current::PerCall(reporter),
+        None => Current::Global(REPORTER.read().unwrap_or_else(|e| e.into_inner()).clone()),
+    }
+}
+
+/// Derefs to the reporter to use, see [`current`]
+pub(crate) enum Current {
+    PerCall(&'static dyn Reporter),
+    Global(Option<Arc<dyn Reporter>>),
+}
+
+impl Current {
+    /// Neither set per call nor with [`set_reporter`]
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    pub(crate) fn is_default(&self) -> bool {
+        matches!(self, Current::Global(global) if global.is_none())
+    }
+}
+
+impl Deref for Current {
+    type Target = dyn Reporter;
+
+    fn deref(&self) -> &(dyn Reporter + 'static) {
+        match self {
+            Current::PerCall(reporter) => *reporter,
+            Current::Global(global) => global.as_deref().unwrap_or(&Stderr),
+        }
+    }
+}
+
+/// Keeps whatever it's handed, for tests to look at
+#[cfg(test)]
+pub(crate) struct Collect {
+    reports: std::sync::Mutex<Vec<(String, Duration)>>,
+    lines: std::sync::Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl Collect {
+    pub(crate) const fn new() -> Self {
+        Self {
+            reports: std::sync::Mutex::new(Vec::new()),
+            lines: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The measurements reported so far, as labels and times
+    pub(crate) fn reports(&self) -> Vec<(String, Duration)> {
+        self.reports.lock().unwrap().clone()
+    }
+
+    /// The lines printed so far
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Reporter for Collect {
+    fn report(&self, label: &str, elapsed: Duration) {
+        let report = (label.to_string(), elapsed);
+        self.reports.lock().unwrap().push(report);
+    }
+
+    fn line(&self, line: fmt::Arguments) {
+        self.lines.lock().unwrap().push(line.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_releases_lock() {
+        let current = current(None);
+        // Which is what `set_reporter` from within a reporter needs
+        assert!(REPORTER.try_write().is_ok());
+        drop(current);
+    }
+}
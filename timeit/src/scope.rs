@@ -0,0 +1,66 @@
+//! Timing a whole scope, see [`timeit_scope!`](crate::timeit_scope)
+use std::borrow::Cow;
+
+use crate::intern;
+use crate::report::{Label, Timer};
+use crate::{Options, Outcome};
+
+/// Times from its creation until it's dropped, however the scope is left
+/// (falling off the end, `return`, `?` or a panic)
+///
+/// ```
+/// use timeit::TimeitGuard;
+///
+/// fn load() -> Result<u32, std::num::ParseIntError> {
+///     let _guard = TimeitGuard::new("load config");
+///     let value = "42".parse::<u32>()?;
+///     Ok(value)
+/// }
+/// assert_eq!(load(), Ok(42));
+/// ```
+/// > load config took 1.20 µs
+///
+/// Labels can be borrowed or owned. An owned label (`format!("GET {}", route)`)
+/// is copied once per distinct value and reused after that, so keep them to a
+/// bounded set like route names or tenant ids.
+#[must_use = "the scope is timed until the guard is dropped"]
+pub struct TimeitGuard<'a> {
+    timer: Option<Timer<'a>>,
+}
+
+impl<'a> TimeitGuard<'a> {
+    #[cfg_attr(any(feature = "tracy", feature = "opentelemetry"), track_caller)]
+    pub fn new(label: impl Into<Cow<'a, str>>) -> Self {
+        Self::with_options(label, &Options::default())
+    }
+
+    /// Apply the same [`Options`] as `timeit!` (only those that make sense
+    /// for a single run, like `threshold`, `level` or `correlate`)
+    #[cfg_attr(any(feature = "tracy", feature = "opentelemetry"), track_caller)]
+    pub fn with_options(label: impl Into<Cow<'a, str>>, opts: &Options<'a>) -> Self {
+        if !crate::is_enabled() {
+            return Self { timer: None };
+        }
+        let mut timer = Timer::start(Label::Described(intern::label(label)), opts);
+        timer.begin();
+        Self { timer: Some(timer) }
+    }
+
+    /// Report now, noting the outcome, rather than when dropped
+    #[doc(hidden)]
+    pub fn finish(mut self, outcome: Option<Outcome>) {
+        if let Some(mut timer) = self.timer.take() {
+            timer.end();
+            timer.finish(outcome);
+        }
+    }
+}
+
+impl Drop for TimeitGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(mut timer) = self.timer.take() {
+            timer.end();
+            timer.finish(None);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Latency SLO enforcement, see [`ensure_within!`](crate::ensure_within)
+//!
+//! Every check is counted per SLO, and each violation is handed to the handler
+//! registered with [`on_violation()`], which can bump a metric, emit an event or
+//! start shedding load. Without a handler, violations are printed.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use crate::options;
+
+type Handler = Arc<dyn Fn(&Violation) + Send + Sync>;
+
+static HANDLER: RwLock<Option<Handler>> = RwLock::new(None);
+static STATS: Mutex<BTreeMap<String, SloStats>> = Mutex::new(BTreeMap::new());
+
+/// A block that took longer than its SLO allows
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation<'a> {
+    pub slo: &'a str,
+    pub took: Duration,
+    pub budget: Duration,
+    /// Violations in a row, including this one, for handlers that only react
+    /// to sustained trouble (like shedding load)
+    pub consecutive: u64,
+}
+
+impl fmt::Display for Violation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SLO '{}' violated: took {} ms, over the budget of {} ms ({} in a row)",
+            self.slo,
+            self.took.as_millis(),
+            self.budget.as_millis(),
+            self.consecutive
+        )
+    }
+}
+
+/// Checks and violations of a single SLO so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SloStats {
+    pub checks: u64,
+    pub violations: u64,
+    /// Violations since the last check that made it in time
+    pub consecutive: u64,
+}
+
+/// Call `handler` for every violation from now on, replacing the previous one
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// static SHEDDING: AtomicBool = AtomicBool::new(false);
+///
+/// timeit::slo::on_violation(|violation| {
+///     if violation.consecutive >= 5 {
+///         SHEDDING.store(true, Ordering::Relaxed);
+///     }
+/// });
+/// ```
+pub fn on_violation(handler: impl Fn(&Violation) + Send + Sync + 'static) {
+    *HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(handler));
+}
+
+/// Statistics for an SLO, `None` if it was never checked
+pub fn stats(slo: &str) -> Option<SloStats> {
+    STATS.lock().unwrap_or_else(|e| e.into_inner()).get(slo).copied()
+}
+
+/// Count a check of `slo`, handling the violation if it `took` too long
+#[doc(hidden)]
+pub fn check(slo: &str, budget: Duration, took: Duration) {
+    let violated = took > budget;
+    let consecutive = {
+        let mut stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = match stats.get_mut(slo) {
+            Some(stats) => stats,
+            None => stats.entry(slo.to_owned()).or_default(),
+        };
+        stats.checks += 1;
+        if violated {
+            stats.violations += 1;
+            stats.consecutive += 1;
+        } else {
+            stats.consecutive = 0;
+        }
+        stats.consecutive
+    };
+    if !violated {
+        return;
+    }
+    let violation = Violation {
+        slo,
+        took,
+        budget,
+        consecutive,
+    };
+    // Called without the lock, so it can replace itself or check other SLOs
+    let handler = HANDLER.read().unwrap_or_else(|e| e.into_inner()).clone();
+    match handler {
+        Some(handler) => handler(&violation),
+        None if !options::is_quiet() => eprintln!("{}", violation),
+        None => {}
+    }
+}
@@ -0,0 +1,141 @@
+//! Measurements sent to StatsD over UDP, see [`StatsdReporter`]
+use std::fmt::Write;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::Reporter;
+
+/// Sends a StatsD `timing` metric per measurement, named after its label, to
+/// a StatsD server or the Datadog agent
+/// ```no_run
+/// use timeit::{timeit, StatsdReporter};
+///
+/// let statsd = StatsdReporter::new("127.0.0.1:8125")?
+///     .with_prefix("checkout.")
+///     .with_tag("env", "prod");
+/// timeit::set_reporter(statsd);
+/// # fn fetch_cart() {}
+/// timeit!(fetch_cart());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+/// > checkout.fetch_cart:12.042|ms|#env:prod
+///
+/// Tags use the DogStatsD `|#key:value` extension, which plain StatsD servers
+/// don't understand, so leave them out for those. Characters StatsD gives a
+/// meaning to (`:`, `|`, `@`, `#`, `,` and whitespace) are replaced with `_`
+/// in names and tags, and anonymous expressions aren't sent.
+///
+/// Like StatsD clients usually are, it's fire-and-forget: a metric that
+/// can't be sent right away (the socket buffer is full, nothing listens on
+/// the port) is dropped, and never holds up the code being timed.
+#[derive(Debug)]
+pub struct StatsdReporter {
+    socket: UdpSocket,
+    prefix: String,
+    /// `|#key:value,...`, or empty
+    tags: String,
+}
+
+impl StatsdReporter {
+    /// Send to `addr`, usually port 8125 of localhost or of the agent's host
+    pub fn new(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to send to"))?;
+        let local = match addr.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind((local, 0))?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            prefix: String::new(),
+            tags: String::new(),
+        })
+    }
+
+    /// Start every metric name with `prefix`, like `myapp.`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = sanitized(prefix);
+        self
+    }
+
+    /// Tag every metric with `key:value`
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        let separator = if self.tags.is_empty() { "|#" } else { "," };
+        let _ = write!(
+            self.tags,
+            "{}{}:{}",
+            separator,
+            sanitized(key),
+            sanitized(value)
+        );
+        self
+    }
+
+    /// `name:12.042|ms|#key:value`
+    fn metric(&self, label: &str, elapsed: Duration) -> String {
+        format!(
+            "{}{}:{}|ms{}",
+            self.prefix,
+            sanitized(label),
+            elapsed.as_secs_f64() * 1e3,
+            self.tags
+        )
+    }
+}
+
+impl Reporter for StatsdReporter {
+    fn report(&self, label: &str, elapsed: Duration) {
+        if label.is_empty() {
+            return;
+        }
+        let _ = self.socket.send(self.metric(label, elapsed).as_bytes());
+    }
+}
+
+fn sanitized(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statsd() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let statsd = StatsdReporter::new(server.local_addr().unwrap())
+            .unwrap()
+            .with_prefix("app.")
+            .with_tag("env", "prod")
+            .with_tag("region", "eu west|1");
+
+        statsd.report("", Duration::from_millis(1));
+        statsd.report("GET /users/:id", Duration::from_micros(12_250));
+        let mut buf = [0; 512];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf[..len]).unwrap(),
+            "app.GET_/users/_id:12.25|ms|#env:prod,region:eu_west_1"
+        );
+
+        let plain = StatsdReporter::new(server.local_addr().unwrap()).unwrap();
+        plain.report("fetch", Duration::from_secs(2));
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"fetch:2000|ms");
+    }
+}
@@ -0,0 +1,163 @@
+//! Output lines laid out by the caller, see [`Options::fmt`](crate::Options::fmt)
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Outcome, Unit};
+
+/// Everything `{...}` can stand for
+const PLACEHOLDERS: [&str; 7] = [
+    "name",
+    "elapsed",
+    "elapsed_ms",
+    "elapsed_us",
+    "elapsed_ns",
+    "outcome",
+    "group",
+];
+
+/// Whether every placeholder of `template` is known, and every brace is
+/// either part of one or doubled (`{{`, `}}`)
+pub const fn is_valid(template: &str) -> bool {
+    let bytes = template.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' if i + 1 < bytes.len() && bytes[i + 1] == b'{' => i += 2,
+            b'}' if i + 1 < bytes.len() && bytes[i + 1] == b'}' => i += 2,
+            b'{' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < bytes.len() && bytes[end] != b'}' {
+                    end += 1;
+                }
+                if end == bytes.len() || !is_placeholder(bytes, start, end) {
+                    return false;
+                }
+                i = end + 1;
+            }
+            b'}' => return false,
+            _ => i += 1,
+        }
+    }
+    true
+}
+
+const fn is_placeholder(bytes: &[u8], start: usize, end: usize) -> bool {
+    let mut p = 0;
+    'placeholders: while p < PLACEHOLDERS.len() {
+        let name = PLACEHOLDERS[p].as_bytes();
+        p += 1;
+        if name.len() != end - start {
+            continue;
+        }
+        let mut i = 0;
+        while i < name.len() {
+            if name[i] != bytes[start + i] {
+                continue 'placeholders;
+            }
+            i += 1;
+        }
+        return true;
+    }
+    false
+}
+
+/// A measurement written out following a template
+pub(crate) struct Templated<'a> {
+    pub(crate) template: &'a str,
+    pub(crate) name: &'a str,
+    pub(crate) group: Option<&'a str>,
+    pub(crate) elapsed: Duration,
+    pub(crate) unit: Unit,
+    pub(crate) outcome: Option<Outcome>,
+}
+
+impl Templated<'_> {
+    fn placeholder(&self, f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+        match name {
+            "name" => f.write_str(self.name),
+            "elapsed" => write!(f, "{}", self.unit.display(self.elapsed)),
+            "elapsed_ms" => write!(f, "{:.3}", self.elapsed.as_secs_f64() * 1e3),
+            "elapsed_us" => write!(f, "{}", self.elapsed.as_micros()),
+            "elapsed_ns" => write!(f, "{}", self.elapsed.as_nanos()),
+            "outcome" => match self.outcome {
+                Some(outcome) => write!(f, "{}", outcome),
+                None => Ok(()),
+            },
+            "group" => f.write_str(self.group.unwrap_or_default()),
+            // Only templates set at runtime can get here, keep it as written
+            name => write!(f, "{{{}}}", name),
+        }
+    }
+}
+
+impl fmt::Display for Templated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = self.template;
+        while let Some(brace) = rest.find(['{', '}']) {
+            f.write_str(&rest[..brace])?;
+            let after = &rest[brace + 1..];
+            match (&rest[brace..=brace], after.chars().next()) {
+                ("{", Some('{')) | ("}", Some('}')) => {
+                    f.write_str(&rest[brace..=brace])?;
+                    rest = &after[1..];
+                }
+                ("{", _) => match after.find('}') {
+                    Some(end) => {
+                        self.placeholder(f, &after[..end])?;
+                        rest = &after[end + 1..];
+                    }
+                    None => {
+                        f.write_str(&rest[brace..])?;
+                        rest = "";
+                    }
+                },
+                _ => {
+                    f.write_str("}")?;
+                    rest = after;
+                }
+            }
+        }
+        f.write_str(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("[perf] {name}: {elapsed_ms}ms"));
+        assert!(is_valid("{group}/{name} {elapsed} {outcome} {{raw}}"));
+        assert!(is_valid("no placeholders"));
+        assert!(!is_valid("{nme}"));
+        assert!(!is_valid("{name"));
+        assert!(!is_valid("name}"));
+    }
+
+    #[test]
+    fn test_templated() {
+        let line = |template| {
+            Templated {
+                template,
+                name: "fetch",
+                group: None,
+                elapsed: Duration::from_micros(12_345),
+                unit: Unit::Auto,
+                outcome: Some(Outcome::Err),
+            }
+            .to_string()
+        };
+        assert_eq!(
+            line("[perf] {name}: {elapsed_ms}ms"),
+            "[perf] fetch: 12.345ms"
+        );
+        assert_eq!(
+            line("{name} {elapsed} {elapsed_us} {elapsed_ns} {outcome}"),
+            "fetch 12.3 ms 12345 12345000 Err"
+        );
+        assert_eq!(line("{{{name}}} [{group}]"), "{fetch} []");
+        assert_eq!(line("{unknown} {name"), "{unknown} {name");
+    }
+}